@@ -29,19 +29,16 @@ pub fn recomposer(bits : &Vec<Ciphertext>, encoding_out : &Encoding, server_key
     assert_eq!(l, 4);
     let result = server_key.simple_sum(&bits_with_new_modulo);
     match result{
-        Ciphertext::EncodingEncrypted(c, _) =>{Ciphertext::EncodingEncrypted(c, encoding_out.clone())},
-        _ => {panic!()}
+        Ciphertext::EncodingEncrypted(c, _, variance) =>{Ciphertext::EncodingEncrypted(c, encoding_out.clone(), variance)},
+        Ciphertext::Trivial(value, _) => {Ciphertext::Trivial(value, encoding_out.clone())}
     }          
 }
 
 
 
 
-pub fn decomposer(input : &Ciphertext, encoding_out : &Encoding, server_key : &ServerKey, client_key_debug : &ClientKey) -> Vec<Ciphertext>{
-    let encoding_in = match input{
-        Ciphertext::EncodingEncrypted(_, encoding_in) => encoding_in,
-        _ => panic!("No encoding with trivial ciphertexts")
-    };
+pub fn decomposer(inputs : &[Ciphertext], encoding_out : &Encoding, server_key : &ServerKey, client_key_debug : &ClientKey) -> Vec<Vec<Ciphertext>>{
+    let encoding_in = inputs[0].get_encoding();
     let mut o = encoding_in.get_origin_modulus();
     assert!(o & (o-1) == 0); //check that o is a power of two
     //count the number of bits
@@ -55,12 +52,13 @@ pub fn decomposer(input : &Ciphertext, encoding_out : &Encoding, server_key : &S
     }
 
 
-    let mut result = server_key.mvb(
-        &input, 
+    //the inputs are decomposed in parallel
+    let mut results = server_key.mvb_batch(
+        inputs, 
         &vec![encoding_out.clone();l], 
         &functions,
     );
 
-    result.reverse();
-    result
+    results.iter_mut().for_each(|result| result.reverse());
+    results
 }
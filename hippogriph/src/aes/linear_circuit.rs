@@ -27,8 +27,9 @@ impl LinearCircuit {
         
         //check and dimensioning of the containers
         assert_eq!(self.x.len(), header[0]);
-        self.t = vec![Ciphertext::Trivial(0);header[2]];
-        self.y = vec![Ciphertext::Trivial(0);header[4]];
+        let zero = server_key.trivial_encrypt(0, &Encoding::parity_encoding());
+        self.t = vec![zero.clone();header[2]];
+        self.y = vec![zero;header[4]];
         let (offset_x, offset_t, offset_y) = (header[1], header[3], header[5]);
 
         for line in lines {
@@ -42,13 +43,13 @@ impl LinearCircuit {
                                   else if elmts[4].contains('y'){   &self.y[elmts[4][1..].parse::<usize>().unwrap() - offset_y]    }
                                   else {    panic!()    };
             if elmts[0].contains('y'){    
-                self.y[elmts[0][1..].parse::<usize>().unwrap() - offset_y] = server_key.simple_sum(&vec![op1.to_owned(), op2.to_owned()]);
+                self.y[elmts[0][1..].parse::<usize>().unwrap() - offset_y] = server_key.simple_sum(&[op1.to_owned(), op2.to_owned()]);
                 if elmts[3] == "XNOR" {
                     self.y[elmts[0][1..].parse::<usize>().unwrap() - offset_y] = server_key.simple_plaintext_sum(&self.y[elmts[0][1..].parse::<usize>().unwrap()] , 1, 2);
                 }
             }
             else if elmts[0].contains('t'){  
-                self.t[elmts[0][1..].parse::<usize>().unwrap() - offset_t] = server_key.simple_sum(&vec![op1.to_owned(), op2.to_owned()]);
+                self.t[elmts[0][1..].parse::<usize>().unwrap() - offset_t] = server_key.simple_sum(&[op1.to_owned(), op2.to_owned()]);
                 if elmts[3] == "XNOR" {
                     self.t[elmts[0][1..].parse::<usize>().unwrap() - offset_t] = server_key.simple_plaintext_sum(&self.t[elmts[0][1..].parse::<usize>().unwrap()- offset_t] , 1, 2);
                 }                
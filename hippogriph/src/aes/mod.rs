@@ -2,7 +2,7 @@ use std::{fs::{File, OpenOptions}, time::{Instant, SystemTime, UNIX_EPOCH}};
 use std::io::{self, Write};
 
 use clear::clear_sub_bytes;
-use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use tfhe::{core_crypto::prelude::DynamicDistribution, odd::prelude::*};
 
 use crate::aes::aes_utils::{pretty_print_clear, u8_to_vec_bool_integer};
@@ -13,6 +13,8 @@ mod aes_utils;
 mod linear_circuit;
 mod casts;
 mod clear;
+#[cfg(test)]
+mod s_box_circuit;
 
 
 pub struct AESStateBoolean{
@@ -69,11 +71,7 @@ impl AESStateArithmetic{
 
     pub fn aes_decomposer(&self, server_key : &ServerKey, client_key_debug : &ClientKey) -> AESStateBoolean{
         AESStateBoolean{
-            bits : self.nibbles
-            //.iter()
-            .par_iter() //select the line to select parallelization
-            .map(|x| decomposer(x, &Encoding::parity_encoding(), server_key, client_key_debug))
-            .collect::<Vec<Vec<Ciphertext>>>()
+            bits : decomposer(&self.nibbles, &Encoding::parity_encoding(), server_key, client_key_debug)
             .concat()
         }
      }
@@ -82,19 +80,13 @@ impl AESStateArithmetic{
 
 
 
-fn sub_bytes(state : &AESStateArithmetic, server_key:&ServerKey, client_key_debug : &ClientKey) -> AESStateArithmetic{
+fn sub_bytes(state : &AESStateArithmetic, server_key:&ServerKey) -> AESStateArithmetic{
     assert_eq!(state.nibbles.len(), 32);
     AESStateArithmetic{
-        nibbles : (0..16)
-                .into_par_iter()    //comment this line to activate parallelisation
-                .map(|i| (i, state.nibbles[i*2..(i+1)*2].to_vec()))
-                .map(|(i, v)| server_key.full_tree_bootstrapping(&v, 
-                                                                        &vec![state.encoding.clone();2],
-                                                                        256,
-                                                                        &clear_sub_bytes, 
-                                                                        client_key_debug,
-                                                                        i == 0))
-                .collect::<Vec<Vec<Ciphertext>>>()
+        nibbles : server_key.tree_bootstrapping_batch(&state.nibbles.chunks(2).map(|v| v.to_vec()).collect::<Vec<Vec<Ciphertext>>>(),
+                                                &vec![state.encoding.clone();2],
+                                                256,
+                                                &clear_sub_bytes)
                 .concat(),
         encoding : state.encoding.clone()
     }
@@ -186,7 +178,7 @@ pub fn run_aes(state: &AESStateBoolean, server_key:&ServerKey, aes_key : Vec<boo
     for r in 0..9{
         println!("TIMING START_ROUND {} {:?}", r+1, SystemTime::now().duration_since(UNIX_EPOCH).unwrap());
         println!("Round {}", r + 1);
-        state_arith = sub_bytes(&state_arith, server_key, client_key_debug);
+        state_arith = sub_bytes(&state_arith, server_key);
         println!("TIMING POST_SUB_BYTES {} {:?}", r+1, SystemTime::now().duration_since(UNIX_EPOCH).unwrap());
         print_debug_arith(&state_arith, "");
         
@@ -213,7 +205,7 @@ pub fn run_aes(state: &AESStateBoolean, server_key:&ServerKey, aes_key : Vec<boo
         print_debug_arith(&state_arith, expected[r+1]);
         
     }
-    state_arith = sub_bytes(&state_arith, server_key, client_key_debug);
+    state_arith = sub_bytes(&state_arith, server_key);
     
     
     state_bool = state_arith.aes_decomposer(&server_key, &client_key_debug);
@@ -228,6 +220,9 @@ pub fn run_aes(state: &AESStateBoolean, server_key:&ServerKey, aes_key : Vec<boo
 
 
 
+/// Parameters for 40 bits of security. According to `CustomOddParameters::max_supported_norm`,
+/// the bootstrappings of nibbles (p = 17) fail with probability below 2^-17 for linear
+/// combinations of 2-norm up to about 33 (and about 2^-18 for a single ciphertext).
 pub const PARAMETERS_40: CustomOddParameters = CustomOddParameters {
     lwe_dimension: LweDimension(754),
     glwe_dimension: GlweDimension(1),
@@ -244,6 +239,9 @@ pub const PARAMETERS_40: CustomOddParameters = CustomOddParameters {
 
 
 
+/// Parameters for 128 bits of security. According to `CustomOddParameters::max_supported_norm`,
+/// the bootstrappings of nibbles (p = 17) fail with probability below 2^-40 for linear
+/// combinations of 2-norm up to about 5000.
 pub const PARAMETERS_128: CustomOddParameters = CustomOddParameters {
     lwe_dimension: LweDimension(900),
     glwe_dimension: GlweDimension(1),
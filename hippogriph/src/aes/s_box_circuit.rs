@@ -0,0 +1,197 @@
+use tfhe::odd::prelude::*;
+
+use crate::aes::clear::clear_sub_bytes;
+
+
+
+/// The S-box of Boyar and Peralta (the three layers of the `clear` module) as a Boolean circuit,
+/// from the 8 bits of a byte to the 8 bits of its image, the most significant bit first.
+pub fn s_box_circuit() -> BooleanCircuit{
+    let mut circuit = BooleanCircuit::new(8);
+    let x : Vec<Wire> = (0..8).map(|i| circuit.input(i)).collect();
+    let y = pre_circuit(&mut circuit, &x);
+    let z = s_box_boyar(&mut circuit, &y);
+    let s = post_circuit(&mut circuit, &z);
+    s.into_iter().for_each(|wire| circuit.add_output(wire));
+    circuit
+}
+
+
+
+/// Bits of a byte, the most significant first, as the inputs of [`s_box_circuit`]
+fn byte_to_bits(byte : u64) -> Vec<bool>{
+    (0..8).map(|i| (byte >> (7 - i)) & 1 == 1).collect()
+}
+
+
+/// Linear layer of [`clear_pre_circuit`](super::clear::clear_pre_circuit)
+fn pre_circuit(circuit : &mut BooleanCircuit, x : &[Wire]) -> Vec<Wire>{
+    let y14 = circuit.xor(x[3], x[5]);
+    let y13 = circuit.xor(x[0], x[6]);
+    let y9 = circuit.xor(x[0], x[3]);
+    let y8 = circuit.xor(x[0], x[5]);
+    let t0 = circuit.xor(x[1], x[2]);
+    let y1 = circuit.xor(t0, x[7]);
+    let y4 = circuit.xor(y1, x[3]);
+    let y12 = circuit.xor(y13, y14);
+    let y2 = circuit.xor(y1, x[0]);
+    let y5 = circuit.xor(y1, x[6]);
+    let y3 = circuit.xor(y5, y8);
+    let t1 = circuit.xor(x[4], y12);
+    let y15 = circuit.xor(t1, x[5]);
+    let y20 = circuit.xor(t1, x[1]);
+    let y6 = circuit.xor(y15, x[7]);
+    let y10 = circuit.xor(y15, t0);
+    let y11 = circuit.xor(y20, y9);
+    let y7 = circuit.xor(x[7], y11);
+    let y17 = circuit.xor(y10, y11);
+    let y19 = circuit.xor(y10, y8);
+    let y16 = circuit.xor(t0, y11);
+    let y21 = circuit.xor(y13, y16);
+    let y18 = circuit.xor(x[0], y16);
+    vec![x[7], y1, y2, y3, y4, y5, y6, y7, y8, y9, y10, y11, y12, y13, y14, y15, y16, y17, y18, y19, y20, y21]
+}
+
+
+/// Non-linear layer of [`clear_s_box_boyar`](super::clear::clear_s_box_boyar)
+fn s_box_boyar(circuit : &mut BooleanCircuit, y : &[Wire]) -> Vec<Wire>{
+    let t2 = circuit.and(y[12], y[15]);
+    let t3 = circuit.and(y[3], y[6]);
+    let t4 = circuit.xor(t3, t2);
+    let t5 = circuit.and(y[4], y[0]);
+    let t6 = circuit.xor(t5, t2);
+    let t7 = circuit.and(y[13], y[16]);
+    let t8 = circuit.and(y[5], y[1]);
+    let t9 = circuit.xor(t8, t7);
+    let t10 = circuit.and(y[2], y[7]);
+    let t11 = circuit.xor(t10, t7);
+    let t12 = circuit.and(y[9], y[11]);
+    let t13 = circuit.and(y[14], y[17]);
+    let t14 = circuit.xor(t13, t12);
+    let t15 = circuit.and(y[8], y[10]);
+    let t16 = circuit.xor(t15, t12);
+    let t17 = circuit.xor(t4, t14);
+    let t18 = circuit.xor(t6, t16);
+    let t19 = circuit.xor(t9, t14);
+    let t20 = circuit.xor(t11, t16);
+    let t21 = circuit.xor(t17, y[20]);
+    let t22 = circuit.xor(t18, y[19]);
+    let t23 = circuit.xor(t19, y[21]);
+    let t24 = circuit.xor(t20, y[18]);
+    let t25 = circuit.xor(t21, t22);
+    let t26 = circuit.and(t21, t23);
+    let t27 = circuit.xor(t24, t26);
+    let t28 = circuit.and(t25, t27);
+    let t29 = circuit.xor(t28, t22);
+    let t30 = circuit.xor(t23, t24);
+    let t31 = circuit.xor(t22, t26);
+    let t32 = circuit.and(t31, t30);
+    let t33 = circuit.xor(t32, t24);
+    let t34 = circuit.xor(t23, t33);
+    let t35 = circuit.xor(t27, t33);
+    let t36 = circuit.and(t24, t35);
+    let t37 = circuit.xor(t36, t34);
+    let t38 = circuit.xor(t27, t36);
+    let t39 = circuit.and(t29, t38);
+    let t40 = circuit.xor(t25, t39);
+    let t41 = circuit.xor(t40, t37);
+    let t42 = circuit.xor(t29, t33);
+    let t43 = circuit.xor(t29, t40);
+    let t44 = circuit.xor(t33, t37);
+    let t45 = circuit.xor(t42, t41);
+    let z0 = circuit.and(t44, y[15]);
+    let z1 = circuit.and(t37, y[6]);
+    let z2 = circuit.and(t33, y[0]);
+    let z3 = circuit.and(t43, y[16]);
+    let z4 = circuit.and(t40, y[1]);
+    let z5 = circuit.and(t29, y[7]);
+    let z6 = circuit.and(t42, y[11]);
+    let z7 = circuit.and(t45, y[17]);
+    let z8 = circuit.and(t41, y[10]);
+    let z9 = circuit.and(t44, y[12]);
+    let z10 = circuit.and(t37, y[3]);
+    let z11 = circuit.and(t33, y[4]);
+    let z12 = circuit.and(t43, y[13]);
+    let z13 = circuit.and(t40, y[5]);
+    let z14 = circuit.and(t29, y[2]);
+    let z15 = circuit.and(t42, y[9]);
+    let z16 = circuit.and(t45, y[14]);
+    let z17 = circuit.and(t41, y[8]);
+    vec![z0, z1, z2, z3, z4, z5, z6, z7, z8, z9, z10, z11, z12, z13, z14, z15, z16, z17]
+}
+
+
+/// Linear layer of [`clear_post_circuit`](super::clear::clear_post_circuit)
+fn post_circuit(circuit : &mut BooleanCircuit, x : &[Wire]) -> Vec<Wire>{
+    let t46 = circuit.xor(x[15], x[16]);
+    let t47 = circuit.xor(x[10], x[11]);
+    let t48 = circuit.xor(x[5], x[13]);
+    let t49 = circuit.xor(x[9], x[10]);
+    let t50 = circuit.xor(x[2], x[12]);
+    let t51 = circuit.xor(x[2], x[5]);
+    let t52 = circuit.xor(x[7], x[8]);
+    let t53 = circuit.xor(x[0], x[3]);
+    let t54 = circuit.xor(x[6], x[7]);
+    let t55 = circuit.xor(x[16], x[17]);
+    let t56 = circuit.xor(x[12], t48);
+    let t57 = circuit.xor(t50, t53);
+    let t58 = circuit.xor(x[4], t46);
+    let t59 = circuit.xor(x[3], t54);
+    let t60 = circuit.xor(t46, t57);
+    let t61 = circuit.xor(x[14], t57);
+    let t62 = circuit.xor(t52, t58);
+    let t63 = circuit.xor(t49, t58);
+    let t64 = circuit.xor(x[4], t59);
+    let t65 = circuit.xor(t61, t62);
+    let t66 = circuit.xor(x[1], t63);
+    let y0 = circuit.xor(t59, t63);
+    let y6 = circuit.xor(t56, t62);
+    let y6 = circuit.not(y6);
+    let y7 = circuit.xor(t48, t60);
+    let y7 = circuit.not(y7);
+    let t67 = circuit.xor(t64, t65);
+    let y3 = circuit.xor(t53, t66);
+    let y4 = circuit.xor(t51, t66);
+    let y5 = circuit.xor(t47, t65);
+    let y1 = circuit.xor(t64, y3);
+    let y1 = circuit.not(y1);
+    let y2 = circuit.xor(t55, t67);
+    let y2 = circuit.not(y2);
+    vec![y0, y1, y2, y3, y4, y5, y6, y7]
+}
+
+
+
+#[test]
+fn test_s_box_circuit(){
+    let circuit = s_box_circuit();
+    let compiled = circuit.compile(&CompilerOptions::default());
+    // the 119 gates fit in 74 gadgets of at most 4 inputs, the NOT gates being merged into the
+    // XOR gadgets they read
+    assert_eq!(compiled.gadget_count(), 74);
+
+    let backend = SimulatedServerKey::new();
+    let encoding = compiled.wire_encoding();
+    for byte in 0..256u64{
+        let bits = byte_to_bits(byte);
+        let expected = byte_to_bits(clear_sub_bytes(byte));
+        assert_eq!(circuit.evaluate(&bits), expected);
+
+        let inputs : Vec<_> = bits.iter().map(|b| backend.trivial_encrypt(*b as u64, &encoding)).collect();
+        let outputs : Vec<bool> = compiled.execute(&backend, &inputs).iter().map(|c| c.decrypt_trivial().unwrap() == 1).collect();
+        assert_eq!(outputs, expected);
+    }
+}
+
+
+#[test]
+fn test_s_box_circuit_encrypted(){
+    let (client_key, server_key) = gen_keys(&super::PARAMETERS_40);
+    let compiled = s_box_circuit().compile(&CompilerOptions::default());
+    let encoding = compiled.wire_encoding();
+    for byte in [0x00, 0x53, 0xff]{
+        let inputs : Vec<_> = byte_to_bits(byte).iter().map(|b| client_key.encrypt_arithmetic(*b as u64, &encoding)).collect();
+        let outputs : Vec<bool> = compiled.execute(&server_key, &inputs).iter().map(|c| client_key.decrypt(c) == 1).collect();
+        assert_eq!(outputs, byte_to_bits(clear_sub_bytes(byte)), "byte {byte:02x}");
+    }
+}
@@ -0,0 +1,206 @@
+//! A common interface to the homomorphic operators.
+//!
+//! It is implemented by the [`ServerKey`], and by the
+//! [`SimulatedServerKey`](crate::odd::simulation::SimulatedServerKey) which computes on clear
+//! values: a circuit written against [`OddBackend`] can be validated by the simulator before being
+//! run on encrypted data.
+
+use crate::odd::prelude::*;
+
+pub trait OddBackend {
+    fn try_trivial_encrypt(&self, message: u64, encoding: &Encoding) -> Result<Ciphertext, OddError>;
+
+    fn try_apply_lut_with_representatives(
+        &self,
+        input: &Ciphertext,
+        encoding_out: &Encoding,
+        representatives: &[u64],
+        f: &dyn Fn(u64) -> u64,
+    ) -> Result<Ciphertext, OddError>;
+
+    fn try_mvb_with_representatives(
+        &self,
+        input: &Ciphertext,
+        encodings_out: &[Encoding],
+        representatives: &[Vec<u64>],
+        fis: &[Box<dyn Fn(u64) -> u64>],
+    ) -> Result<Vec<Ciphertext>, OddError>;
+
+    fn try_full_tree_bootstrapping(
+        &self,
+        inputs: &[Ciphertext],
+        encodings_out: &[Encoding],
+        t: u64,
+        f: &dyn Fn(u64) -> u64,
+    ) -> Result<Vec<Ciphertext>, OddError>;
+
+    fn try_exec_gadget(&self, inputs: &[Ciphertext], enc_inter: &Encoding, enc_out: &Encoding) -> Result<Ciphertext, OddError>;
+
+    fn try_encoding_switching_mul_constant(&self, input: &Ciphertext, coefficient: u64) -> Result<Ciphertext, OddError>;
+
+    fn try_encoding_switching_sum_constant(&self, input: &Ciphertext, constant: u64, modulus: u64) -> Result<Ciphertext, OddError>;
+
+    fn try_simple_sum(&self, input: &[Ciphertext]) -> Result<Ciphertext, OddError>;
+
+    fn try_simple_plaintext_sum(&self, input: &Ciphertext, constant: u64, modulus: u64) -> Result<Ciphertext, OddError>;
+
+    fn try_simple_mul_constant(&self, input: &Ciphertext, coeff: u64, modulus: u64) -> Result<Ciphertext, OddError>;
+
+    fn try_simple_negation(&self, input: &Ciphertext) -> Result<Ciphertext, OddError>;
+
+    fn try_apply_lut(&self, input: &Ciphertext, encoding_out: &Encoding, f: &dyn Fn(u64) -> u64) -> Result<Ciphertext, OddError> {
+        let representatives = encoding_out.try_default_representatives()?;
+        self.try_apply_lut_with_representatives(input, encoding_out, &representatives, f)
+    }
+
+    fn try_mvb(&self, input: &Ciphertext, encodings_out: &[Encoding], fis: &[Box<dyn Fn(u64) -> u64>]) -> Result<Vec<Ciphertext>, OddError> {
+        let representatives = encodings_out.iter().map(Encoding::try_default_representatives).collect::<Result<Vec<_>, _>>()?;
+        self.try_mvb_with_representatives(input, encodings_out, &representatives, fis)
+    }
+
+    fn try_encoding_switching_lut(&self, input: &Ciphertext, encoding_out: &Encoding) -> Result<Ciphertext, OddError> {
+        self.try_apply_lut(input, encoding_out, &|x| x)
+    }
+
+    fn try_linear_combination(&self, input: &[Ciphertext], coefficients: &[u64], modulus: u64) -> Result<Ciphertext, OddError> {
+        if input.len() != coefficients.len() {
+            return Err(OddError::LengthMismatch { expected: input.len(), got: coefficients.len() });
+        }
+        let buffer = input
+            .iter()
+            .zip(coefficients)
+            .map(|(ct, coeff)| self.try_simple_mul_constant(ct, *coeff, modulus))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.try_simple_sum(&buffer)
+    }
+
+    fn trivial_encrypt(&self, message: u64, encoding: &Encoding) -> Ciphertext {
+        self.try_trivial_encrypt(message, encoding).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    fn apply_lut(&self, input: &Ciphertext, encoding_out: &Encoding, f: &dyn Fn(u64) -> u64) -> Ciphertext {
+        self.try_apply_lut(input, encoding_out, f).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    fn apply_lut_with_representatives(&self, input: &Ciphertext, encoding_out: &Encoding, representatives: &[u64], f: &dyn Fn(u64) -> u64) -> Ciphertext {
+        self.try_apply_lut_with_representatives(input, encoding_out, representatives, f).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    fn mvb(&self, input: &Ciphertext, encodings_out: &[Encoding], fis: &[Box<dyn Fn(u64) -> u64>]) -> Vec<Ciphertext> {
+        self.try_mvb(input, encodings_out, fis).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    fn mvb_with_representatives(&self, input: &Ciphertext, encodings_out: &[Encoding], representatives: &[Vec<u64>], fis: &[Box<dyn Fn(u64) -> u64>]) -> Vec<Ciphertext> {
+        self.try_mvb_with_representatives(input, encodings_out, representatives, fis).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    fn full_tree_bootstrapping(
+        &self,
+        inputs: &[Ciphertext],
+        encodings_out: &[Encoding],
+        t: u64,
+        f: &dyn Fn(u64) -> u64,
+    ) -> Vec<Ciphertext> {
+        self.try_full_tree_bootstrapping(inputs, encodings_out, t, f).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    fn exec_gadget(&self, inputs: &[Ciphertext], enc_inter: &Encoding, enc_out: &Encoding) -> Ciphertext {
+        self.try_exec_gadget(inputs, enc_inter, enc_out).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    fn encoding_switching_lut(&self, input: &Ciphertext, encoding_out: &Encoding) -> Ciphertext {
+        self.try_encoding_switching_lut(input, encoding_out).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    fn encoding_switching_mul_constant(&self, input: &Ciphertext, coefficient: u64) -> Ciphertext {
+        self.try_encoding_switching_mul_constant(input, coefficient).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    fn encoding_switching_sum_constant(&self, input: &Ciphertext, constant: u64, modulus: u64) -> Ciphertext {
+        self.try_encoding_switching_sum_constant(input, constant, modulus).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    fn simple_sum(&self, input: &[Ciphertext]) -> Ciphertext {
+        self.try_simple_sum(input).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    fn simple_plaintext_sum(&self, input: &Ciphertext, constant: u64, modulus: u64) -> Ciphertext {
+        self.try_simple_plaintext_sum(input, constant, modulus).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    fn simple_mul_constant(&self, input: &Ciphertext, coeff: u64, modulus: u64) -> Ciphertext {
+        self.try_simple_mul_constant(input, coeff, modulus).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    fn simple_negation(&self, input: &Ciphertext) -> Ciphertext {
+        self.try_simple_negation(input).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    fn linear_combination(&self, input: &[Ciphertext], coefficients: &[u64], modulus: u64) -> Ciphertext {
+        self.try_linear_combination(input, coefficients, modulus).unwrap_or_else(|err| panic!("{err}"))
+    }
+}
+
+impl OddBackend for ServerKey {
+    fn try_trivial_encrypt(&self, message: u64, encoding: &Encoding) -> Result<Ciphertext, OddError> {
+        Self::try_trivial_encrypt(self, message, encoding)
+    }
+
+    fn try_apply_lut_with_representatives(
+        &self,
+        input: &Ciphertext,
+        encoding_out: &Encoding,
+        representatives: &[u64],
+        f: &dyn Fn(u64) -> u64,
+    ) -> Result<Ciphertext, OddError> {
+        Self::try_apply_lut_with_representatives(self, input, encoding_out, representatives, f)
+    }
+
+    fn try_mvb_with_representatives(
+        &self,
+        input: &Ciphertext,
+        encodings_out: &[Encoding],
+        representatives: &[Vec<u64>],
+        fis: &[Box<dyn Fn(u64) -> u64>],
+    ) -> Result<Vec<Ciphertext>, OddError> {
+        Self::try_mvb_with_representatives(self, input, encodings_out, representatives, fis)
+    }
+
+    fn try_full_tree_bootstrapping(
+        &self,
+        inputs: &[Ciphertext],
+        encodings_out: &[Encoding],
+        t: u64,
+        f: &dyn Fn(u64) -> u64,
+    ) -> Result<Vec<Ciphertext>, OddError> {
+        Self::try_full_tree_bootstrapping(self, inputs, encodings_out, t, f)
+    }
+
+    fn try_exec_gadget(&self, inputs: &[Ciphertext], enc_inter: &Encoding, enc_out: &Encoding) -> Result<Ciphertext, OddError> {
+        Self::try_exec_gadget(self, inputs, enc_inter, enc_out)
+    }
+
+    fn try_encoding_switching_mul_constant(&self, input: &Ciphertext, coefficient: u64) -> Result<Ciphertext, OddError> {
+        Self::try_encoding_switching_mul_constant(self, input, coefficient)
+    }
+
+    fn try_encoding_switching_sum_constant(&self, input: &Ciphertext, constant: u64, modulus: u64) -> Result<Ciphertext, OddError> {
+        Self::try_encoding_switching_sum_constant(self, input, constant, modulus)
+    }
+
+    fn try_simple_sum(&self, input: &[Ciphertext]) -> Result<Ciphertext, OddError> {
+        Self::try_simple_sum(self, input)
+    }
+
+    fn try_simple_plaintext_sum(&self, input: &Ciphertext, constant: u64, modulus: u64) -> Result<Ciphertext, OddError> {
+        Self::try_simple_plaintext_sum(self, input, constant, modulus)
+    }
+
+    fn try_simple_mul_constant(&self, input: &Ciphertext, coeff: u64, modulus: u64) -> Result<Ciphertext, OddError> {
+        Self::try_simple_mul_constant(self, input, coeff, modulus)
+    }
+
+    fn try_simple_negation(&self, input: &Ciphertext) -> Result<Ciphertext, OddError> {
+        Self::try_simple_negation(self, input)
+    }
+}
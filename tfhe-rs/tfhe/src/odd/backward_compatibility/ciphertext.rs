@@ -0,0 +1,18 @@
+use tfhe_versionable::VersionsDispatch;
+
+use crate::odd::ciphertext::{Ciphertext, CompressedCiphertext, Encoding};
+
+#[derive(VersionsDispatch)]
+pub enum CiphertextVersions {
+    V0(Ciphertext),
+}
+
+#[derive(VersionsDispatch)]
+pub enum CompressedCiphertextVersions {
+    V0(CompressedCiphertext),
+}
+
+#[derive(VersionsDispatch)]
+pub enum EncodingVersions {
+    V0(Encoding),
+}
@@ -0,0 +1,8 @@
+use tfhe_versionable::VersionsDispatch;
+
+use crate::odd::client_key::ClientKey;
+
+#[derive(VersionsDispatch)]
+pub enum ClientKeyVersions {
+    V0(ClientKey),
+}
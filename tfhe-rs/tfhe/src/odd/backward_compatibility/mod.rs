@@ -0,0 +1,6 @@
+pub mod ciphertext;
+pub mod client_key;
+pub mod noise;
+pub mod parameters;
+pub mod public_key;
+pub mod server_key;
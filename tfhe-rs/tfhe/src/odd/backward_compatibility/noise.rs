@@ -0,0 +1,8 @@
+use tfhe_versionable::VersionsDispatch;
+
+use crate::odd::noise::NoiseVariance;
+
+#[derive(VersionsDispatch)]
+pub enum NoiseVarianceVersions {
+    V0(NoiseVariance),
+}
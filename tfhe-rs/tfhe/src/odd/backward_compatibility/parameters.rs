@@ -0,0 +1,8 @@
+use tfhe_versionable::VersionsDispatch;
+
+use crate::odd::parameters::CustomOddParameters;
+
+#[derive(VersionsDispatch)]
+pub enum CustomOddParametersVersions {
+    V0(CustomOddParameters),
+}
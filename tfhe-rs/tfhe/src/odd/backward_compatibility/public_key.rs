@@ -0,0 +1,13 @@
+use tfhe_versionable::VersionsDispatch;
+
+use crate::odd::public_key::{CompactPublicKey, PublicKey};
+
+#[derive(VersionsDispatch)]
+pub enum PublicKeyVersions {
+    V0(PublicKey),
+}
+
+#[derive(VersionsDispatch)]
+pub enum CompactPublicKeyVersions {
+    V0(CompactPublicKey),
+}
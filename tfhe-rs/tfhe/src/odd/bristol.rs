@@ -0,0 +1,409 @@
+//! Import and evaluation of circuits in the Bristol Fashion format.
+//!
+//! A Bristol Fashion file starts with the number of gates and of wires, then the number and the
+//! sizes (in bits) of the input values, and the same for the output values. The inputs are the
+//! first wires and the outputs the last ones. Each of the following lines is a gate:
+//! `<input count> <output count> <input wires> <output wires> <operation>`, with the operations
+//! XOR, AND, INV, EQ (a constant), EQW (a copy) and MAND (several ANDs).
+//!
+//! The circuits are evaluated on bits in the parity encoding:
+//! * XOR, INV, EQ and EQW are linear operations;
+//! * an AND switches each of its operands (once per wire) to a negacyclic binary encoding, the
+//!   only encodings of an odd p a bootstrapping of the parity encoding can reach, and evaluates a
+//!   gadget of these two encodings back to the parity encoding.
+
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::odd::prelude::*;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BristolGate {
+    Xor { a: usize, b: usize, out: usize },
+    And { a: usize, b: usize, out: usize },
+    Inv { a: usize, out: usize },
+    /// Assign a constant to `out`
+    Eq { value: bool, out: usize },
+    /// Copy `a` to `out`
+    Eqw { a: usize, out: usize },
+}
+
+impl BristolGate {
+    fn inputs(&self) -> Vec<usize> {
+        match *self {
+            Self::Xor { a, b, .. } | Self::And { a, b, .. } => vec![a, b],
+            Self::Inv { a, .. } | Self::Eqw { a, .. } => vec![a],
+            Self::Eq { .. } => vec![],
+        }
+    }
+
+    fn output(&self) -> usize {
+        match *self {
+            Self::Xor { out, .. }
+            | Self::And { out, .. }
+            | Self::Inv { out, .. }
+            | Self::Eq { out, .. }
+            | Self::Eqw { out, .. } => out,
+        }
+    }
+}
+
+/// A circuit in the Bristol Fashion format, parsed with [`str::parse`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct BristolCircuit {
+    wire_count: usize,
+    input_sizes: Vec<usize>,
+    output_sizes: Vec<usize>,
+    gates: Vec<BristolGate>,
+    // gadget evaluating the AND gates, searched once at parsing
+    and_gadget: Gadget,
+}
+
+fn parse_error(line: usize, message: impl Into<String>) -> OddError {
+    OddError::CircuitParse {
+        line,
+        message: message.into(),
+    }
+}
+
+fn parse_numbers(line: usize, text: &str) -> Result<Vec<usize>, OddError> {
+    text.split_whitespace()
+        .map(|word| {
+            word.parse::<usize>()
+                .map_err(|_| parse_error(line, format!("expected a number, got `{word}`")))
+        })
+        .collect()
+}
+
+/// Parse a line giving the number of values followed by their sizes
+fn parse_sizes(line: usize, text: &str) -> Result<Vec<usize>, OddError> {
+    let numbers = parse_numbers(line, text)?;
+    match numbers.split_first() {
+        Some((count, sizes)) if *count == sizes.len() => Ok(sizes.to_vec()),
+        _ => Err(parse_error(line, "expected the number of values followed by their sizes")),
+    }
+}
+
+/// Parse a gate line into one gate, or several for a MAND
+fn parse_gate(line: usize, text: &str) -> Result<Vec<BristolGate>, OddError> {
+    let (numbers, operation) = text
+        .rsplit_once(char::is_whitespace)
+        .ok_or_else(|| parse_error(line, "expected the wires and the operation of the gate"))?;
+    let numbers = parse_numbers(line, numbers)?;
+    let [input_count, output_count, ..] = numbers[..] else {
+        return Err(parse_error(line, "expected the numbers of inputs and outputs of the gate"));
+    };
+    let wires = &numbers[2..];
+    if wires.len() != input_count + output_count {
+        return Err(parse_error(
+            line,
+            format!("expected {} wires, got {}", input_count + output_count, wires.len()),
+        ));
+    }
+    let (inputs, outputs) = wires.split_at(input_count);
+    match (operation, inputs, outputs) {
+        ("XOR", &[a, b], &[out]) => Ok(vec![BristolGate::Xor { a, b, out }]),
+        ("AND", &[a, b], &[out]) => Ok(vec![BristolGate::And { a, b, out }]),
+        ("INV", &[a], &[out]) => Ok(vec![BristolGate::Inv { a, out }]),
+        ("EQW", &[a], &[out]) => Ok(vec![BristolGate::Eqw { a, out }]),
+        ("EQ", &[value], &[out]) if value < 2 => Ok(vec![BristolGate::Eq { value: value == 1, out }]),
+        ("EQ", &[_], &[_]) => Err(parse_error(line, "the constant of EQ should be 0 or 1")),
+        ("MAND", _, _) if inputs.len() == 2 * outputs.len() => {
+            let (a, b) = inputs.split_at(outputs.len());
+            Ok((0..outputs.len())
+                .map(|i| BristolGate::And { a: a[i], b: b[i], out: outputs[i] })
+                .collect())
+        }
+        ("XOR" | "AND" | "INV" | "EQW" | "EQ" | "MAND", _, _) => {
+            Err(parse_error(line, format!("wrong number of wires for {operation}")))
+        }
+        _ => Err(parse_error(line, format!("unknown operation `{operation}`"))),
+    }
+}
+
+impl FromStr for BristolCircuit {
+    type Err = OddError;
+
+    fn from_str(text: &str) -> Result<Self, OddError> {
+        // the line numbers start at 1, the blank lines being skipped
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+        let mut header = |what: &str| lines.next().ok_or_else(|| parse_error(0, format!("missing {what}")));
+
+        let (line, counts) = header("the numbers of gates and wires")?;
+        let counts = parse_numbers(line, counts)?;
+        let [gate_count, wire_count] = counts[..] else {
+            return Err(parse_error(line, "expected the numbers of gates and wires"));
+        };
+        let (line, inputs) = header("the sizes of the inputs")?;
+        let input_sizes = parse_sizes(line, inputs)?;
+        let (line, outputs) = header("the sizes of the outputs")?;
+        let output_sizes = parse_sizes(line, outputs)?;
+
+        let input_count: usize = input_sizes.iter().sum();
+        let output_count: usize = output_sizes.iter().sum();
+        if input_count + output_count > wire_count {
+            return Err(parse_error(
+                line,
+                format!("{input_count} inputs and {output_count} outputs do not fit in {wire_count} wires"),
+            ));
+        }
+
+        // every wire is assigned once, before being read
+        let mut assigned = vec![false; wire_count];
+        assigned[..input_count].iter_mut().for_each(|a| *a = true);
+        let mut gates = vec![];
+        let mut gate_lines = 0;
+        for (line, text) in lines {
+            for gate in parse_gate(line, text)? {
+                if let Some(wire) = gate.inputs().into_iter().find(|w| !assigned.get(*w).copied().unwrap_or(false)) {
+                    return Err(parse_error(line, format!("the wire {wire} is read before being assigned")));
+                }
+                let out = gate.output();
+                match assigned.get(out) {
+                    Some(false) => assigned[out] = true,
+                    Some(true) => return Err(parse_error(line, format!("the wire {out} is assigned twice"))),
+                    None => return Err(parse_error(line, format!("the wire {out} exceeds the {wire_count} wires"))),
+                }
+                gates.push(gate);
+            }
+            gate_lines += 1;
+        }
+        if gate_lines != gate_count {
+            return Err(parse_error(0, format!("expected {gate_count} gates, got {gate_lines}")));
+        }
+        if let Some(wire) = (wire_count - output_count..wire_count).find(|w| !assigned[*w]) {
+            return Err(parse_error(0, format!("the output wire {wire} is never assigned")));
+        }
+
+        Ok(Self {
+            wire_count,
+            input_sizes,
+            output_sizes,
+            gates,
+            and_gadget: and_gadget()?,
+        })
+    }
+}
+
+impl BristolCircuit {
+    pub fn wire_count(&self) -> usize {
+        self.wire_count
+    }
+
+    /// Sizes in bits of the input values
+    pub fn input_sizes(&self) -> &[usize] {
+        &self.input_sizes
+    }
+
+    /// Sizes in bits of the output values
+    pub fn output_sizes(&self) -> &[usize] {
+        &self.output_sizes
+    }
+
+    pub fn gates(&self) -> &[BristolGate] {
+        &self.gates
+    }
+
+    /// Number of AND gates, i.e. of gadgets of an execution
+    pub fn and_count(&self) -> usize {
+        self.gates.iter().filter(|gate| matches!(gate, BristolGate::And { .. })).count()
+    }
+
+    fn input_count(&self) -> usize {
+        self.input_sizes.iter().sum()
+    }
+
+    fn output_wires(&self) -> std::ops::Range<usize> {
+        self.wire_count - self.output_sizes.iter().sum::<usize>()..self.wire_count
+    }
+
+    /// Evaluate the circuit on clear bits: the inputs and the outputs are the concatenations of
+    /// the bits of the values
+    pub fn evaluate(&self, inputs: &[bool]) -> Vec<bool> {
+        self.try_evaluate(inputs).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_evaluate(&self, inputs: &[bool]) -> Result<Vec<bool>, OddError> {
+        if inputs.len() != self.input_count() {
+            return Err(OddError::LengthMismatch {
+                expected: self.input_count(),
+                got: inputs.len(),
+            });
+        }
+        let mut wires = vec![false; self.wire_count];
+        wires[..inputs.len()].copy_from_slice(inputs);
+        for gate in &self.gates {
+            wires[gate.output()] = match *gate {
+                BristolGate::Xor { a, b, .. } => wires[a] ^ wires[b],
+                BristolGate::And { a, b, .. } => wires[a] & wires[b],
+                BristolGate::Inv { a, .. } => !wires[a],
+                BristolGate::Eq { value, .. } => value,
+                BristolGate::Eqw { a, .. } => wires[a],
+            };
+        }
+        Ok(wires[self.output_wires()].to_vec())
+    }
+
+    /// Evaluate the circuit on bits encrypted under the parity encoding (see the module
+    /// documentation), returning the output bits under the same encoding
+    pub fn execute<B: OddBackend>(&self, backend: &B, inputs: &[Ciphertext]) -> Vec<Ciphertext> {
+        self.try_execute(backend, inputs).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_execute<B: OddBackend>(&self, backend: &B, inputs: &[Ciphertext]) -> Result<Vec<Ciphertext>, OddError> {
+        if inputs.len() != self.input_count() {
+            return Err(OddError::LengthMismatch {
+                expected: self.input_count(),
+                got: inputs.len(),
+            });
+        }
+        let parity = Encoding::parity_encoding();
+        if inputs.iter().any(|c| *c.get_encoding() != parity) {
+            return Err(OddError::InvalidEncoding);
+        }
+        let and = &self.and_gadget;
+
+        let mut wires: Vec<Option<Ciphertext>> = vec![None; self.wire_count];
+        inputs.iter().enumerate().for_each(|(i, c)| wires[i] = Some(c.clone()));
+        // the operands of the AND gates, switched to the input encodings of the gadget
+        let mut switched: HashMap<(usize, usize), Ciphertext> = HashMap::new();
+        for gate in &self.gates {
+            let wire = |w: usize| wires[w].as_ref().unwrap();
+            let result = match *gate {
+                BristolGate::Xor { a, b, .. } => backend.try_simple_sum(&[wire(a).clone(), wire(b).clone()])?,
+                BristolGate::Inv { a, .. } => backend.try_simple_plaintext_sum(wire(a), 1, 2)?,
+                BristolGate::Eq { value, .. } => backend.try_trivial_encrypt(value as u64, &parity)?,
+                BristolGate::Eqw { a, .. } => wire(a).clone(),
+                BristolGate::And { a, b, .. } => {
+                    let mut operands = vec![];
+                    for (i, w) in [a, b].into_iter().enumerate() {
+                        let operand = match switched.entry((w, i)) {
+                            Entry::Occupied(entry) => entry.get().clone(),
+                            Entry::Vacant(entry) => entry
+                                .insert(backend.try_encoding_switching_lut(wire(w), &and.input_encodings[i])?)
+                                .clone(),
+                        };
+                        operands.push(operand);
+                    }
+                    backend.try_exec_gadget(&operands, &and.intermediate_encoding, &parity)?
+                }
+            };
+            wires[gate.output()] = Some(result);
+        }
+        Ok(self.output_wires().map(|w| wires[w].clone().unwrap()).collect())
+    }
+}
+
+/// Gadget of an AND on the negacyclic binary encodings reached by bootstrapping the parity
+/// encoding
+fn and_gadget() -> Result<Gadget, OddError> {
+    let constraints = GadgetConstraints {
+        parity: ModulusParity::Odd,
+        negacyclic_inputs: true,
+        ..GadgetConstraints::default()
+    };
+    search_gadget(2, 2, &|x| x[0] & x[1], &constraints).ok_or(OddError::NoGadget {
+        arity: 2,
+        modulus: constraints.max_modulus,
+    })
+}
+
+
+/// A circuit of two 2-bit inputs using every operation
+#[cfg(test)]
+const TEST_CIRCUIT: &str = "
+9 14
+2 2 2
+1 3
+
+2 1 0 2 4 XOR
+2 1 0 2 5 AND
+2 1 1 3 6 XOR
+2 1 4 4 7 XOR
+4 2 1 6 3 5 8 9 MAND
+1 1 1 10 EQ
+2 1 8 10 12 XOR
+1 1 9 13 INV
+1 1 6 11 EQW
+";
+
+
+#[test]
+fn test_bristol_clear_evaluation() {
+    let circuit: BristolCircuit = TEST_CIRCUIT.parse().unwrap();
+    assert_eq!(circuit.input_sizes(), &[2, 2]);
+    assert_eq!(circuit.output_sizes(), &[3]);
+    assert_eq!(circuit.and_count(), 3);
+    for x in 0..16 {
+        let bits: Vec<bool> = (0..4).map(|i| (x >> i) & 1 == 1).collect();
+        let (a0, a1, b0, b1) = (bits[0], bits[1], bits[2], bits[3]);
+        let expected = vec![a1 ^ b1, !(a1 && b1), !((a1 ^ b1) && a0 && b0)];
+        assert_eq!(circuit.evaluate(&bits), expected);
+    }
+}
+
+
+#[test]
+fn test_bristol_execution() {
+    use crate::odd::TEST_PARAMETERS;
+
+    let circuit: BristolCircuit = TEST_CIRCUIT.parse().unwrap();
+    let parity = Encoding::parity_encoding();
+    let backend = SimulatedServerKey::new();
+    for x in 0..16u64 {
+        let bits: Vec<bool> = (0..4).map(|i| (x >> i) & 1 == 1).collect();
+        let inputs = bits.iter().map(|b| backend.trivial_encrypt(*b as u64, &parity)).collect::<Vec<_>>();
+        let outputs: Vec<bool> = circuit
+            .execute(&backend, &inputs)
+            .iter()
+            .map(|c| c.decrypt_trivial().unwrap() == 1)
+            .collect();
+        assert_eq!(outputs, circuit.evaluate(&bits), "inputs {bits:?}");
+    }
+
+    let (cks, sks) = gen_keys(&TEST_PARAMETERS);
+    for x in [0b1011u64, 0b0110] {
+        let bits: Vec<bool> = (0..4).map(|i| (x >> i) & 1 == 1).collect();
+        let inputs = bits.iter().map(|b| cks.encrypt_arithmetic(*b as u64, &parity)).collect::<Vec<_>>();
+        let outputs: Vec<bool> = circuit.execute(&sks, &inputs).iter().map(|c| cks.decrypt(c) == 1).collect();
+        assert_eq!(outputs, circuit.evaluate(&bits));
+    }
+}
+
+
+#[test]
+fn test_bristol_parse_errors() {
+    let error = |text: &str| match text.parse::<BristolCircuit>() {
+        Err(OddError::CircuitParse { line, .. }) => line,
+        other => panic!("expected a parse error, got {other:?}"),
+    };
+    // one gate, an input of 2 bits on the wires 0 and 1 and an output of 1 bit on the wire 2
+    let header = "1 3\n1 2\n1 1\n";
+    assert!(format!("{header}2 1 0 1 2 AND").parse::<BristolCircuit>().is_ok());
+    assert_eq!(error(&format!("{header}2 1 0 1 2 NAND")), 4);
+    assert_eq!(error(&format!("{header}2 1 0 x 2 AND")), 4);
+    assert_eq!(error(&format!("{header}AND")), 4);
+    assert_eq!(error(&format!("{header}3 1 0 1 1 2 AND")), 4);
+    assert_eq!(error(&format!("{header}2 1 0 1 2 INV")), 4);
+    assert_eq!(error(&format!("{header}2 1 0 2 2 XOR")), 4);
+    assert_eq!(error(&format!("{header}2 1 0 1 5 XOR")), 4);
+    assert_eq!(error(&format!("{header}2 1 0 1 1 XOR")), 4);
+    assert_eq!(error(&format!("{header}1 1 2 2 EQ")), 4);
+    assert_eq!(error("1 3\n2 2\n1 1\n2 1 0 1 2 XOR"), 2);
+    assert_eq!(error("1 3\n1 2\n1 2\n2 1 0 1 2 XOR"), 3);
+    assert_eq!(error("1 3\n1 2"), 0);
+    // wrong gate count, and an output wire never assigned
+    assert_eq!(error("2 3\n1 2\n1 1\n2 1 0 1 2 XOR"), 0);
+    assert_eq!(error("1 4\n1 2\n1 1\n2 1 0 1 2 XOR"), 0);
+
+    let circuit: BristolCircuit = format!("{header}2 1 0 1 2 AND").parse().unwrap();
+    assert_eq!(
+        circuit.try_evaluate(&[true]).unwrap_err(),
+        OddError::LengthMismatch { expected: 2, got: 1 }
+    );
+}
@@ -2,20 +2,158 @@
 //!
 //! This module implements the ciphertext structure containing an encryption of a Boolean message.
 
+use crate::conformance::ParameterSetConformant;
 use crate::core_crypto::entities::*;
+use crate::odd::error::OddError;
+use crate::odd::noise::NoiseVariance;
+use crate::odd::parameters::CustomOddParameters;
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use tfhe_versionable::Versionize;
+
+use super::backward_compatibility::ciphertext::{
+    CiphertextVersions, CompressedCiphertextVersions, EncodingVersions,
+};
+
+
+/// A ciphertext, either an LWE encryption or a trivial (public) element of Zp, both
+/// interpreted through their [`Encoding`].
+///
+/// An LWE encryption carries the estimated variance of its noise (see [`crate::odd::noise`]).
+/// This estimate is serialized along with the ciphertext, so it is only asserted by whoever
+/// produced it: the conformance check rejects a variance below the one of a fresh encryption or a
+/// bootstrapping, but a server receiving ciphertexts from untrusted parties cannot rely on its
+/// [`NoiseCheck`](crate::odd::noise::NoiseCheck) to catch a ciphertext noisier than it claims.
+#[derive(Clone, Debug, Serialize, Deserialize, Versionize)]
+#[versionize(CiphertextVersions)]
+pub enum Ciphertext {
+    EncodingEncrypted(LweCiphertextOwned<u64>, Encoding, NoiseVariance),
+    Trivial(ZpElem, Encoding),
+}
 
+impl Ciphertext {
+    pub fn get_encoding(&self) -> &Encoding {
+        match self {
+            Self::EncodingEncrypted(_, encoding, _) | Self::Trivial(_, encoding) => encoding,
+        }
+    }
 
-#[derive(Clone, Debug)]
-pub enum Ciphertext {
-    EncodingEncrypted(LweCiphertextOwned<u64>, Encoding),
-    Trivial(ZpElem),
+    /// The estimated variance of the noise (0 for a trivial ciphertext)
+    pub fn noise_variance(&self) -> NoiseVariance {
+        match self {
+            Self::EncodingEncrypted(_, _, variance) => *variance,
+            Self::Trivial(..) => NoiseVariance::ZERO,
+        }
+    }
+
+    pub fn is_trivial(&self) -> bool {
+        matches!(self, Self::Trivial(..))
+    }
+
+    /// Return the element of Zo encoded by a trivial ciphertext (no client key is needed)
+    pub fn decrypt_trivial(&self) -> Result<u64, OddError> {
+        match self {
+            Self::Trivial(value, encoding) => {
+                encoding
+                    .inverse_encoding(*value)
+                    .ok_or_else(|| OddError::UndecodableValue {
+                        value: *value,
+                        modulus: encoding.get_modulus(),
+                    })
+            }
+            Self::EncodingEncrypted(..) => Err(OddError::NotTrivialCiphertext),
+        }
+    }
+}
+
+impl crate::named::Named for Ciphertext {
+    const NAME: &'static str = "odd::Ciphertext";
+}
+
+impl ParameterSetConformant for Ciphertext {
+    type ParameterSet = CustomOddParameters;
+
+    fn is_conformant(&self, parameter_set: &CustomOddParameters) -> bool {
+        match self {
+            Self::EncodingEncrypted(ct, encoding, variance) => {
+                ct.is_conformant(&parameter_set.to_lwe_ciphertext_parameters())
+                    && encoding.is_valid()
+                    && *variance >= parameter_set.min_encrypted_variance()
+            }
+            Self::Trivial(value, encoding) => *value < encoding.get_modulus() && encoding.is_valid(),
+        }
+    }
+}
+
+/// A compressed ciphertext, whose mask is replaced by the seed used to generate it.
+///
+/// It is produced by the client and has to be decompressed before being used in a homomorphic
+/// evaluation.
+#[derive(Clone, Debug, Serialize, Deserialize, Versionize)]
+#[versionize(CompressedCiphertextVersions)]
+pub struct CompressedCiphertext {
+    pub(crate) ciphertext: SeededLweCiphertext<u64>,
+    pub(crate) encoding: Encoding,
+    pub(crate) noise_variance: NoiseVariance,
+}
+
+impl crate::named::Named for CompressedCiphertext {
+    const NAME: &'static str = "odd::CompressedCiphertext";
+}
+
+impl ParameterSetConformant for CompressedCiphertext {
+    type ParameterSet = CustomOddParameters;
+
+    fn is_conformant(&self, parameter_set: &CustomOddParameters) -> bool {
+        let Self {
+            ciphertext,
+            encoding,
+            noise_variance,
+        } = self;
+
+        ciphertext.is_conformant(&parameter_set.to_lwe_ciphertext_parameters())
+            && encoding.is_valid()
+            && *noise_variance >= parameter_set.encryption_variance()
+    }
+}
+
+impl CompressedCiphertext {
+    pub fn decompress(&self) -> Ciphertext {
+        Ciphertext::EncodingEncrypted(
+            self.ciphertext.decompress_into_lwe_ciphertext(),
+            self.encoding.clone(),
+            self.noise_variance,
+        )
+    }
+
+    pub fn get_encoding(&self) -> &Encoding {
+        &self.encoding
+    }
+
+    /// Deconstruct a [`CompressedCiphertext`] into its constituents.
+    pub fn into_raw_parts(self) -> (SeededLweCiphertext<u64>, Encoding, NoiseVariance) {
+        (self.ciphertext, self.encoding, self.noise_variance)
+    }
+
+    /// Construct a [`CompressedCiphertext`] from its constituents.
+    pub fn from_raw_parts(
+        ciphertext: SeededLweCiphertext<u64>,
+        encoding: Encoding,
+        noise_variance: NoiseVariance,
+    ) -> Self {
+        Self {
+            ciphertext,
+            encoding,
+            noise_variance,
+        }
+    }
 }
 
 type ZoElem = u64;
 type ZpElem = u64;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize, Versionize)]
+#[versionize(EncodingVersions)]
 pub struct Encoding {
     origin_modulus: u64,         // o in the paper
     parts: Vec<HashSet<ZpElem>>, //element of index i \in \Zo returns the elems of \Zp associated with i in the encoding.
@@ -34,9 +172,18 @@ impl PartialEq for Encoding {
     }
 }
 
+impl crate::named::Named for Encoding {
+    const NAME: &'static str = "odd::Encoding";
+}
+
 impl Encoding {
     pub fn is_valid(&self) -> bool {
-        assert_eq!(self.origin_modulus, self.parts.len().try_into().unwrap());
+        if self.modulus_p < 2 || self.origin_modulus != self.parts.len() as u64 {
+            return false;
+        }
+        if !self.parts.iter().all(|part| part.iter().all(|x| *x < self.modulus_p)) {
+            return false;
+        }
 
         let x = self.parts.iter().enumerate().all(|(i, part_1)| {
             self.parts
@@ -44,7 +191,6 @@ impl Encoding {
                 .skip(i + 1)
                 .all(|part_2| part_1.is_disjoint(part_2))
         }); //check disjonction of all parts
-        let x = true;
 
         let y = match self.modulus_p % 2 == 1 || self.modulus_p == 2 {
             true => true,
@@ -96,6 +242,39 @@ impl Encoding {
         return None; //placeholder, this ZpElem is never reached
     }
 
+    /// Return the element of Zp written by a bootstrapping for each element of Zo, when the caller
+    /// does not choose one: the smallest element of its part.
+    pub fn try_default_representatives(&self) -> Result<Vec<ZpElem>, OddError> {
+        (0..self.origin_modulus)
+            .map(|i| {
+                self.get_part(i)
+                    .iter()
+                    .min()
+                    .copied()
+                    .ok_or(OddError::InvalidRepresentative { message: i })
+            })
+            .collect()
+    }
+
+    /// Check that `representatives[i]` belongs to the part of i, for each element i of Zo.
+    pub fn try_check_representatives(&self, representatives: &[ZpElem]) -> Result<(), OddError> {
+        if representatives.len() as u64 != self.origin_modulus {
+            return Err(OddError::LengthMismatch {
+                expected: self.origin_modulus as usize,
+                got: representatives.len(),
+            });
+        }
+        (0..self.origin_modulus)
+            .find(|&i| !self.is_partition_containing(i, representatives[i as usize]))
+            .map_or(Ok(()), |message| Err(OddError::InvalidRepresentative { message }))
+    }
+
+    pub fn has_padding(&self) -> bool {
+        // for an even p, the second half of Zp only holds the opposites of the first one, so it should stay empty
+        self.modulus_p % 2 == 1
+            || self.parts.iter().all(|part| part.iter().all(|x| *x < self.modulus_p / 2))
+    }
+
     pub fn is_canonical(&self) -> bool {
         self.parts.iter().all(|part| part.len() == 1)
     }
@@ -110,7 +289,12 @@ impl Encoding {
     }
 
     pub fn add_constant(&self, constant: ZpElem) -> Self {
-        Self::new(
+        self.try_add_constant(constant)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_add_constant(&self, constant: ZpElem) -> Result<Self, OddError> {
+        Self::try_new(
             self.origin_modulus,
             self.parts
                 .iter()
@@ -135,12 +319,29 @@ impl Encoding {
     }
 
     pub fn get_part_single_value_if_canonical(&self, element_of_zo: ZoElem) -> ZpElem {
-        assert!(self.is_canonical());
-        self.get_part(element_of_zo)
+        self.try_get_part_single_value_if_canonical(element_of_zo)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_get_part_single_value_if_canonical(
+        &self,
+        element_of_zo: ZoElem,
+    ) -> Result<ZpElem, OddError> {
+        if !self.is_canonical() {
+            return Err(OddError::NonCanonicalEncoding);
+        }
+        if element_of_zo >= self.origin_modulus {
+            return Err(OddError::MessageOutOfRange {
+                message: element_of_zo,
+                origin_modulus: self.origin_modulus,
+            });
+        }
+        Ok(self
+            .get_part(element_of_zo)
             .iter()
             .next()
             .unwrap()
-            .to_owned()
+            .to_owned())
     }
 
     pub fn negative_on_o_ring(&self, element_of_zo: ZoElem) -> ZoElem {
@@ -148,16 +349,23 @@ impl Encoding {
     }
 
     pub fn new(origin_modulus: u64, parts: Vec<HashSet<ZpElem>>, modulus_p: u64) -> Self {
-        assert!(parts.iter().all(|part| part.iter().all(|x| *x < modulus_p)));
+        Self::try_new(origin_modulus, parts, modulus_p).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_new(
+        origin_modulus: u64,
+        parts: Vec<HashSet<ZpElem>>,
+        modulus_p: u64,
+    ) -> Result<Self, OddError> {
         let new_encoding = Self {
             origin_modulus,
             parts,
             modulus_p,
         };
         if new_encoding.is_valid() {
-            new_encoding
+            Ok(new_encoding)
         } else {
-            panic!("This Arithmetic Encoding is not correct !");
+            Err(OddError::InvalidEncoding)
         }
     }
 
@@ -166,7 +374,16 @@ impl Encoding {
         values_for_singletons: Vec<ZpElem>,
         modulus_p: u64,
     ) -> Self {
-        Self::new(
+        Self::try_new_canonical(origin_modulus, values_for_singletons, modulus_p)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_new_canonical(
+        origin_modulus: u64,
+        values_for_singletons: Vec<ZpElem>,
+        modulus_p: u64,
+    ) -> Result<Self, OddError> {
+        Self::try_new(
             origin_modulus,
             values_for_singletons
                 .iter()
@@ -180,6 +397,13 @@ impl Encoding {
         Self::new_canonical(2, vec![0, value_for_singleton_true], modulus_p)
     }
 
+    pub fn try_new_canonical_binary(
+        value_for_singleton_true: ZpElem,
+        modulus_p: u64,
+    ) -> Result<Self, OddError> {
+        Self::try_new_canonical(2, vec![0, value_for_singleton_true], modulus_p)
+    }
+
     pub fn parity_encoding() -> Self {
         Self::new_canonical_binary(1, 2)
     }
@@ -194,6 +418,14 @@ impl Encoding {
 
 
     pub fn apply_lut_to_encoding(&self, f: &dyn Fn(ZoElem) -> ZoElem) -> Self {
+        self.try_apply_lut_to_encoding(f)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_apply_lut_to_encoding(
+        &self,
+        f: &dyn Fn(ZoElem) -> ZoElem,
+    ) -> Result<Self, OddError> {
         //the origin modulus of the ouput may be different as the one of the input.
         let mut parts_hashmap: HashMap<ZoElem, HashSet<ZpElem>> = HashMap::new();
         for i in 0..self.origin_modulus {
@@ -206,17 +438,30 @@ impl Encoding {
                 }
             };
         }
-        let parts = (0..self.origin_modulus)
+        // the image of f may be larger than Zo (e.g. in a tree bootstrapping, where the inputs
+        // may have a smaller origin modulus than the outputs)
+        let new_origin_modulus = parts_hashmap
+            .keys()
+            .map(|i| i + 1)
+            .max()
+            .unwrap_or(0)
+            .max(self.origin_modulus);
+        let parts = (0..new_origin_modulus)
             .map(|i| match parts_hashmap.get(&i) {
                 Some(part) => part.to_owned(),
                 None => HashSet::new(),
             })
             .collect();
-        Self::new(self.origin_modulus, parts, self.modulus_p)
+        Self::try_new(new_origin_modulus, parts, self.modulus_p)
     }
 
     pub fn multiply_encoding_by_constant(&self, constant: ZpElem) -> Self {
-        Self::new(
+        self.try_multiply_encoding_by_constant(constant)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_multiply_encoding_by_constant(&self, constant: ZpElem) -> Result<Self, OddError> {
+        Self::try_new(
             self.origin_modulus,
             self.parts
                 .iter()
@@ -268,3 +513,88 @@ fn bad_arithmetic_encoding_negacyclicity() {
 fn good_arithmetic_encoding_negacyclicity() {
     let _ = Encoding::new_canonical(3, vec![2, 1, 5], 8);
 }
+
+#[test]
+fn invalid_encoding_is_not_conformant() {
+    use crate::core_crypto::prelude::CiphertextModulus;
+    use crate::odd::TEST_PARAMETERS;
+
+    let lwe_size = TEST_PARAMETERS.ciphertext_lwe_dimension().to_lwe_size();
+    let lwe = LweCiphertext::new(0u64, lwe_size, CiphertextModulus::new_native());
+    let good = Encoding::new_canonical(3, vec![0, 1, 2], 5);
+    let fresh = TEST_PARAMETERS.encryption_variance();
+    assert!(Ciphertext::EncodingEncrypted(lwe.clone(), good.clone(), fresh).is_conformant(&TEST_PARAMETERS));
+
+    // two values of Zo share the same element of Zp
+    let duplicate = Encoding {
+        origin_modulus: 3,
+        parts: vec![[0].into(), [1].into(), [1].into()],
+        modulus_p: 5,
+    };
+    assert!(!Ciphertext::EncodingEncrypted(lwe.clone(), duplicate, fresh).is_conformant(&TEST_PARAMETERS));
+
+    // an element outside of Zp
+    let out_of_range = Encoding {
+        origin_modulus: 3,
+        parts: vec![[0].into(), [1].into(), [7].into()],
+        modulus_p: 5,
+    };
+    assert!(!Ciphertext::EncodingEncrypted(lwe.clone(), out_of_range, fresh).is_conformant(&TEST_PARAMETERS));
+
+    // wrong number of parts
+    let missing_part = Encoding {
+        origin_modulus: 3,
+        parts: vec![[0].into(), [1].into()],
+        modulus_p: 5,
+    };
+    assert!(!Ciphertext::EncodingEncrypted(lwe, missing_part, fresh).is_conformant(&TEST_PARAMETERS));
+
+    // wrong lwe dimension
+    let small_lwe = LweCiphertext::new(0u64, TEST_PARAMETERS.lwe_dimension.to_lwe_size(), CiphertextModulus::new_native());
+    assert!(!Ciphertext::EncodingEncrypted(small_lwe, good.clone(), fresh).is_conformant(&TEST_PARAMETERS));
+
+    // a variance below the one of any encryption or bootstrapping
+    let lwe = LweCiphertext::new(0u64, lwe_size, CiphertextModulus::new_native());
+    assert!(!Ciphertext::EncodingEncrypted(lwe.clone(), good.clone(), NoiseVariance::ZERO).is_conformant(&TEST_PARAMETERS));
+    assert!(!Ciphertext::EncodingEncrypted(lwe, good, NoiseVariance(-1.0)).is_conformant(&TEST_PARAMETERS));
+}
+
+#[test]
+fn test_compressed_ciphertext() {
+    use crate::odd::prelude::*;
+    use crate::odd::TEST_PARAMETERS;
+
+    let (cks, sks) = gen_keys(&TEST_PARAMETERS);
+    let encoding = Encoding::new_canonical(5, vec![0, 1, 2, 3, 4], 11);
+
+    for msg in 0..5 {
+        let compressed = cks.encrypt_arithmetic_compressed(msg, &encoding);
+        assert!(compressed.is_conformant(&TEST_PARAMETERS));
+
+        let compressed_size = bincode::serialized_size(&compressed).unwrap();
+        let ct = compressed.decompress();
+        let full_size = bincode::serialized_size(&ct).unwrap();
+        assert!(compressed_size * 10 < full_size);
+
+        assert_eq!(cks.decrypt(&ct), msg);
+        let res = sks.apply_lut(&ct, &encoding, &|x| (x + 1) % 5);
+        assert_eq!(cks.decrypt(&res), (msg + 1) % 5);
+    }
+}
+
+#[test]
+fn try_new_encoding_errors() {
+    assert_eq!(
+        Encoding::try_new(3, [[0, 2].into(), [0].into(), [1].into()].into(), 5),
+        Err(OddError::InvalidEncoding)
+    );
+    assert_eq!(
+        Encoding::try_new_canonical(3, vec![1, 5, 2], 8),
+        Err(OddError::InvalidEncoding)
+    );
+    let non_canonical = Encoding::new(2, [[0, 2].into(), [1].into()].into(), 5);
+    assert_eq!(
+        non_canonical.try_get_part_single_value_if_canonical(0),
+        Err(OddError::NonCanonicalEncoding)
+    );
+}
@@ -0,0 +1,670 @@
+//! Compilation of Boolean circuits into odd gadgets.
+//!
+//! A [`BooleanCircuit`] is a list of AND, OR, XOR, NOT, MUX and majority gates. Its compilation
+//! fuses the gates into gadgets, functions of a few wires evaluated by a single bootstrapping (see
+//! [`ServerKey::exec_gadget`]):
+//! * every wire is encrypted as 0 or 1 in Zp, p being chosen by the [`CompilerOptions`], so that a
+//!   gadget input is scaled by its coefficient with a free
+//!   [`OddBackend::encoding_switching_mul_constant`];
+//! * the NOT gates are absorbed by the gadgets reading them;
+//! * a gate used by a single gadget is merged into it, as long as the merged gadget has at most
+//!   `max_gadget_inputs` inputs and is found by [`search_gadget`].
+//!
+//! The resulting [`CompiledCircuit`] runs on any [`OddBackend`], so it can be checked on a
+//! [`SimulatedServerKey`] before running on encrypted data.
+
+use std::collections::HashMap;
+
+use crate::odd::prelude::*;
+
+/// A wire of a [`BooleanCircuit`]: one of its inputs or the output of one of its gates
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Wire(usize);
+
+impl Wire {
+    /// Index of the wire: the inputs come first, followed by the gates in their order of insertion
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Gate {
+    Not(Wire),
+    And(Wire, Wire),
+    Or(Wire, Wire),
+    Xor(Wire, Wire),
+    /// `if_true` if `selector` is set, `if_false` otherwise
+    Mux {
+        selector: Wire,
+        if_true: Wire,
+        if_false: Wire,
+    },
+    Majority(Wire, Wire, Wire),
+}
+
+impl Gate {
+    fn operands(&self) -> Vec<Wire> {
+        match *self {
+            Self::Not(a) => vec![a],
+            Self::And(a, b) | Self::Or(a, b) | Self::Xor(a, b) => vec![a, b],
+            Self::Mux {
+                selector,
+                if_true,
+                if_false,
+            } => vec![selector, if_true, if_false],
+            Self::Majority(a, b, c) => vec![a, b, c],
+        }
+    }
+
+    /// Output of the gate, `operands` being the values of [`Self::operands`]
+    fn evaluate(&self, operands: &[bool]) -> bool {
+        match self {
+            Self::Not(..) => !operands[0],
+            Self::And(..) => operands[0] & operands[1],
+            Self::Or(..) => operands[0] | operands[1],
+            Self::Xor(..) => operands[0] ^ operands[1],
+            Self::Mux { .. } => {
+                if operands[0] {
+                    operands[1]
+                } else {
+                    operands[2]
+                }
+            }
+            Self::Majority(..) => operands.iter().filter(|x| **x).count() >= 2,
+        }
+    }
+}
+
+/// A Boolean circuit, built gate by gate
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BooleanCircuit {
+    input_count: usize,
+    gates: Vec<Gate>,
+    outputs: Vec<Wire>,
+}
+
+impl BooleanCircuit {
+    pub fn new(input_count: usize) -> Self {
+        Self {
+            input_count,
+            gates: vec![],
+            outputs: vec![],
+        }
+    }
+
+    pub fn input_count(&self) -> usize {
+        self.input_count
+    }
+
+    pub fn gates(&self) -> &[Gate] {
+        &self.gates
+    }
+
+    pub fn outputs(&self) -> &[Wire] {
+        &self.outputs
+    }
+
+    /// Wire of the `index`-th input
+    pub fn input(&self, index: usize) -> Wire {
+        assert!(index < self.input_count, "the circuit has {} inputs", self.input_count);
+        Wire(index)
+    }
+
+    /// Append a gate, returning its output wire
+    pub fn add_gate(&mut self, gate: Gate) -> Wire {
+        self.gates.push(gate);
+        Wire(self.input_count + self.gates.len() - 1)
+    }
+
+    pub fn not(&mut self, a: Wire) -> Wire {
+        self.add_gate(Gate::Not(a))
+    }
+
+    pub fn and(&mut self, a: Wire, b: Wire) -> Wire {
+        self.add_gate(Gate::And(a, b))
+    }
+
+    pub fn or(&mut self, a: Wire, b: Wire) -> Wire {
+        self.add_gate(Gate::Or(a, b))
+    }
+
+    pub fn xor(&mut self, a: Wire, b: Wire) -> Wire {
+        self.add_gate(Gate::Xor(a, b))
+    }
+
+    pub fn mux(&mut self, selector: Wire, if_true: Wire, if_false: Wire) -> Wire {
+        self.add_gate(Gate::Mux {
+            selector,
+            if_true,
+            if_false,
+        })
+    }
+
+    pub fn majority(&mut self, a: Wire, b: Wire, c: Wire) -> Wire {
+        self.add_gate(Gate::Majority(a, b, c))
+    }
+
+    pub fn add_output(&mut self, wire: Wire) {
+        self.outputs.push(wire);
+    }
+
+    /// Check that every gate only uses the inputs and the previous gates
+    fn try_check_wires(&self) -> Result<(), OddError> {
+        for (i, gate) in self.gates.iter().enumerate() {
+            if let Some(wire) = gate.operands().iter().find(|w| w.0 >= self.input_count + i) {
+                return Err(OddError::InvalidWire { wire: wire.0 });
+            }
+        }
+        let wire_count = self.input_count + self.gates.len();
+        self.outputs
+            .iter()
+            .find(|w| w.0 >= wire_count)
+            .map_or(Ok(()), |wire| Err(OddError::InvalidWire { wire: wire.0 }))
+    }
+
+    /// Evaluate the circuit on clear values
+    pub fn evaluate(&self, inputs: &[bool]) -> Vec<bool> {
+        self.try_evaluate(inputs).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_evaluate(&self, inputs: &[bool]) -> Result<Vec<bool>, OddError> {
+        if inputs.len() != self.input_count {
+            return Err(OddError::LengthMismatch {
+                expected: self.input_count,
+                got: inputs.len(),
+            });
+        }
+        self.try_check_wires()?;
+        let mut values = inputs.to_vec();
+        for gate in &self.gates {
+            let operands: Vec<bool> = gate.operands().iter().map(|w| values[w.0]).collect();
+            values.push(gate.evaluate(&operands));
+        }
+        Ok(self.outputs.iter().map(|w| values[w.0]).collect())
+    }
+
+    /// Compile the circuit into gadgets
+    pub fn compile(&self, options: &CompilerOptions) -> CompiledCircuit {
+        self.try_compile(options).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_compile(&self, options: &CompilerOptions) -> Result<CompiledCircuit, OddError> {
+        self.try_check_wires()?;
+        Compiler::new(self, options).run()
+    }
+}
+
+/// Options of the compilation of a [`BooleanCircuit`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CompilerOptions {
+    /// Plaintext modulus of every gadget
+    pub modulus: u64,
+    /// Largest number of inputs of a gadget obtained by merging gates
+    pub max_gadget_inputs: usize,
+}
+
+impl Default for CompilerOptions {
+    /// With p = 17, every function of 4 bits has a gadget (with the coefficients 1, 2, 4, 8)
+    fn default() -> Self {
+        Self {
+            modulus: 17,
+            max_gadget_inputs: 4,
+        }
+    }
+}
+
+/// Where the value of a wire comes from once compiled
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum Source {
+    Input(usize),
+    Gadget(usize),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Value {
+    Constant(bool),
+    Wire { source: Source, negated: bool },
+}
+
+/// A Boolean function of some sources, as its truth table: the bit i of an index is the value of
+/// `support[i]`
+#[derive(Clone, Debug)]
+struct Function {
+    support: Vec<Source>,
+    table: Vec<bool>,
+}
+
+impl Function {
+    fn evaluate(&self, assignment: &HashMap<Source, bool>) -> bool {
+        let index = self
+            .support
+            .iter()
+            .enumerate()
+            .map(|(i, source)| (assignment[source] as usize) << i)
+            .sum::<usize>();
+        self.table[index]
+    }
+
+    /// Remove the sources the table does not depend on
+    fn reduce(self) -> Self {
+        let relevant: Vec<usize> = (0..self.support.len())
+            .filter(|i| (0..self.table.len()).any(|index| self.table[index] != self.table[index ^ (1 << i)]))
+            .collect();
+        let support: Vec<Source> = relevant.iter().map(|i| self.support[*i]).collect();
+        let table = (0..1usize << relevant.len())
+            .map(|index| {
+                let full_index = relevant
+                    .iter()
+                    .enumerate()
+                    .map(|(j, i)| ((index >> j) & 1) << i)
+                    .sum::<usize>();
+                self.table[full_index]
+            })
+            .collect();
+        Self { support, table }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct CompiledGadget {
+    inputs: Vec<Source>,
+    gadget: Gadget,
+}
+
+/// A Boolean circuit compiled into gadgets, ready to be executed
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompiledCircuit {
+    modulus: u64,
+    input_count: usize,
+    gadgets: Vec<CompiledGadget>,
+    outputs: Vec<Value>,
+}
+
+struct Compiler<'a> {
+    circuit: &'a BooleanCircuit,
+    options: &'a CompilerOptions,
+    /// Number of gates and outputs reading each wire, the readers of a NOT gate being counted as
+    /// readers of its operand
+    uses: Vec<usize>,
+    /// Function of each gadget, with the wire it computes
+    nodes: Vec<(Function, Gadget)>,
+    node_wires: Vec<usize>,
+    gadgets: HashMap<Vec<bool>, Option<Gadget>>,
+}
+
+impl<'a> Compiler<'a> {
+    fn new(circuit: &'a BooleanCircuit, options: &'a CompilerOptions) -> Self {
+        let wire_count = circuit.input_count + circuit.gates.len();
+        let mut uses = vec![0; wire_count];
+        for wire in circuit.gates.iter().flat_map(Gate::operands).chain(circuit.outputs.iter().copied()) {
+            uses[wire.0] += 1;
+        }
+        for (i, gate) in circuit.gates.iter().enumerate().rev() {
+            if let Gate::Not(a) = gate {
+                uses[a.0] = uses[a.0] - 1 + uses[circuit.input_count + i];
+            }
+        }
+        Self {
+            circuit,
+            options,
+            uses,
+            nodes: vec![],
+            node_wires: vec![],
+            gadgets: HashMap::new(),
+        }
+    }
+
+    /// Gadget computing a function of `table`, if any
+    fn gadget(&mut self, table: &[bool]) -> Option<Gadget> {
+        let options = self.options;
+        self.gadgets
+            .entry(table.to_vec())
+            .or_insert_with(|| {
+                let arity = table.len().trailing_zeros() as usize;
+                let constraints = GadgetConstraints {
+                    min_modulus: options.modulus,
+                    max_modulus: options.modulus,
+                    ..GadgetConstraints::default()
+                };
+                let f = |x: &[u64]| {
+                    let index = x.iter().enumerate().map(|(i, b)| (*b as usize) << i).sum::<usize>();
+                    table[index] as u64
+                };
+                search_gadget(2, arity, &f, &constraints)
+            })
+            .clone()
+    }
+
+    /// Function of a gate, the operands which are gadgets in `inlined` being replaced by their
+    /// own function
+    fn gate_function(&self, gate: &Gate, operands: &[Value], inlined: &[usize]) -> Function {
+        let mut support = vec![];
+        for operand in operands {
+            if let Value::Wire { source, .. } = operand {
+                let sources = match source {
+                    Source::Gadget(n) if inlined.contains(n) => self.nodes[*n].0.support.clone(),
+                    _ => vec![*source],
+                };
+                for source in sources {
+                    if !support.contains(&source) {
+                        support.push(source);
+                    }
+                }
+            }
+        }
+        let table = (0..1usize << support.len())
+            .map(|index| {
+                let assignment: HashMap<Source, bool> = support
+                    .iter()
+                    .enumerate()
+                    .map(|(i, source)| (*source, (index >> i) & 1 == 1))
+                    .collect();
+                let values: Vec<bool> = operands
+                    .iter()
+                    .map(|operand| match operand {
+                        Value::Constant(b) => *b,
+                        Value::Wire { source, negated } => {
+                            let value = match source {
+                                Source::Gadget(n) if inlined.contains(n) => self.nodes[*n].0.evaluate(&assignment),
+                                _ => assignment[source],
+                            };
+                            value ^ negated
+                        }
+                    })
+                    .collect();
+                gate.evaluate(&values)
+            })
+            .collect();
+        Function { support, table }.reduce()
+    }
+
+    fn run(mut self) -> Result<CompiledCircuit, OddError> {
+        let circuit = self.circuit;
+        let mut values: Vec<Value> = (0..circuit.input_count)
+            .map(|i| Value::Wire {
+                source: Source::Input(i),
+                negated: false,
+            })
+            .collect();
+
+        for (i, gate) in circuit.gates.iter().enumerate() {
+            let operands: Vec<Value> = gate.operands().iter().map(|w| values[w.0]).collect();
+            let mut function = self.gate_function(gate, &operands, &[]);
+
+            // merge the gadgets only read by this gate, while the result stays small enough
+            let mut inlined = vec![];
+            for operand in &operands {
+                if let Value::Wire { source: Source::Gadget(n), .. } = operand {
+                    if self.uses[self.node_wires[*n]] != 1 || inlined.contains(n) {
+                        continue;
+                    }
+                    let mut candidate = inlined.clone();
+                    candidate.push(*n);
+                    let merged = self.gate_function(gate, &operands, &candidate);
+                    if merged.support.len() <= self.options.max_gadget_inputs && self.gadget(&merged.table).is_some() {
+                        inlined = candidate;
+                        function = merged;
+                    }
+                }
+            }
+
+            let value = match function.support.len() {
+                0 => Value::Constant(function.table[0]),
+                1 => Value::Wire {
+                    source: function.support[0],
+                    negated: function.table[0],
+                },
+                arity => {
+                    let gadget = self.gadget(&function.table).ok_or(OddError::NoGadget {
+                        arity,
+                        modulus: self.options.modulus,
+                    })?;
+                    self.nodes.push((function, gadget));
+                    self.node_wires.push(circuit.input_count + i);
+                    Value::Wire {
+                        source: Source::Gadget(self.nodes.len() - 1),
+                        negated: false,
+                    }
+                }
+            };
+            values.push(value);
+        }
+
+        let outputs: Vec<Value> = circuit.outputs.iter().map(|w| values[w.0]).collect();
+        Ok(self.schedule(&outputs))
+    }
+
+    /// Keep the gadgets needed by the outputs, in their order of creation
+    fn schedule(self, outputs: &[Value]) -> CompiledCircuit {
+        let mut needed = vec![false; self.nodes.len()];
+        let mut stack: Vec<usize> = outputs
+            .iter()
+            .filter_map(|value| match value {
+                Value::Wire { source: Source::Gadget(n), .. } => Some(*n),
+                _ => None,
+            })
+            .collect();
+        while let Some(n) = stack.pop() {
+            if !needed[n] {
+                needed[n] = true;
+                stack.extend(self.nodes[n].0.support.iter().filter_map(|source| match source {
+                    Source::Gadget(m) => Some(*m),
+                    Source::Input(_) => None,
+                }));
+            }
+        }
+
+        let mut positions = vec![0; self.nodes.len()];
+        let mut position = 0;
+        for (n, is_needed) in needed.iter().enumerate() {
+            positions[n] = position;
+            position += *is_needed as usize;
+        }
+        let remap = |source: Source| match source {
+            Source::Gadget(n) => Source::Gadget(positions[n]),
+            Source::Input(i) => Source::Input(i),
+        };
+        let gadgets = self
+            .nodes
+            .into_iter()
+            .zip(needed)
+            .filter(|(_, is_needed)| *is_needed)
+            .map(|((function, gadget), _)| CompiledGadget {
+                inputs: function.support.into_iter().map(remap).collect(),
+                gadget,
+            })
+            .collect();
+        let outputs = outputs
+            .iter()
+            .map(|value| match *value {
+                Value::Wire { source, negated } => Value::Wire {
+                    source: remap(source),
+                    negated,
+                },
+                Value::Constant(value) => Value::Constant(value),
+            })
+            .collect();
+        CompiledCircuit {
+            modulus: self.options.modulus,
+            input_count: self.circuit.input_count,
+            gadgets,
+            outputs,
+        }
+    }
+}
+
+impl CompiledCircuit {
+    /// Encoding of the inputs and the outputs of the circuit: 0 and 1 in Zp
+    pub fn wire_encoding(&self) -> Encoding {
+        Encoding::new_canonical_binary(1, self.modulus)
+    }
+
+    /// Number of bootstrappings of an execution
+    pub fn gadget_count(&self) -> usize {
+        self.gadgets.len()
+    }
+
+    /// Run the circuit on `inputs`, encrypted under [`Self::wire_encoding`]. The outputs are
+    /// encrypted under the same encoding; the negated outputs are computed as 1 - x with
+    /// [`OddBackend::simple_negation`], which keeps their noise unchanged.
+    pub fn execute<B: OddBackend>(&self, backend: &B, inputs: &[Ciphertext]) -> Vec<Ciphertext> {
+        self.try_execute(backend, inputs).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_execute<B: OddBackend>(&self, backend: &B, inputs: &[Ciphertext]) -> Result<Vec<Ciphertext>, OddError> {
+        if inputs.len() != self.input_count {
+            return Err(OddError::LengthMismatch {
+                expected: self.input_count,
+                got: inputs.len(),
+            });
+        }
+        let wire_encoding = self.wire_encoding();
+        if inputs.iter().any(|c| *c.get_encoding() != wire_encoding) {
+            return Err(OddError::InvalidEncoding);
+        }
+
+        let mut results: Vec<Ciphertext> = vec![];
+        for CompiledGadget { inputs: sources, gadget } in &self.gadgets {
+            let scaled = sources
+                .iter()
+                .zip(&gadget.coefficients)
+                .map(|(source, coefficient)| {
+                    let wire = match source {
+                        Source::Input(i) => &inputs[*i],
+                        Source::Gadget(n) => &results[*n],
+                    };
+                    backend.try_encoding_switching_mul_constant(wire, *coefficient)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            results.push(backend.try_exec_gadget(&scaled, &gadget.intermediate_encoding, &wire_encoding)?);
+        }
+
+        self.outputs
+            .iter()
+            .map(|value| match value {
+                Value::Constant(b) => backend.try_trivial_encrypt(*b as u64, &wire_encoding),
+                Value::Wire { source, negated } => {
+                    let wire = match source {
+                        Source::Input(i) => &inputs[*i],
+                        Source::Gadget(n) => &results[*n],
+                    };
+                    if *negated {
+                        let opposite = backend.try_simple_negation(wire)?;
+                        backend.try_simple_plaintext_sum(&opposite, 1, self.modulus)
+                    } else {
+                        Ok(wire.clone())
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+
+/// A full adder and a few gates around it, with negated outputs and a constant
+#[cfg(test)]
+fn test_circuit() -> BooleanCircuit {
+    let mut circuit = BooleanCircuit::new(4);
+    let (a, b, c, d) = (circuit.input(0), circuit.input(1), circuit.input(2), circuit.input(3));
+    let ab = circuit.xor(a, b);
+    let sum = circuit.xor(ab, c);
+    let carry = circuit.majority(a, b, c);
+    let not_d = circuit.not(d);
+    let selected = circuit.mux(not_d, sum, carry);
+    let or = circuit.or(selected, a);
+    let not_or = circuit.not(or);
+    let zero = circuit.xor(d, d);
+    for wire in [sum, carry, not_or, zero, not_d] {
+        circuit.add_output(wire);
+    }
+    circuit
+}
+
+
+#[test]
+fn test_compiled_circuit_simulation() {
+    let circuit = test_circuit();
+    let compiled = circuit.compile(&CompilerOptions::default());
+    // the sum, the carry and the merged mux and or
+    assert_eq!(compiled.gadget_count(), 3);
+
+    let backend = SimulatedServerKey::new();
+    let encoding = compiled.wire_encoding();
+    for x in 0..16u64 {
+        let bits: Vec<bool> = (0..4).map(|i| (x >> i) & 1 == 1).collect();
+        let inputs = bits
+            .iter()
+            .map(|b| backend.trivial_encrypt(*b as u64, &encoding))
+            .collect::<Vec<_>>();
+        let outputs: Vec<bool> = compiled
+            .execute(&backend, &inputs)
+            .iter()
+            .map(|c| {
+                assert_eq!(*c.get_encoding(), encoding);
+                c.decrypt_trivial().unwrap() == 1
+            })
+            .collect();
+        assert_eq!(outputs, circuit.evaluate(&bits), "inputs {bits:?}");
+    }
+}
+
+
+#[test]
+fn test_compiled_circuit_encrypted() {
+    use crate::odd::TEST_PARAMETERS;
+
+    let (cks, sks) = gen_keys(&TEST_PARAMETERS);
+    let circuit = test_circuit();
+    let compiled = circuit.compile(&CompilerOptions::default());
+    let encoding = compiled.wire_encoding();
+    for x in [0b0111u64, 0b1010] {
+        let bits: Vec<bool> = (0..4).map(|i| (x >> i) & 1 == 1).collect();
+        let inputs = bits
+            .iter()
+            .map(|b| cks.encrypt_arithmetic(*b as u64, &encoding))
+            .collect::<Vec<_>>();
+        let results = compiled.execute(&sks, &inputs);
+        // the negation of d keeps the noise of its fresh encryption
+        assert_eq!(results[4].noise_variance(), inputs[3].noise_variance());
+        let outputs: Vec<bool> = results.iter().map(|c| cks.decrypt(c) == 1).collect();
+        assert_eq!(outputs, circuit.evaluate(&bits));
+    }
+}
+
+
+#[test]
+fn test_compilation_errors() {
+    let mut circuit = BooleanCircuit::new(2);
+    let (a, b) = (circuit.input(0), circuit.input(1));
+    let and = circuit.and(a, b);
+    circuit.add_output(and);
+
+    // the parity encoding only computes affine functions
+    let options = CompilerOptions {
+        modulus: 2,
+        ..CompilerOptions::default()
+    };
+    assert_eq!(
+        circuit.try_compile(&options).unwrap_err(),
+        OddError::NoGadget { arity: 2, modulus: 2 }
+    );
+
+    circuit.add_gate(Gate::Not(Wire(7)));
+    assert_eq!(
+        circuit.try_compile(&CompilerOptions::default()).unwrap_err(),
+        OddError::InvalidWire { wire: 7 }
+    );
+
+    let compiled = BooleanCircuit::new(2).compile(&CompilerOptions::default());
+    let backend = SimulatedServerKey::new();
+    let parity = backend.trivial_encrypt(1, &Encoding::parity_encoding());
+    assert_eq!(
+        compiled.try_execute(&backend, std::slice::from_ref(&parity)).unwrap_err(),
+        OddError::LengthMismatch { expected: 2, got: 1 }
+    );
+    assert_eq!(
+        compiled.try_execute(&backend, &[parity.clone(), parity]).unwrap_err(),
+        OddError::InvalidEncoding
+    );
+}
@@ -6,7 +6,12 @@
 use crate::odd::{engine::WithThreadLocalEngine, prelude::*};
 use crate::odd::parameters::CustomOddParameters;
 use crate::core_crypto::entities::*;
+use crate::core_crypto::prelude::DynamicDistribution;
+use serde::{Deserialize, Serialize};
 use std::fmt::{Debug, Formatter};
+use tfhe_versionable::Versionize;
+
+use super::backward_compatibility::client_key::ClientKeyVersions;
 
 use super::{ciphertext::Encoding, engine::OddEngine};
 /// A structure containing the client key, which must be kept secret.
@@ -17,13 +22,18 @@ use super::{ciphertext::Encoding, engine::OddEngine};
 /// * `glwe_secret_key` - a GLWE secret key, used to generate the bootstrapping keys and key
 /// switching keys.
 /// * `parameters` - the cryptographic parameter set.
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize, Versionize)]
+#[versionize(ClientKeyVersions)]
 pub struct ClientKey {
     pub(crate) lwe_secret_key: LweSecretKeyOwned<u64>,
     pub(crate) glwe_secret_key: GlweSecretKeyOwned<u64>,
     pub(crate) parameters: CustomOddParameters,
 }
 
+impl crate::named::Named for ClientKey {
+    const NAME: &'static str = "odd::ClientKey";
+}
+
 impl PartialEq for ClientKey {
     fn eq(&self, other: &Self) -> bool {
         self.parameters == other.parameters
@@ -45,12 +55,43 @@ impl Debug for ClientKey {
 }
 
 impl ClientKey {
+    /// Return the secret key selected by `encryption_key_choice` and its noise distribution
+    pub fn encryption_key_and_noise(
+        &self,
+    ) -> (LweSecretKeyView<'_, u64>, DynamicDistribution<u64>) {
+        match self.parameters.encryption_key_choice {
+            EncryptionKeyChoice::Big => (
+                self.glwe_secret_key.as_lwe_secret_key(),
+                self.parameters.glwe_noise_distribution,
+            ),
+            EncryptionKeyChoice::Small => (
+                self.lwe_secret_key.as_view(),
+                self.parameters.lwe_noise_distribution,
+            ),
+        }
+    }
 
     pub fn encrypt_arithmetic(&self, message: u64, encoding : &Encoding) -> Ciphertext {
-        assert!(encoding.is_canonical());
+        self.try_encrypt_arithmetic(message, encoding).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Encrypt a message of Zo with the given canonical encoding, returning an error if the
+    /// encoding is not canonical or if the message is not in Zo.
+    pub fn try_encrypt_arithmetic(&self, message: u64, encoding : &Encoding) -> Result<Ciphertext, OddError> {
         OddEngine::with_thread_local_mut(|engine| engine.encrypt_arithmetic(message, encoding, self))
     }
 
+    /// Encrypt a message of Zo with the given canonical encoding, returning a compressed
+    /// ciphertext (a seed and the body instead of the n + 1 words of an LWE ciphertext of
+    /// dimension n) to be decompressed on the server side.
+    pub fn encrypt_arithmetic_compressed(&self, message: u64, encoding : &Encoding) -> CompressedCiphertext {
+        self.try_encrypt_arithmetic_compressed(message, encoding).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_encrypt_arithmetic_compressed(&self, message: u64, encoding : &Encoding) -> Result<CompressedCiphertext, OddError> {
+        OddEngine::with_thread_local_mut(|engine| engine.encrypt_arithmetic_compressed(message, encoding, self))
+    }
+
     
 
     /// Decrypt a ciphertext encrypting a Boolean message using the client key.
@@ -73,6 +114,12 @@ impl ClientKey {
     /// # }
     /// ```
     pub fn decrypt(&self, ct: &Ciphertext) -> u64 {
+        self.try_decrypt(ct).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Decrypt a ciphertext, returning an error if the decrypted value does not belong to any
+    /// part of its encoding (e.g. because the noise grew too large).
+    pub fn try_decrypt(&self, ct: &Ciphertext) -> Result<u64, OddError> {
         OddEngine::with_thread_local_mut(|engine| engine.decrypt(ct, self))
     }
 
@@ -0,0 +1,265 @@
+//! Search of (o,p)-encodings realising a function with a single bootstrapping.
+//!
+//! A gadget computes a function f of k elements of Zo by summing their encryptions and
+//! bootstrapping the sum, as [`OddEngine::exec_gadget_with_extraction`] does. This only works if
+//! the sums of two tuples with different images by f never collide in Zp: the sums then form the
+//! parts of an intermediate encoding, which the bootstrapping maps to any output encoding.
+//!
+//! [`search_gadget`] looks for the smallest modulus p and the coefficients c_i such that encoding
+//! the i-th input as x -> c_i * e(x) (e(x) = x, or e(x) = 2x - (o - 1) for negacyclic inputs)
+//! gives such a gadget.
+//!
+//! [`OddEngine::exec_gadget_with_extraction`]: crate::odd::engine::OddEngine::exec_gadget_with_extraction
+
+use std::collections::{HashMap, HashSet};
+
+use crate::odd::prelude::*;
+
+/// Parity required for the modulus p of a gadget
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ModulusParity {
+    #[default]
+    Any,
+    Odd,
+    Even,
+}
+
+/// Constraints on the gadgets returned by [`search_gadget`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct GadgetConstraints {
+    pub parity: ModulusParity,
+    /// Smallest modulus p to try
+    pub min_modulus: u64,
+    /// Largest modulus p to try
+    pub max_modulus: u64,
+    /// Encode the inputs negacyclically, i.e. with e(o - 1 - x) = -e(x), as the binary encodings
+    /// {-c, c} of the recomposition of a nibble
+    pub negacyclic_inputs: bool,
+}
+
+impl Default for GadgetConstraints {
+    fn default() -> Self {
+        Self {
+            parity: ModulusParity::Any,
+            min_modulus: 2,
+            max_modulus: 64,
+            negacyclic_inputs: false,
+        }
+    }
+}
+
+/// Encodings realising a function with [`OddEngine::exec_gadget_with_extraction`]: the inputs
+/// encrypted under `input_encodings` are summed, and the sum is bootstrapped from
+/// `intermediate_encoding` to any encoding of the outputs.
+///
+/// [`OddEngine::exec_gadget_with_extraction`]: crate::odd::engine::OddEngine::exec_gadget_with_extraction
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gadget {
+    pub modulus: u64,
+    /// Coefficient c_i applied to the base encoding of the i-th input
+    pub coefficients: Vec<u64>,
+    pub input_encodings: Vec<Encoding>,
+    pub intermediate_encoding: Encoding,
+}
+
+/// Element of Zp encoding x in the base encoding of the inputs
+fn base_encoding(x: u64, origin_modulus: u64, modulus: u64, negacyclic: bool) -> u64 {
+    if negacyclic {
+        (2 * x + modulus * origin_modulus - (origin_modulus - 1)) % modulus
+    } else {
+        x % modulus
+    }
+}
+
+/// Tuples of `arity` elements of Zo, with the image of each of them by f
+fn truth_table(origin_modulus: u64, arity: usize, f: &dyn Fn(&[u64]) -> u64) -> Vec<(Vec<u64>, u64)> {
+    let count = origin_modulus.pow(arity as u32);
+    (0..count)
+        .map(|mut index| {
+            let inputs: Vec<u64> = (0..arity)
+                .map(|_| {
+                    let x = index % origin_modulus;
+                    index /= origin_modulus;
+                    x
+                })
+                .collect();
+            let output = f(&inputs);
+            (inputs, output)
+        })
+        .collect()
+}
+
+/// Intermediate encoding of the sums, if the inputs encoded by `values` (`values[i][x]` being the
+/// element of Zp encoding x in the i-th input) never sum to the same element for different outputs
+fn intermediate_encoding(
+    modulus: u64,
+    values: &[&Vec<u64>],
+    table: &[(Vec<u64>, u64)],
+    output_modulus: u64,
+) -> Option<Encoding> {
+    let mut outputs: HashMap<u64, u64> = HashMap::new();
+    for (inputs, output) in table {
+        let sum = inputs.iter().zip(values).map(|(x, v)| v[*x as usize]).sum::<u64>() % modulus;
+        if *outputs.entry(sum).or_insert(*output) != *output {
+            return None;
+        }
+    }
+    let mut parts = vec![HashSet::new(); output_modulus as usize];
+    for (sum, output) in outputs {
+        parts[output as usize].insert(sum);
+    }
+    Encoding::try_new(output_modulus, parts, modulus).ok()
+}
+
+/// Search a gadget computing f, a function of `arity` elements of Zo (o being `origin_modulus`)
+/// to Z_{o'} (o' - 1 being the largest output of f), under `constraints`. The moduli are tried in
+/// increasing order (the noise margin shrinks with p), and the coefficients in lexicographic
+/// order. Returns `None` if no modulus between `constraints.min_modulus` and
+/// `constraints.max_modulus` fits.
+///
+/// The search enumerates p^k coefficients and o^k tuples for each modulus, so it is meant for
+/// small gadgets.
+pub fn search_gadget(
+    origin_modulus: u64,
+    arity: usize,
+    f: &dyn Fn(&[u64]) -> u64,
+    constraints: &GadgetConstraints,
+) -> Option<Gadget> {
+    let table = truth_table(origin_modulus, arity, f);
+    let output_modulus = table.iter().map(|(_, output)| output + 1).max().unwrap_or(1);
+    let parity_fits = |modulus: &u64| match constraints.parity {
+        ModulusParity::Any => true,
+        ModulusParity::Odd => modulus % 2 == 1,
+        ModulusParity::Even => modulus % 2 == 0,
+    };
+    for modulus in (constraints.min_modulus.max(2)..=constraints.max_modulus).filter(parity_fits) {
+        // the coefficients giving a valid input encoding, with the values of this encoding
+        let valid: Vec<(u64, Vec<u64>)> = (0..modulus)
+            .map(|coefficient| {
+                let values: Vec<u64> = (0..origin_modulus)
+                    .map(|x| {
+                        coefficient * base_encoding(x, origin_modulus, modulus, constraints.negacyclic_inputs)
+                            % modulus
+                    })
+                    .collect();
+                (coefficient, values)
+            })
+            .filter(|(_, values)| Encoding::try_new_canonical(origin_modulus, values.clone(), modulus).is_ok())
+            .collect();
+        if valid.is_empty() {
+            continue;
+        }
+        let mut indices = vec![0; arity];
+        loop {
+            let values: Vec<&Vec<u64>> = indices.iter().map(|i| &valid[*i].1).collect();
+            if let Some(intermediate_encoding) = intermediate_encoding(modulus, &values, &table, output_modulus) {
+                return Some(Gadget {
+                    modulus,
+                    coefficients: indices.iter().map(|i| valid[*i].0).collect(),
+                    input_encodings: values
+                        .iter()
+                        .map(|v| Encoding::new_canonical(origin_modulus, (*v).clone(), modulus))
+                        .collect(),
+                    intermediate_encoding,
+                });
+            }
+            // next coefficients, the last one varying first
+            match (0..arity).rev().find(|i| indices[*i] + 1 < valid.len()) {
+                Some(i) => {
+                    indices[i] += 1;
+                    indices[i + 1..].iter_mut().for_each(|index| *index = 0);
+                }
+                None => break,
+            }
+        }
+    }
+    None
+}
+
+
+#[test]
+fn test_search_boolean_gadgets() {
+    use crate::odd::engine::{OddEngine, WithThreadLocalEngine};
+    use crate::odd::TEST_PARAMETERS;
+
+    let (cks, sks) = gen_keys(&TEST_PARAMETERS);
+    let majority = |x: &[u64]| (x.iter().sum::<u64>() >= 2) as u64;
+    let and_xor = |x: &[u64]| (x[0] & x[1]) ^ x[2];
+    let constraints = GadgetConstraints::default();
+    let odd_negacyclic = GadgetConstraints {
+        parity: ModulusParity::Odd,
+        negacyclic_inputs: true,
+        ..constraints
+    };
+    let cases = [
+        (&majority as &dyn Fn(&[u64]) -> u64, &constraints),
+        (&and_xor, &constraints),
+        (&and_xor, &odd_negacyclic),
+    ];
+    for (f, constraints) in cases {
+        let gadget = search_gadget(2, 3, f, constraints).unwrap();
+        match constraints.parity {
+            ModulusParity::Odd => assert_eq!(gadget.modulus % 2, 1),
+            _ => assert!(gadget.modulus <= 8),
+        }
+        for inputs in 0..8u64 {
+            let x: Vec<u64> = (0..3).map(|i| (inputs >> i) & 1).collect();
+            let cts = x
+                .iter()
+                .zip(&gadget.input_encodings)
+                .map(|(xi, encoding)| cks.encrypt_arithmetic(*xi, encoding))
+                .collect::<Vec<_>>();
+            let result = OddEngine::with_thread_local_mut(|engine| {
+                engine.exec_gadget_with_extraction(
+                    &gadget.input_encodings,
+                    &gadget.intermediate_encoding,
+                    &Encoding::parity_encoding(),
+                    &cts,
+                    &sks,
+                )
+            })
+            .unwrap();
+            assert_eq!(cks.decrypt(&result), f(&x), "inputs {x:?}, gadget {gadget:?}");
+        }
+    }
+}
+
+
+#[test]
+fn test_search_arithmetic_gadgets() {
+    // the recomposition of a nibble needs 16 distinct sums, so p = 17 with an odd modulus
+    let recomposition = |x: &[u64]| x[0] + 2 * x[1] + 4 * x[2] + 8 * x[3];
+    let odd = GadgetConstraints {
+        parity: ModulusParity::Odd,
+        ..GadgetConstraints::default()
+    };
+    let gadget = search_gadget(2, 4, &recomposition, &odd).unwrap();
+    assert_eq!(gadget.modulus, 17);
+    assert_eq!(gadget.intermediate_encoding.get_origin_modulus(), 16);
+
+    // a sum of two elements of Z3 needs 5 distinct sums, negacyclic inputs included
+    let sum = |x: &[u64]| x[0] + x[1];
+    let gadget = search_gadget(3, 2, &sum, &GadgetConstraints::default()).unwrap();
+    assert_eq!(gadget.modulus, 5);
+    let negacyclic = GadgetConstraints {
+        negacyclic_inputs: true,
+        ..GadgetConstraints::default()
+    };
+    let gadget = search_gadget(3, 2, &sum, &negacyclic).unwrap();
+    for encoding in &gadget.input_encodings {
+        let first = encoding.get_part_single_value_if_canonical(0);
+        assert_eq!(encoding.get_part_single_value_if_canonical(2), encoding.negative_on_p_ring(first));
+    }
+
+    // no modulus fits a bound below the number of distinct outputs
+    let small = GadgetConstraints {
+        max_modulus: 4,
+        ..GadgetConstraints::default()
+    };
+    assert!(search_gadget(3, 2, &sum, &small).is_none());
+    let large = GadgetConstraints {
+        min_modulus: 9,
+        ..GadgetConstraints::default()
+    };
+    assert_eq!(search_gadget(3, 2, &sum, &large).unwrap().modulus, 9);
+}
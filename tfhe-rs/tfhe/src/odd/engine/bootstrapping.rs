@@ -1,22 +1,101 @@
 #![allow(non_snake_case)] 
 
 
-use crate::core_crypto::prelude::{CiphertextModulus, Container, ContiguousEntityContainer, ContiguousEntityContainerMut, Fft, MonomialDegree, PBSOrder, PlaintextCount};
+use crate::core_crypto::prelude::{CiphertextModulus, Container, ContainerMut, ContiguousEntityContainer, ContiguousEntityContainerMut, Fft, GlweSize, MonomialDegree, PBSOrder, PlaintextCount};
 use crate::odd::engine::polynomial_algorithms::polynomial_wrapping_monic_monomial_mul_assign;
-use crate::odd::engine::slice_algorithms::slice_wrapping_add_assign;
+use crate::odd::engine::slice_algorithms::{slice_wrapping_add_assign, slice_wrapping_sub_assign};
 use crate::odd::prelude::*;
 use crate::core_crypto::algorithms::*;
 use crate::core_crypto::commons::computation_buffers::ComputationBuffers;
 use crate::core_crypto::commons::generators::{DeterministicSeeder, EncryptionRandomGenerator};
 use crate::core_crypto::commons::math::random::{DefaultRandomGenerator, Seeder};
 use crate::core_crypto::entities::*;
-
-use self::polynomial_algorithms::polynomial_karatsuba_wrapping_mul;
+use crate::core_crypto::fft_impl::fft64::math::fft::FftView;
+use crate::odd::backward_compatibility::server_key::{CompressedServerKeyVersions, ServerKeyVersions};
+use dyn_stack::PodStack;
+use itertools::Itertools;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tfhe_versionable::Versionize;
 
 
 /////Accumulator used in the BlindRotate part of the bootstrapping
 type Accumulator = Vec<u64>;
 
+/// Number of accumulators (and of vi polynomials) kept by each engine, the caches being emptied
+/// when they are full
+const LOOKUP_TABLE_CACHE_CAPACITY: usize = 256;
+
+
+/// Key of the caches of accumulators and of vi polynomials, which only depend on the parts of the
+/// intermediate encoding, on the output modulus, on the representatives and on the shape of the
+/// GLWE ciphertexts
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct LookupTableKey {
+    parts: Vec<Vec<u64>>,
+    modulus: u64,
+    output_modulus: u64,
+    representatives: Vec<u64>,
+    glwe_size: usize,
+    polynomial_size: usize,
+}
+
+/// Polynomial with few non-zero coefficients, such as the vi of a mvb (at most p of them)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct SparsePolynomial {
+    polynomial_size: PolynomialSize,
+    /// Degree and value of each non-zero coefficient
+    terms: Vec<(usize, u64)>,
+}
+
+impl SparsePolynomial {
+    pub(crate) fn polynomial_size(&self) -> PolynomialSize {
+        self.polynomial_size
+    }
+
+    //output += lhs * self mod X^N + 1, in O(N) per non-zero coefficient instead of a dense product
+    fn wrapping_mul_add_assign(&self, output : &mut [u64], lhs : &[u64]) {
+        let N = self.polynomial_size.0;
+        for &(degree, value) in &self.terms {
+            // lhs[j] X^(j + degree) wraps around to -lhs[j] X^(j + degree - N) when j + degree >= N
+            let (low, high) = lhs.split_at(N - degree);
+            for (out, x) in output[degree..].iter_mut().zip(low) {
+                *out = out.wrapping_add(x.wrapping_mul(value));
+            }
+            for (out, x) in output[..degree].iter_mut().zip(high) {
+                *out = out.wrapping_sub(x.wrapping_mul(value));
+            }
+        }
+    }
+
+    //constant coefficient of lhs * self mod X^N + 1
+    fn wrapping_mul_constant_term(&self, lhs : &[u64]) -> u64 {
+        let N = self.polynomial_size.0;
+        self.terms.iter().fold(0u64, |acc, &(degree, value)| match degree {
+            0 => acc.wrapping_add(lhs[0].wrapping_mul(value)),
+            _ => acc.wrapping_sub(lhs[N - degree].wrapping_mul(value)),
+        })
+    }
+}
+
+impl LookupTableKey {
+    fn new(enc_inter : &Encoding, enc_out : &Encoding, representatives : &[u64], server_key : &ServerKey) -> Self {
+        let parts = (0..enc_inter.get_origin_modulus())
+            .map(|i| enc_inter.get_part(i).iter().copied().sorted().collect())
+            .collect();
+        Self {
+            parts,
+            modulus: enc_inter.get_modulus(),
+            output_modulus: enc_out.get_modulus(),
+            representatives: representatives.to_vec(),
+            glwe_size: server_key.bootstrapping_key.glwe_size().0,
+            polynomial_size: server_key.bootstrapping_key.polynomial_size().0,
+        }
+    }
+}
+
 
 /// Memory used as buffer for the bootstrap
 ///
@@ -28,7 +107,8 @@ pub struct Memory {
 }
 
 pub struct BuffersRef<'a> {
-    pub(crate) lookup_table: GlweCiphertextMutView<'a, u64>,
+    // For the blind rotations whose result is not sample extracted in place
+    pub(crate) buffer_glwe: GlweCiphertextMutView<'a, u64>,
     // For the intermediate keyswitch result in the case of a big ciphertext
     pub(crate) buffer_lwe_after_ks: LweCiphertextMutView<'a, u64>,
     // For the intermediate PBS result in the case of a smallciphertext
@@ -37,10 +117,9 @@ pub struct BuffersRef<'a> {
 
 
 impl Memory {
-    //generate the vector of values to fill in each window gor given input and output encodings in simple bootstrapping
-    pub fn create_accumulator(encoding_in : &Encoding, encoding_out : &Encoding) -> Accumulator{
+    //generate the vector of values to fill in each window gor given input and output encodings in simple bootstrapping, representatives[i] being the ZpElem written for the ZoElem i
+    pub fn create_accumulator(encoding_in : &Encoding, encoding_out : &Encoding, representatives : &[u64]) -> Accumulator{
         assert!(encoding_in.is_valid());
-        assert!(encoding_out.is_canonical());
         let p = encoding_in.get_modulus();
         assert!(p % 2 == 1);
         let mut accu : Accumulator = vec![0;p.try_into().unwrap()];
@@ -50,7 +129,7 @@ impl Memory {
                 let i = encoding_in.inverse_encoding(k / 2);
                 //Finding the new ZpElem corresponding to i in encoding out
                 accu[k as usize] = match i{
-                    Some(i) => encoding_out.get_part_single_value_if_canonical(i),
+                    Some(i) => representatives[i as usize],
                     None => 0
                 };
             }
@@ -59,7 +138,7 @@ impl Memory {
                 let i = encoding_in.inverse_encoding((p + 1)/2 + (k - 1) / 2);
                 //Finding the new ZpElem corresponing to i in encoging out
                 accu[k as usize] = match i{
-                    Some(i) => encoding_out.negative_on_p_ring(encoding_out.get_part_single_value_if_canonical(i)),
+                    Some(i) => encoding_out.negative_on_p_ring(representatives[i as usize]),
                     None => 0
                 };
             }
@@ -71,9 +150,8 @@ impl Memory {
 
 
 
-    pub fn create_accumulator_wopbs(encoding_in : &Encoding, encoding_out : &Encoding) -> Accumulator{
+    pub fn create_accumulator_wopbs(encoding_in : &Encoding, representatives : &[u64]) -> Accumulator{
         assert!(encoding_in.is_valid());
-        assert!(encoding_out.is_canonical());
         let p = encoding_in.get_modulus();
         assert!(p % 2 == 0 && p != 2);
         let mut accu : Accumulator = vec![0;p.try_into().unwrap()];
@@ -82,7 +160,7 @@ impl Memory {
              let i = encoding_in.inverse_encoding(k);
              //Finding the new ZpElem corresponding to i in encoding out
              accu[k as usize] = match i{
-                 Some(i) => encoding_out.get_part_single_value_if_canonical(i),
+                 Some(i) => representatives[i as usize],
                  None => 0
              };
         }
@@ -138,32 +216,40 @@ impl Memory {
 
 
 
-    /// Return a tuple with buffers that matches the server key.
-    ///
-    /// - The first element is the accumulator for bootstrap step.
-    /// - The second element is a lwe buffer where the result of the of the bootstrap should be
-    ///   written
-    fn as_buffers(
-        &mut self,
+    /// Return the scratch buffers that match the server key, the accumulators being left to the
+    /// caller (see [`Self::create_lookup_table_accumulator`]).
+    fn as_buffers(&mut self, server_key: &ServerKey) -> BuffersRef<'_>{
+        let (buffer_glwe, buffer_lwe_after_ks, buffer_lwe_after_pbs) = self.allocate_ciphertexts_for_bootstrapping(server_key);
+        BuffersRef {
+            buffer_glwe,
+            buffer_lwe_after_ks,
+            buffer_lwe_after_pbs,
+        }
+    }
+
+
+    /// Build the accumulator of a bootstrapping from `enc_in` to `enc_out`, as a trivial GLWE
+    /// ciphertext matching the server key.
+    pub(crate) fn create_lookup_table_accumulator(
         server_key: &ServerKey,
         enc_in : &Encoding,
-        enc_out : &Encoding
-    ) -> BuffersRef<'_>{
-        let (mut accumulator, buffer_lwe_after_ks, buffer_lwe_after_pbs) = self.allocate_ciphertexts_for_bootstrapping(server_key);
+        enc_out : &Encoding,
+        representatives : &[u64]
+    ) -> GlweCiphertextOwned<u64>{
+        let mut accumulator = GlweCiphertext::new(0u64, server_key.bootstrapping_key.glwe_size(), server_key.bootstrapping_key.polynomial_size(), CiphertextModulus::new_native());
 
         ////accumulator filling
         let p = enc_in.get_modulus();
         let new_p = enc_out.get_modulus() as u64;
-        accumulator.get_mut_mask().as_mut().fill(0u64);
         let N_poly: usize = accumulator.get_mut_body().as_mut().len();    //(N degree of the polynomial)
 
         if p != 2{  //wopbs is managed in this block as well
 
             let accu_data = if p % 2 == 1{
-                Self::create_accumulator(enc_in, enc_out)
+                Self::create_accumulator(enc_in, enc_out, representatives)
             }
             else{
-                Self::create_accumulator_wopbs(enc_in, enc_out)
+                Self::create_accumulator_wopbs(enc_in, representatives)
             };
             // print!("Accumulator:");
             // accu_data.iter().enumerate().for_each(|(i, x)| print!("{}:{} |", i, x));
@@ -185,8 +271,8 @@ impl Memory {
         // for now, the case p=2 is still particular
         else{
             //check that we have negacyclicity
-            let new_false = enc_out.get_part_single_value_if_canonical(0);
-            let new_true = enc_out.get_part_single_value_if_canonical(1);
+            let new_false = representatives[0];
+            let new_true = representatives[1];
 
             assert!(new_false == (new_p as u64 - new_true) % new_p as u64);
             //Is the 0 window true or false ?
@@ -201,37 +287,24 @@ impl Memory {
             accumulator.get_mut_body().as_mut()[N_poly / 2..].fill(buffer_value as u64);   //filling of the second half window
         }
 
-        BuffersRef {
-            lookup_table: accumulator,
-            buffer_lwe_after_ks,
-            buffer_lwe_after_pbs,
-        }
+        accumulator
     }
 
 
 
 
-    pub fn as_buffers_common_factor(
-        &mut self,
+    /// Build the accumulator blind rotated to get the common factor of a mvb with output `enc_out`.
+    pub(crate) fn create_common_factor_accumulator(
         server_key: &ServerKey,
         enc_out : &Encoding
-    ) -> BuffersRef<'_>{
-        
-        let (mut accumulator, buffer_lwe_after_ks,  buffer_lwe_after_pbs) = self.allocate_ciphertexts_for_bootstrapping(server_key);
-
-        ////accumulator filling
-        accumulator.get_mut_mask().as_mut().fill(0u64);
+    ) -> GlweCiphertextOwned<u64>{
+        let mut accumulator = GlweCiphertext::new(0u64, server_key.bootstrapping_key.glwe_size(), server_key.bootstrapping_key.polynomial_size(), CiphertextModulus::new_native());
 
         let constant = if enc_out.get_modulus() % 2 == 0{1u128 << 63} else {1u128 << 64}; 
 
         accumulator.get_mut_body().as_mut().fill((constant / (enc_out.get_modulus() as u128)) as u64);   //filling the common factor with ones (no taking into account the tau factor here) (il faudra mettre un scaling sinon on va se noyer dans le bruit)
 
-
-        BuffersRef {
-            lookup_table: accumulator,
-            buffer_lwe_after_ks,
-            buffer_lwe_after_pbs,
-        }
+        accumulator
     }
 
 }
@@ -249,12 +322,101 @@ impl Memory {
 /// In more details, it contains:
 /// * `bootstrapping_key` - a public key, used to perform the bootstrapping operation.
 /// * `key_switching_key` - a public key, used to perform the key-switching operation.
-#[derive(Clone)]
+/// * `lwe_packing_keyswitch_key` - a public key, used to pack LWE ciphertexts into a new
+///   accumulator during tree bootstrapping.
+/// * `parameters` - the parameters of the keys, from which the noise of the ciphertexts is
+///   estimated.
+/// * `noise_check` - what to do when this estimate gets too large (see [`ServerKey::set_noise_check`]).
+#[derive(Clone, Serialize, Deserialize, Versionize)]
+#[versionize(ServerKeyVersions)]
 pub struct ServerKey {
     pub(crate) bootstrapping_key: FourierLweBootstrapKeyOwned,
     pub(crate) key_switching_key: LweKeyswitchKeyOwned<u64>,
     pub(crate) lwe_packing_keyswitch_key : LwePackingKeyswitchKeyOwned<u64>,
-    pub(crate) pbs_order: PBSOrder
+    pub(crate) pbs_order: PBSOrder,
+    pub(crate) parameters: CustomOddParameters,
+    #[serde(skip)]
+    pub(crate) noise_check: NoiseCheck,
+}
+
+impl crate::named::Named for ServerKey {
+    const NAME: &'static str = "odd::ServerKey";
+}
+
+/// A structure containing a compressed server public key.
+///
+/// The masks of the bootstrapping key, the key switching key and the packing key switching key
+/// are replaced by the seeds used to generate them. It is meant to be sent to the server, which
+/// then calls [`CompressedServerKey::decompress`] to get back a [`ServerKey`].
+#[derive(Clone, Serialize, Deserialize, Versionize)]
+#[versionize(CompressedServerKeyVersions)]
+pub struct CompressedServerKey {
+    pub(crate) bootstrapping_key: SeededLweBootstrapKeyOwned<u64>,
+    pub(crate) key_switching_key: SeededLweKeyswitchKeyOwned<u64>,
+    pub(crate) lwe_packing_keyswitch_key: SeededLwePackingKeyswitchKeyOwned<u64>,
+    pub(crate) pbs_order: PBSOrder,
+    pub(crate) parameters: CustomOddParameters,
+}
+
+impl crate::named::Named for CompressedServerKey {
+    const NAME: &'static str = "odd::CompressedServerKey";
+}
+
+impl CompressedServerKey {
+    /// Decompress a [`CompressedServerKey`] into a [`ServerKey`].
+    pub fn decompress(&self) -> ServerKey {
+        let Self {
+            bootstrapping_key: compressed_bootstrapping_key,
+            key_switching_key: compressed_key_switching_key,
+            lwe_packing_keyswitch_key: compressed_packing_keyswitch_key,
+            pbs_order,
+            parameters,
+        } = self;
+
+        let ((key_switching_key, lwe_packing_keyswitch_key), bootstrapping_key) = rayon::join(
+            || {
+                rayon::join(
+                    || {
+                        compressed_key_switching_key
+                            .clone()
+                            .par_decompress_into_lwe_keyswitch_key()
+                    },
+                    || {
+                        compressed_packing_keyswitch_key
+                            .clone()
+                            .decompress_into_lwe_packing_keyswitch_key()
+                    },
+                )
+            },
+            || {
+                let decompressed_bootstrapping_key = compressed_bootstrapping_key
+                    .clone()
+                    .par_decompress_into_lwe_bootstrap_key();
+
+                let mut bootstrapping_key = FourierLweBootstrapKeyOwned::new(
+                    decompressed_bootstrapping_key.input_lwe_dimension(),
+                    decompressed_bootstrapping_key.glwe_size(),
+                    decompressed_bootstrapping_key.polynomial_size(),
+                    decompressed_bootstrapping_key.decomposition_base_log(),
+                    decompressed_bootstrapping_key.decomposition_level_count(),
+                );
+                par_convert_standard_lwe_bootstrap_key_to_fourier(
+                    &decompressed_bootstrapping_key,
+                    &mut bootstrapping_key,
+                );
+                bootstrapping_key
+            },
+        );
+
+        ServerKey {
+            bootstrapping_key,
+            key_switching_key,
+            lwe_packing_keyswitch_key,
+            pbs_order: *pbs_order,
+            parameters: *parameters,
+            noise_check: NoiseCheck::default(),
+        }
+    }
 }
 
 impl ServerKey {
@@ -276,9 +438,26 @@ impl ServerKey {
 }
 
 
+/// FFT plan of the blind rotations with a bootstrapping key of this shape, the scratch memory of
+/// the engine being sized for them
+struct BlindRotationPlan {
+    glwe_size: GlweSize,
+    polynomial_size: PolynomialSize,
+    fft: Fft,
+}
+
+
 /// Perform ciphertext bootstraps on the CPU
 pub(crate) struct Bootstrapper {
     memory: Memory,
+    /// Accumulators of the simple bootstrappings already performed by this engine
+    accumulator_cache: HashMap<LookupTableKey, Arc<GlweCiphertextOwned<u64>>>,
+    /// vi polynomials of the mvb already performed by this engine
+    vi_cache: HashMap<LookupTableKey, Arc<SparsePolynomial>>,
+    /// Accumulators of the common factor of the mvb, for each output modulus and shape of key
+    common_factor_cache: HashMap<(u64, usize, usize), Arc<GlweCiphertextOwned<u64>>>,
+    /// Plan of the last bootstrapping key used by this engine, rebuilt only when the shape changes
+    blind_rotation_plan: Option<BlindRotationPlan>,
     /// A structure containing two CSPRNGs to generate material for encryption like public masks
     /// and secret errors.
     ///
@@ -287,7 +466,6 @@ pub(crate) struct Bootstrapper {
     /// encryption.
     pub(crate) encryption_generator: EncryptionRandomGenerator<DefaultRandomGenerator>,
     pub(crate) computation_buffers: ComputationBuffers,
-    #[allow(dead_code)]
     pub(crate) seeder: DeterministicSeeder<DefaultRandomGenerator>,
 }
 
@@ -296,6 +474,10 @@ impl Bootstrapper {
     pub fn new(seeder: &mut dyn Seeder) -> Self {
         Self {
             memory: Memory::default(),
+            accumulator_cache: HashMap::new(),
+            vi_cache: HashMap::new(),
+            common_factor_cache: HashMap::new(),
+            blind_rotation_plan: None,
             encryption_generator: EncryptionRandomGenerator::<_>::new(seeder.seed(), seeder),
             computation_buffers: ComputationBuffers::default(),
             seeder: DeterministicSeeder::<_>::new(seeder.seed()),
@@ -331,6 +513,9 @@ impl Bootstrapper {
                 .unaligned_bytes_required(),
         );
 
+        // the scratch memory no longer matches the plan of the blind rotations
+        self.blind_rotation_plan = None;
+
         // Conversion to fourier domain
         par_convert_standard_lwe_bootstrap_key_to_fourier(
             &standard_bootstrapping_key,
@@ -366,6 +551,8 @@ impl Bootstrapper {
             key_switching_key: ksk,
             lwe_packing_keyswitch_key : packing_ksk,
             pbs_order: cks.parameters.encryption_key_choice.into(),
+            parameters: cks.parameters,
+            noise_check: NoiseCheck::default(),
         }
     }
 
@@ -373,154 +560,276 @@ impl Bootstrapper {
 
 
 
-    pub(crate) fn bootstrap(
-        &mut self,
-        input: &LweCiphertextOwned<u64>,
-        accumulator : &GlweCiphertext<Vec<u64>>,
-        server_key: &ServerKey) -> LweCiphertextOwned<u64> {
-        // let BuffersRef {
-        //     lookup_table: accumulator,
-        //     mut buffer_lwe_after_pbs,
-        //     ..
-        // } = self.memory.as_buffers(server_key);
-
-        let mut buffer_lwe_after_pbs = LweCiphertext::new(0u64, server_key
-            .bootstrapping_key
-            .output_lwe_dimension()
-            .to_lwe_size(), CiphertextModulus::new_native());
+    pub(crate) fn new_compressed_server_key(&mut self, cks: &ClientKey) -> CompressedServerKey {
+        let bootstrapping_key = par_allocate_and_generate_new_seeded_lwe_bootstrap_key(
+            &cks.lwe_secret_key,
+            &cks.glwe_secret_key,
+            cks.parameters.pbs_base_log,
+            cks.parameters.pbs_level,
+            cks.parameters.glwe_noise_distribution,
+            CiphertextModulus::new_native(),
+            &mut self.seeder,
+        );
 
-        let fourier_bsk = &server_key.bootstrapping_key;
+        let big_lwe_secret_key = cks.glwe_secret_key.clone().into_lwe_secret_key();
 
-        let fft = Fft::new(fourier_bsk.polynomial_size());
-        let fft = fft.as_view();
+        // creation of the key switching key
+        let key_switching_key = allocate_and_generate_new_seeded_lwe_keyswitch_key(
+            &big_lwe_secret_key,
+            &cks.lwe_secret_key,
+            cks.parameters.ks_base_log,
+            cks.parameters.ks_level,
+            cks.parameters.lwe_noise_distribution,
+            CiphertextModulus::new_native(),
+            &mut self.seeder,
+        );
 
-        self.computation_buffers.resize(
-            programmable_bootstrap_lwe_ciphertext_mem_optimized_requirement::<u64>(
-                fourier_bsk.glwe_size(),
-                fourier_bsk.polynomial_size(),
-                fft,
-            )
-            .unwrap()
-            .unaligned_bytes_required(),
+        let lwe_packing_keyswitch_key = allocate_and_generate_new_seeded_lwe_packing_keyswitch_key(
+            &big_lwe_secret_key,
+            &cks.glwe_secret_key,
+            cks.parameters.ks_base_log,
+            cks.parameters.ks_level,
+            cks.parameters.glwe_noise_distribution,
+            CiphertextModulus::new_native(),
+            &mut self.seeder,
         );
-        let stack = self.computation_buffers.stack();
 
+        CompressedServerKey {
+            bootstrapping_key,
+            key_switching_key,
+            lwe_packing_keyswitch_key,
+            pbs_order: cks.parameters.encryption_key_choice.into(),
+            parameters: cks.parameters,
+        }
+    }
 
-        let mut output_glwe = GlweCiphertext::new(0u64, accumulator.glwe_size(), accumulator.polynomial_size(), accumulator.ciphertext_modulus());
+    //FFT plan and scratch memory of the blind rotations with the bootstrapping key of server_key, only rebuilt when the shape of the key changes (takes the fields rather than self so that the memory buffers can be borrowed at the same time)
+    fn blind_rotation_context<'a>(
+        plan : &'a mut Option<BlindRotationPlan>,
+        computation_buffers : &'a mut ComputationBuffers,
+        server_key: &ServerKey
+    ) -> (FftView<'a>, &'a mut PodStack){
+        let fourier_bsk = &server_key.bootstrapping_key;
+        let (glwe_size, polynomial_size) = (fourier_bsk.glwe_size(), fourier_bsk.polynomial_size());
+        let outdated = plan.as_ref().is_none_or(|plan| plan.glwe_size != glwe_size || plan.polynomial_size != polynomial_size);
+        if outdated{
+            let fft = Fft::new(polynomial_size);
+            computation_buffers.resize(
+                programmable_bootstrap_lwe_ciphertext_mem_optimized_requirement::<u64>(
+                    glwe_size,
+                    polynomial_size,
+                    fft.as_view(),
+                )
+                .unwrap()
+                .unaligned_bytes_required(),
+            );
+            *plan = Some(BlindRotationPlan { glwe_size, polynomial_size, fft });
+        }
+        (plan.as_ref().unwrap().fft.as_view(), computation_buffers.stack())
+    }
 
-        // programmable_bootstrap_lwe_ciphertext_mem_optimized(
-        //     input,
-        //     &mut buffer_lwe_after_pbs,
-        //     &accumulator,
-        //     fourier_bsk,
-        //     fft,
-        //     stack,
-        // );
 
+    //blind rotation of accumulator by input, the result being written in output (takes the fields rather than self, so that output can be a buffer of the memory)
+    fn blind_rotate_into<OutputCont: ContainerMut<Element = u64>>(
+        plan : &mut Option<BlindRotationPlan>,
+        computation_buffers : &mut ComputationBuffers,
+        input: &LweCiphertextOwned<u64>,
+        output: &mut GlweCiphertext<OutputCont>,
+        accumulator : &GlweCiphertext<Vec<u64>>,
+        server_key: &ServerKey
+    ){
+        let (fft, stack) = Self::blind_rotation_context(plan, computation_buffers, server_key);
         programmable_bootstrap_lwe_ciphertext_without_sample_extract_mem_optimized(
-            input, 
-            &mut output_glwe, 
-            &accumulator, 
-            fourier_bsk, fft, stack);
+            input,
+            output,
+            accumulator,
+            &server_key.bootstrapping_key,
+            fft,
+            stack,
+        );
+    }
 
-        // Self::decrypt_glwe_with_builtin_function(&client_key_debug, &output_glwe);
-        
-        extract_lwe_sample_from_glwe_ciphertext(&output_glwe, &mut buffer_lwe_after_pbs, MonomialDegree(0));
 
-        LweCiphertext::from_container(
-            buffer_lwe_after_pbs.as_ref().to_owned(),
-            input.ciphertext_modulus(),
-        )
+    //blind rotation of accumulator by input, the result being written in the glwe buffer of the memory
+    fn blind_rotate(
+        &mut self,
+        input: &LweCiphertextOwned<u64>,
+        accumulator : &GlweCiphertext<Vec<u64>>,
+        server_key: &ServerKey
+    ) -> GlweCiphertextMutView<'_, u64> {
+        let BuffersRef { mut buffer_glwe, .. } = self.memory.as_buffers(server_key);
+        Self::blind_rotate_into(&mut self.blind_rotation_plan, &mut self.computation_buffers, input, &mut buffer_glwe, accumulator, server_key);
+        buffer_glwe
     }
 
 
-    //perform the BlindRotation of v0
-    pub fn bootstrap_common_factor(
+    //bootstrapping of input by accumulator, the result being extracted in output (an LWE of the big key)
+    pub(crate) fn bootstrap<OutputCont: ContainerMut<Element = u64>>(
         &mut self,
         input: &LweCiphertextOwned<u64>,
-        enc_out : &Encoding,
+        output: &mut LweCiphertext<OutputCont>,
+        accumulator : &GlweCiphertext<Vec<u64>>,
         server_key: &ServerKey
-        ) -> GlweCiphertextOwned<u64> {
-        let BuffersRef {
-            lookup_table:  accumulator,
-            ..
-        } = self.memory.as_buffers_common_factor(server_key, enc_out);
-
+    ){
+        let rotated = self.blind_rotate(input, accumulator, server_key);
+        extract_lwe_sample_from_glwe_ciphertext(&rotated, output, MonomialDegree(0));
+    }
 
-        let fourier_bsk = &server_key.bootstrapping_key;
 
-        let fft = Fft::new(fourier_bsk.polynomial_size());
-        let fft = fft.as_view();
+    //accumulator of the BlindRotation of v0 for the modulus of enc_out
+    fn common_factor_accumulator(&mut self, enc_out : &Encoding, server_key: &ServerKey) -> Arc<GlweCiphertextOwned<u64>>{
+        let key = (enc_out.get_modulus(), server_key.bootstrapping_key.glwe_size().0, server_key.bootstrapping_key.polynomial_size().0);
+        self.common_factor_cache
+            .entry(key)
+            .or_insert_with(|| Arc::new(Memory::create_common_factor_accumulator(server_key, enc_out)))
+            .clone()
+    }
 
-        self.computation_buffers.resize(
-            programmable_bootstrap_lwe_ciphertext_mem_optimized_requirement::<u64>(
-                fourier_bsk.glwe_size(),
-                fourier_bsk.polynomial_size(),
-                fft,
-            )
-            .unwrap()
-            .unaligned_bytes_required(),
-        );
-        let stack = self.computation_buffers.stack();
 
+    //perform the BlindRotation of v0, the result being written in the glwe buffer of the memory
+    pub fn bootstrap_common_factor(
+        &mut self,
+        input: &LweCiphertextOwned<u64>,
+        enc_out : &Encoding,
+        server_key: &ServerKey
+    ) -> GlweCiphertextMutView<'_, u64> {
+        let accumulator = self.common_factor_accumulator(enc_out, server_key);
+        self.blind_rotate(input, &accumulator, server_key)
+    }
 
-        let mut output = GlweCiphertext::new(0u64, accumulator.glwe_size(), accumulator.polynomial_size(), accumulator.ciphertext_modulus());
 
-        programmable_bootstrap_lwe_ciphertext_without_sample_extract_mem_optimized(
-            input,
-            &mut output,
-            &accumulator,
-            fourier_bsk,
-            fft,
-            stack,
-        );
+    //perform the BlindRotation of v0, the result being written in output
+    pub fn bootstrap_common_factor_into<OutputCont: ContainerMut<Element = u64>>(
+        &mut self,
+        input: &LweCiphertextOwned<u64>,
+        output: &mut GlweCiphertext<OutputCont>,
+        enc_out : &Encoding,
+        server_key: &ServerKey
+    ){
+        let accumulator = self.common_factor_accumulator(enc_out, server_key);
+        Self::blind_rotate_into(&mut self.blind_rotation_plan, &mut self.computation_buffers, input, output, &accumulator, server_key);
+    }
 
 
-        // Self::decrypt_glwe_with_builtin_function(&client_key_debug, &output);
-        // println!("-----------------------");
+    //differences between consecutive windows of the accumulator of a simple bootstrapping, which are the coefficients of the vi of a mvb (the last one closing the negacyclic loop)
+    //the windows of an even input modulus do not cover the whole rotation, so the vi are only defined for an odd one
+    pub(crate) fn create_vi_differences(enc_in : &Encoding, enc_out : &Encoding, representatives : &[u64]) -> Result<Vec<u64>, OddError>{
+        if enc_in.get_modulus() % 2 == 0{
+            return Err(OddError::UnsupportedModulus { operation: "mvb", modulus: enc_in.get_modulus() });
+        }
+        let mut accumulator_data = Memory::create_accumulator(enc_in, enc_out, representatives);
+        let p = enc_in.get_modulus() as usize;
+        let mut new_p = enc_out.get_modulus();
 
-        GlweCiphertext::from_container(
-            output.as_ref().to_owned(),
-            output.polynomial_size(),
-            input.ciphertext_modulus(),
-        )
+        //The product of v0 with the vi doubles the values of the accumulator, so we have to divide them by 2. For an odd p_out, we multiply each accumulator values with the inverse mod p of 2.
+        if new_p % 2 == 1{
+            let inv2 = (new_p + 1) / 2;
+            accumulator_data = accumulator_data.iter().map(|x| x * inv2 % new_p).collect();
+        }
+        // Else, the division per 2 has been carried out in the v0 rotation (whose constant is 1/2p instead of 1/p), so the differences live in Z_2p.
+        else{
+            new_p *= 2;
+        }
+        let mut differences : Vec<u64> = (0..p-1)
+            .map(|i| (accumulator_data[i+1] as i32 - accumulator_data[i] as i32).rem_euclid(new_p as i32) as u64)
+            .collect();
+        differences.push((new_p as i32 - accumulator_data[0] as i32 - accumulator_data[p-1] as i32).rem_euclid(new_p as i32) as u64);
+        Ok(differences)
     }
 
 
     fn create_vi_for_mvb(
-        &mut self, 
         enc_in : &Encoding,
         enc_out : &Encoding,
+        representatives : &[u64],
         server_key: &ServerKey
-    ) -> Polynomial<Vec<u64>>{
-        let mut accumulator_data = Memory::create_accumulator(enc_in, enc_out);
+    ) -> Result<SparsePolynomial, OddError>{
         let N_poly: usize = server_key.bootstrapping_key.polynomial_size().0;
+        let p = enc_in.get_modulus() as usize;
 
-   
-        let mut accumulator =  Polynomial::new(0u64, PolynomialSize(N_poly));
+        // one coefficient in the middle of each window of the accumulator
+        let terms = Self::create_vi_differences(enc_in, enc_out, representatives)?.into_iter().enumerate()
+            .filter(|(_, diff)| *diff != 0)
+            .map(|(i, diff)| (N_poly / (2 * p) + i * N_poly / p, diff))
+            .collect();
 
-        let p = enc_in.get_modulus() as usize;
-        let mut new_p = enc_out.get_modulus();
+        Ok(SparsePolynomial { polynomial_size: PolynomialSize(N_poly), terms })
+    } 
 
 
-        //Here, we perform the division per 2. As for now we use only odd values for p_out, we multiply each accumulator values with the the inverse mod p of 2. Note that if p = 2, other black magic should be performed to get rid of the 2x factor...
-        if new_p % 2 == 1{
-            let inv2 = (new_p + 1) / 2;
-            accumulator_data = accumulator_data.iter().map(|x| x * inv2 % new_p).collect();
+    //LWE encryption of the constant coefficient of v0 * vi: the sample extraction needs the whole mask of the product, but only the constant coefficient of its body
+    fn extract_product_constant_term<Cont: Container<Element = u64>>(v0 : &GlweCiphertext<Cont>, vi : &SparsePolynomial) -> LweCiphertextOwned<u64>{
+        let mut accu_i = GlweCiphertext::new(0u64, v0.glwe_size(), v0.polynomial_size(), v0.ciphertext_modulus());
+        let (v0_mask, v0_body) = v0.get_mask_and_body();
+        let (mut mask, mut body) = accu_i.get_mut_mask_and_body();
+        mask.as_mut_polynomial_list().iter_mut().zip(v0_mask.as_polynomial_list().iter()).for_each(|(mut output, v0_poly_j)| vi.wrapping_mul_add_assign(output.as_mut(), v0_poly_j.as_ref()));
+        body.as_mut()[0] = vi.wrapping_mul_constant_term(v0_body.as_ref());
+
+        //Sample Extraction
+        let mut output_lwe_i = LweCiphertext::new(0u64, accu_i.glwe_size().to_glwe_dimension().to_equivalent_lwe_dimension(accu_i.polynomial_size()).to_lwe_size(), accu_i.ciphertext_modulus());
+        extract_lwe_sample_from_glwe_ciphertext(&accu_i, &mut output_lwe_i, MonomialDegree(0));
+        output_lwe_i
+    }
+
+
+    //accumulator of a simple bootstrapping, built at its first use and then taken from the cache
+    pub(crate) fn cached_accumulator(
+        &mut self,
+        enc_inter : &Encoding,
+        enc_out : &Encoding,
+        representatives : &[u64],
+        server_key: &ServerKey
+    ) -> Arc<GlweCiphertextOwned<u64>>{
+        let key = LookupTableKey::new(enc_inter, enc_out, representatives, server_key);
+        if let Some(accumulator) = self.accumulator_cache.get(&key){
+            return accumulator.clone();
         }
-        // Else, the division per 2 has been carried out in the v0 rotation.
-        else if new_p == 2{
-            new_p = 4;
+        if self.accumulator_cache.len() >= LOOKUP_TABLE_CACHE_CAPACITY{
+            self.accumulator_cache.clear();
         }
-        for i in 0usize..p-1{
-            let diff = (accumulator_data[i+1] as i32 - accumulator_data[i] as i32).rem_euclid(new_p as i32) as u64;
-            accumulator[N_poly / (2 * p) + i * N_poly / p] = diff;
+        let accumulator = Arc::new(Memory::create_lookup_table_accumulator(server_key, enc_inter, enc_out, representatives));
+        self.accumulator_cache.insert(key, accumulator.clone());
+        accumulator
+    }
+
+
+    //vi of a mvb, built at its first use and then taken from the cache
+    fn cached_vi(
+        &mut self,
+        enc_inter : &Encoding,
+        enc_out : &Encoding,
+        representatives : &[u64],
+        server_key: &ServerKey
+    ) -> Result<Arc<SparsePolynomial>, OddError>{
+        let key = LookupTableKey::new(enc_inter, enc_out, representatives, server_key);
+        if let Some(vi) = self.vi_cache.get(&key){
+            return Ok(vi.clone());
         }
-        let diff = (new_p as i32 - accumulator_data[0] as i32 - accumulator_data[p-1] as i32).rem_euclid(new_p as i32) as u64;
-        accumulator[N_poly / (2 * p) + (p-1) * N_poly / p] = diff;
+        if self.vi_cache.len() >= LOOKUP_TABLE_CACHE_CAPACITY{
+            self.vi_cache.clear();
+        }
+        let vi = Arc::new(Self::create_vi_for_mvb(enc_inter, enc_out, representatives, server_key)?);
+        self.vi_cache.insert(key, vi.clone());
+        Ok(vi)
+    }
 
-        accumulator
-    } 
+
+    //vi of a mvb from enc_in computing each lut_fis[i] into encs_out[i]
+    pub(crate) fn create_vis(
+        &mut self,
+        enc_in : &Encoding,
+        encs_out : &[Encoding],
+        representatives : &[Vec<u64>],
+        lut_fis : &[Vec<u64>],
+        server_key: &ServerKey
+    ) -> Result<Vec<Arc<SparsePolynomial>>, OddError>{
+        encs_out.iter().zip(representatives).zip(lut_fis)
+            .map(|((enc_out, reps), lut_fi)| {
+                let enc_inter = enc_in.try_apply_lut_to_encoding(&|x| lut_fi[x as usize])?;
+                self.cached_vi(&enc_inter, enc_out, reps, server_key)
+            })
+            .collect()
+    }
 
 
     // Debug
@@ -549,52 +858,23 @@ impl Bootstrapper {
     pub(crate) fn mvb_bootstrap(
         &mut self,
         ciphertext : LweCiphertextOwned<u64>,
-        enc_in : &Encoding,
-        encs_out : &Vec<Encoding>,
-        lut_fis : &Vec<Vec<u64>>,
+        encs_out : &[Encoding],
+        vis : &[Arc<SparsePolynomial>],
         server_key: &ServerKey
     ) -> Vec<LweCiphertext<Vec<u64>>>{
-        // let start_mvb = Instant::now();
-
-        // let start_bootstrapping_common_factor = Instant::now();
-        // Ici problème si les outputs n'ont pas tout le même modulo d'encodage
-
-        let v0 = self.bootstrap_common_factor(&ciphertext, &encs_out[0], server_key);
-
-        // Self::decrypt_glwe_with_builtin_function(&client_key_debug, &v0);
-        // println!("----------------------------------------");
-        // let stop_bootstrapping_common_factor = start_bootstrapping_common_factor.elapsed();
-        // println!("Durée BR common factor: {:?}: {:?}", stop_bootstrapping_common_factor.as_millis(), SystemTime::now().duration_since(UNIX_EPOCH).unwrap());
-
-        // construction of the vi's:
-        let vis : Vec<Polynomial<_>> = encs_out.iter().zip(lut_fis)
-                        .map(|(enc_out, lut_fi)| (enc_out, |x| lut_fi[x as usize]) )
-                        .map(|(enc_out, fi)| (enc_in.apply_lut_to_encoding(&fi), enc_out))
-                        .map(|(enc_inter, enc_out)| {self.create_vi_for_mvb(&enc_inter, enc_out, server_key)})
-                        .collect();
-
-        // vis[0].iter().for_each(|x| println!("{}|", x));
-
-
-        // let start_polynomial_multiplications = Instant::now();
-        //Multiplication between accumulators and the vi's
-        let mut result = vec![];
-        for vi in vis{
-            let mut accu_i = GlweCiphertext::new(0u64, v0.glwe_size(), v0.polynomial_size(), v0.ciphertext_modulus());
-            accu_i.as_mut_polynomial_list().iter_mut().zip(v0.as_polynomial_list().iter()).for_each(|(mut output, v0_poly_j)| polynomial_karatsuba_wrapping_mul(&mut output, &v0_poly_j, &vi));
-
-            //Sample Extraction
-            let mut output_lwe_i = LweCiphertext::new(0u64, accu_i.glwe_size().to_glwe_dimension().to_equivalent_lwe_dimension(accu_i.polynomial_size()).to_lwe_size(), accu_i.ciphertext_modulus());
-            extract_lwe_sample_from_glwe_ciphertext(&accu_i, &mut output_lwe_i, MonomialDegree(0));
-            result.push(output_lwe_i)
+        // The common factor depends on the output modulus: one blind rotation per distinct modulus, shared by all the outputs having this modulus
+        let mut outputs : Vec<Option<LweCiphertextOwned<u64>>> = vec![None; encs_out.len()];
+        for enc_out in encs_out.iter().unique_by(|enc_out| enc_out.get_modulus()){
+            let modulus = enc_out.get_modulus();
+            let v0 = self.bootstrap_common_factor(&ciphertext, enc_out, server_key);
+            let v0 = v0.as_view();
+
+            //Multiplication between accumulators and the vi's, independent for each output and computed in parallel
+            outputs.par_iter_mut().zip(vis).zip(encs_out)
+                .filter(|(_, enc_out)| enc_out.get_modulus() == modulus)
+                .for_each(|((output, vi), _)| *output = Some(Self::extract_product_constant_term(&v0, vi)));
         }
-        // let stop_polynomial_multiplications = start_polynomial_multiplications.elapsed();
-        // println!("Durée Polynomial multiplication: {:?}: {:?}", stop_polynomial_multiplications.as_millis(), SystemTime::now().duration_since(UNIX_EPOCH).unwrap());
-
-        // let stop_mvb = start_mvb.elapsed();
-        // println!("Durée MVB: {:?}: {:?}", stop_mvb.as_millis(), SystemTime::now().duration_since(UNIX_EPOCH).unwrap());
-        result
-
+        outputs.into_iter().map(Option::unwrap).collect()
     }
 
 
@@ -609,183 +889,111 @@ impl Bootstrapper {
 
 
 
+    // The common factor has been computed for the modulus of the outputs, which should all share it
     pub(crate) fn mvb_bootstrap_with_common_factor_given(
         &mut self,
         common_factor : &GlweCiphertext<Vec<u64>>,
-        enc_in : &Encoding,
-        encs_out : &Vec<Encoding>,
-        lut_fis : &Vec<Vec<u64>>,
-        server_key: &ServerKey,
-        client_key_debug : &ClientKey
+        vis : &[Arc<SparsePolynomial>],
     ) -> Vec<LweCiphertext<Vec<u64>>>{
-        // let start_mvb = Instant::now();
-
-        // let start_bootstrapping_common_factor = Instant::now();
-        // Ici problème si les outputs n'ont pas tout le même modulo d'encodage
-
-        let v0: &GlweCiphertext<Vec<u64>> = common_factor;
-
-        // Self::decrypt_glwe_with_builtin_function(&client_key_debug, &v0_bis);
-        // println!("----------------------------------------");
-        // let stop_bootstrapping_common_factor = start_bootstrapping_common_factor.elapsed();
-        // println!("Durée BR common factor: {:?}: {:?}", stop_bootstrapping_common_factor.as_millis(), SystemTime::now().duration_since(UNIX_EPOCH).unwrap());
-
-        // construction of the vi's:
-        let vis : Vec<Polynomial<_>> = encs_out.iter().zip(lut_fis)
-                        .map(|(enc_out, lut_fi)| (enc_out, |x| lut_fi[x as usize]) )
-                        .map(|(enc_out, fi)| (enc_in.apply_lut_to_encoding(&fi), enc_out))
-                        .map(|(enc_inter, enc_out)| {self.create_vi_for_mvb(&enc_inter, enc_out, server_key)})
-                        .collect();
-
-        // vis[0].iter().for_each(|x| println!("{}|", x));
-
-
-        // let start_polynomial_multiplications = Instant::now();
-        //Multiplication between accumulators and the vi's
-        let mut result = vec![];
-        for vi in vis{
-            let mut accu_i = GlweCiphertext::new(0u64, v0.glwe_size(), v0.polynomial_size(), v0.ciphertext_modulus());
-            accu_i.as_mut_polynomial_list().iter_mut().zip(v0.as_polynomial_list().iter()).for_each(|(mut output, v0_poly_j)| polynomial_karatsuba_wrapping_mul(&mut output, &v0_poly_j, &vi));
-            
-            // Self::decrypt_glwe_with_builtin_function(&client_key_debug, &accu_i);
-            // println!("------------------------------------------");
-
-            //Sample Extraction
-            let mut output_lwe_i = LweCiphertext::new(0u64, accu_i.glwe_size().to_glwe_dimension().to_equivalent_lwe_dimension(accu_i.polynomial_size()).to_lwe_size(), accu_i.ciphertext_modulus());
-            extract_lwe_sample_from_glwe_ciphertext(&accu_i, &mut output_lwe_i, MonomialDegree(0));
-            result.push(output_lwe_i)
-        }
-        // let stop_polynomial_multiplications = start_polynomial_multiplications.elapsed();
-        // println!("Durée Polynomial multiplication: {:?}: {:?}", stop_polynomial_multiplications.as_millis(), SystemTime::now().duration_since(UNIX_EPOCH).unwrap());
-
-        // let stop_mvb = start_mvb.elapsed();
-        // println!("Durée MVB: {:?}: {:?}", stop_mvb.as_millis(), SystemTime::now().duration_since(UNIX_EPOCH).unwrap());
-        result
-
+        //Multiplication between accumulators and the vi's, independent for each output and computed in parallel
+        vis.par_iter().map(|vi| Self::extract_product_constant_term(common_factor, vi)).collect()
     }
 
 
+    //pack the ciphertexts into an accumulator to be blind rotated by a ciphertext encoded with encoding_selector: lwe_ciphertexts[i] is the value selected by the element i of Zo
+    //for an even p, only the first half of Zp has its own window: an element x + p/2 selects the opposite of the value selected by x (negacyclicity)
     pub(crate) fn pack_into_new_accumulator(
         &self, 
-        lwe_ciphertexts : Vec<LweCiphertextOwned<u64>>,
+        lwe_ciphertexts : &[LweCiphertextOwned<u64>],
         server_key : &ServerKey,
-        p : u64,
+        encoding_selector : &Encoding,
     ) -> GlweCiphertext<Vec<u64>>{
         let mut output_glwe_ciphertext = GlweCiphertext::new(0u64, server_key.bootstrapping_key.glwe_size(), server_key.bootstrapping_key.polynomial_size(), CiphertextModulus::new_native());
 
-        //reordering accumulator
-        let accumulator_elements : Vec<_> = (0..p).map(|k|{
-            if k % 2 == 0{ 
-                match lwe_ciphertexts.get(k as usize/2){
-                    Some(c) => c.clone(),
-                    None => LweCiphertext::new(0u64, lwe_ciphertexts[0].lwe_size(), CiphertextModulus::new_native())
-            }}
-            else{
-                match lwe_ciphertexts.get(((p+1)/2 + (k-1)/2) as usize){
-                    Some(c) => {
-                        let mut c_clone = c.clone();
-                        lwe_ciphertext_opposite_assign(&mut c_clone);
-                        c_clone
-                    },
-                    None => LweCiphertext::new(0u64, lwe_ciphertexts[0].lwe_size(), CiphertextModulus::new_native())
+        let p = encoding_selector.get_modulus();
+        let selected = |zp_elem : u64| encoding_selector.inverse_encoding(zp_elem).map(|i| i as usize).filter(|&i| i < lwe_ciphertexts.len());
+
+        //content of the slots of the accumulator, as (index of the selected ciphertext, negation), None for an empty slot
+        let slots : Vec<Option<(usize, bool)>> = if p % 2 == 1{
+            (0..p).map(|k|{
+                if k % 2 == 0{
+                    selected(k / 2).map(|i| (i, false))
                 }
-            }
-        }).collect();
+                else{
+                    selected(p.div_ceil(2) + (k-1)/2).map(|i| (i, true))
+                }
+            }).collect()
+        }
+        else{
+            (0..p/2).map(|k| selected(k).map(|i| (i, false))).collect()
+        };
 
+        //the packing keyswitch is linear: each selected ciphertext is keyswitched once, in parallel, and the negations are applied to the resulting GLWEs
+        let is_selected : Vec<bool> = (0..lwe_ciphertexts.len()).map(|i| slots.iter().flatten().any(|&(j, _)| j == i)).collect();
+        let packed : Vec<Option<GlweCiphertextOwned<u64>>> = lwe_ciphertexts.par_iter().zip(&is_selected).map(|(lwe, &is_selected)|{
+            is_selected.then(|| {
+                let mut glwe = GlweCiphertext::new(
+                    0u64,
+                    output_glwe_ciphertext.glwe_size(),
+                    output_glwe_ciphertext.polynomial_size(),
+                    output_glwe_ciphertext.ciphertext_modulus(),
+                );
+                keyswitch_lwe_ciphertext_into_glwe_ciphertext(&server_key.lwe_packing_keyswitch_key, lwe, &mut glwe);
+                glwe
+            })
+        }).collect();
 
-        assert!(p % 2 == 1, "Pas sûr que ça marche avec une output paire");
         let N = server_key.bootstrapping_key.polynomial_size().0;
-        let size_slice = N / p as usize;
-
-
-
-        if p % 2 == 1{
-
-            let mut buffer = GlweCiphertext::new(
-                0u64,
-                output_glwe_ciphertext.glwe_size(),
-                output_glwe_ciphertext.polynomial_size(),
-                output_glwe_ciphertext.ciphertext_modulus(),
-            );
-
-
-
-            //index \in [0, p-2]
-            for (k, elmt) in accumulator_elements.iter().enumerate(){
-                keyswitch_lwe_ciphertext_into_glwe_ciphertext(&server_key.lwe_packing_keyswitch_key, &elmt, &mut buffer);
-                
-                let number_of_repetition = if k == 0 {size_slice/2} else {size_slice};
-                (0..number_of_repetition).for_each(|i|{
-                    let mut buffer_bis = buffer.clone();
-                    buffer_bis.
-                        as_mut_polynomial_list()
-                        .iter_mut()
-                        .for_each(|mut poly| {
-                            let offset = if k == 0 {0} else {size_slice / 2 + (k - 1) * size_slice};
-                            polynomial_wrapping_monic_monomial_mul_assign(&mut poly, MonomialDegree(offset  + i));
-                        });
-                    slice_wrapping_add_assign(output_glwe_ciphertext.as_mut(), buffer_bis.as_ref());
+        let size_slice = N / slots.len();
+
+        //windows of the accumulator, as (slot, first degree, number of coefficients, negation), the last half window being the negacyclic image of the first one
+        let windows = (0..slots.len())
+            .map(|k| if k == 0 {(0, 0, size_slice / 2, false)} else {(k, size_slice / 2 + (k - 1) * size_slice, size_slice, false)})
+            .chain([(0, N - size_slice / 2, size_slice / 2, true)]);
+
+        //each window receives its GLWE multiplied by X^offset, X^(offset + 1), ..., rotated in a single scratch GLWE
+        let mut rotated = output_glwe_ciphertext.clone();
+        for (k, offset, number_of_repetition, window_negation) in windows{
+            let Some((i, slot_negation)) = slots[k] else { continue };
+            rotated.as_mut().copy_from_slice(packed[i].as_ref().unwrap().as_ref());
+            let mut degree = offset;
+            for _ in 0..number_of_repetition{
+                rotated.as_mut_polynomial_list().iter_mut().for_each(|mut poly| {
+                    polynomial_wrapping_monic_monomial_mul_assign(&mut poly, MonomialDegree(degree));
                 });
+                if slot_negation == window_negation{
+                    slice_wrapping_add_assign(output_glwe_ciphertext.as_mut(), rotated.as_ref());
+                }
+                else{
+                    slice_wrapping_sub_assign(output_glwe_ciphertext.as_mut(), rotated.as_ref());
+                }
+                degree = 1;
             }
-
-            // index = p-1 (last one)
-            keyswitch_lwe_ciphertext_into_glwe_ciphertext(&server_key.lwe_packing_keyswitch_key, &accumulator_elements[0], &mut buffer);
-                glwe_ciphertext_opposite_assign(&mut buffer);
-                (0..size_slice/2).for_each(|i|{
-                    let mut buffer_bis = buffer.clone();
-                    buffer_bis.
-                        as_mut_polynomial_list()
-                        .iter_mut()
-                        .for_each(|mut poly| {
-                            polynomial_wrapping_monic_monomial_mul_assign(&mut poly, MonomialDegree(N - size_slice / 2 + i));
-                        });
-                    slice_wrapping_add_assign(output_glwe_ciphertext.as_mut(), buffer_bis.as_ref());
-                })
-    
-
         }
 
         output_glwe_ciphertext
-
-
     }
 
 
-    pub(crate) fn bootstrap_keyswitch(
+    //bootstrapping of ciphertext followed by a keyswitch back to its key, in place
+    pub(crate) fn bootstrap_keyswitch_assign(
         &mut self,
-        mut ciphertext: LweCiphertextOwned<u64>,
-        enc_inter : &Encoding,
-        enc_out : &Encoding,
+        ciphertext: &mut LweCiphertextOwned<u64>,
+        accumulator : &GlweCiphertextOwned<u64>,
         server_key: &ServerKey,
-    ) -> Ciphertext{
+    ){
+        let (fft, stack) = Self::blind_rotation_context(&mut self.blind_rotation_plan, &mut self.computation_buffers, server_key);
         let BuffersRef {
-            lookup_table: accumulator,
             mut buffer_lwe_after_pbs,
             ..
-        } = self.memory.as_buffers(server_key, enc_inter, enc_out);
-
-        let fourier_bsk = &server_key.bootstrapping_key;
-
-        let fft = Fft::new(fourier_bsk.polynomial_size());
-        let fft = fft.as_view();
-
-        self.computation_buffers.resize(
-            programmable_bootstrap_lwe_ciphertext_mem_optimized_requirement::<u64>(
-                fourier_bsk.glwe_size(),
-                fourier_bsk.polynomial_size(),
-                fft,
-            )
-            .unwrap()
-            .unaligned_bytes_required(),
-        );
-        let stack = self.computation_buffers.stack();
+        } = self.memory.as_buffers(server_key);
 
         // Compute a bootstrap
         programmable_bootstrap_lwe_ciphertext_mem_optimized(
-            &ciphertext,
+            &*ciphertext,
             &mut buffer_lwe_after_pbs,
-            &accumulator,
-            fourier_bsk,
+            accumulator,
+            &server_key.bootstrapping_key,
             fft,
             stack,
         );
@@ -794,49 +1002,31 @@ impl Bootstrapper {
         keyswitch_lwe_ciphertext(
             &server_key.key_switching_key,
             &buffer_lwe_after_pbs,
-            &mut ciphertext,
+            ciphertext,
         );
-
-        Ciphertext::EncodingEncrypted(ciphertext, enc_out.clone())
     }
 
 
 
 
-    pub(crate) fn keyswitch_bootstrap(
+    //keyswitch of ciphertext to the small key followed by a bootstrapping, in place
+    pub(crate) fn keyswitch_bootstrap_assign(
             &mut self,
-            mut ciphertext: LweCiphertextOwned<u64>,
-            enc_inter : &Encoding,
-            enc_out : &Encoding,
+            ciphertext: &mut LweCiphertextOwned<u64>,
+            lookup_table : &GlweCiphertextOwned<u64>,
             server_key: &ServerKey,
-    ) -> Ciphertext {
+    ){
+        let (fft, stack) = Self::blind_rotation_context(&mut self.blind_rotation_plan, &mut self.computation_buffers, server_key);
         let BuffersRef {
-            lookup_table,
             mut buffer_lwe_after_ks,
             ..
-        } = self.memory.as_buffers(server_key, enc_inter, enc_out);
-
-        let fourier_bsk = &server_key.bootstrapping_key;
-
-        let fft = Fft::new(fourier_bsk.polynomial_size());
-        let fft = fft.as_view();
-
-        self.computation_buffers.resize(
-            programmable_bootstrap_lwe_ciphertext_mem_optimized_requirement::<u64>(
-                fourier_bsk.glwe_size(),
-                fourier_bsk.polynomial_size(),
-                fft,
-            )
-            .unwrap()
-            .unaligned_bytes_required(),
-        );
-        let stack = self.computation_buffers.stack();
+        } = self.memory.as_buffers(server_key);
 
         // let start_keyswitch = Instant::now();
         // Keyswitch from large LWE key to the small one
         keyswitch_lwe_ciphertext(
             &server_key.key_switching_key,
-            &ciphertext,
+            &*ciphertext,
             &mut buffer_lwe_after_ks,
         );
         // let stop_keyswitch = start_keyswitch.elapsed();
@@ -847,16 +1037,14 @@ impl Bootstrapper {
         // Compute a bootstrap
         programmable_bootstrap_lwe_ciphertext_mem_optimized(
             &buffer_lwe_after_ks,
-            &mut ciphertext,
-            &lookup_table,
-            fourier_bsk,
+            ciphertext,
+            lookup_table,
+            &server_key.bootstrapping_key,
             fft,
             stack
         );
         // let stop_bootstrap = start_bootstrap.elapsed();
         // println!("Durée Bootstrap: {:?}: {:?}", stop_bootstrap.as_millis(), SystemTime::now().duration_since(UNIX_EPOCH).unwrap());
-
-        Ciphertext::EncodingEncrypted(ciphertext, enc_out.clone())
     }
 
     
@@ -865,11 +1053,36 @@ impl Bootstrapper {
         ct: LweCiphertextOwned<u64>,
         enc_inter : &Encoding,
         enc_out : &Encoding,
+        representatives : &[u64],
         server_key: &ServerKey,
     ) -> Ciphertext {
+        let accumulator = self.cached_accumulator(enc_inter, enc_out, representatives, server_key);
+        self.apply_bootstrapping_pattern_with_accumulator(ct, &accumulator, enc_out, server_key)
+    }
+
+
+    pub(crate) fn apply_bootstrapping_pattern_with_accumulator(
+        &mut self,
+        mut ct: LweCiphertextOwned<u64>,
+        accumulator : &GlweCiphertextOwned<u64>,
+        enc_out : &Encoding,
+        server_key: &ServerKey,
+    ) -> Ciphertext {
+        self.apply_bootstrapping_pattern_with_accumulator_assign(&mut ct, accumulator, server_key);
+        Ciphertext::EncodingEncrypted(ct, enc_out.clone(), server_key.parameters.bootstrap_output_variance(1.0))
+    }
+
+
+    //bootstrapping of ct by accumulator in the order of the server key, the result replacing ct
+    pub(crate) fn apply_bootstrapping_pattern_with_accumulator_assign(
+        &mut self,
+        ct: &mut LweCiphertextOwned<u64>,
+        accumulator : &GlweCiphertextOwned<u64>,
+        server_key: &ServerKey,
+    ){
         match server_key.pbs_order {
-            PBSOrder::KeyswitchBootstrap => self.keyswitch_bootstrap(ct, enc_inter, enc_out, server_key),
-            PBSOrder::BootstrapKeyswitch => self.bootstrap_keyswitch(ct, enc_inter, enc_out, server_key),
+            PBSOrder::KeyswitchBootstrap => self.keyswitch_bootstrap_assign(ct, accumulator, server_key),
+            PBSOrder::BootstrapKeyswitch => self.bootstrap_keyswitch_assign(ct, accumulator, server_key),
         }
     }
 }
@@ -889,3 +1102,31 @@ impl ServerKey {
         output
     }
 }
+
+
+
+#[test]
+fn test_sparse_vi_product() {
+    use crate::core_crypto::algorithms::polynomial_algorithms::polynomial_karatsuba_wrapping_mul;
+    use crate::core_crypto::prelude::{GlweSize, LweSize};
+    use rand::Rng;
+
+    let mut rng = rand::thread_rng();
+    let N = 256;
+    let vi = SparsePolynomial {
+        polynomial_size: PolynomialSize(N),
+        terms: vec![(0, 3), (7, u64::MAX), (100, rng.gen()), (N - 1, rng.gen())],
+    };
+    let mut dense = Polynomial::new(0u64, PolynomialSize(N));
+    vi.terms.iter().for_each(|&(degree, value)| dense[degree] = value);
+
+    let mut v0 = GlweCiphertext::new(0u64, GlweSize(3), PolynomialSize(N), CiphertextModulus::new_native());
+    v0.as_mut().iter_mut().for_each(|x| *x = rng.gen());
+
+    // same sample as the dense product followed by the sample extraction
+    let mut product = GlweCiphertext::new(0u64, v0.glwe_size(), v0.polynomial_size(), v0.ciphertext_modulus());
+    product.as_mut_polynomial_list().iter_mut().zip(v0.as_polynomial_list().iter()).for_each(|(mut output, v0_poly_j)| polynomial_karatsuba_wrapping_mul(&mut output, &v0_poly_j, &dense));
+    let mut expected = LweCiphertext::new(0u64, LweSize(2 * N + 1), CiphertextModulus::new_native());
+    extract_lwe_sample_from_glwe_ciphertext(&product, &mut expected, MonomialDegree(0));
+    assert_eq!(Bootstrapper::extract_product_constant_term(&v0, &vi), expected);
+}
@@ -11,15 +11,16 @@ use crate::core_crypto::prelude::CiphertextModulus;
 use crate::core_crypto::prelude::Container;
 use crate::core_crypto::prelude::ContiguousEntityContainer;
 use crate::core_crypto::prelude::EncryptionKeyChoice;
+use crate::core_crypto::prelude::LwePublicKeyZeroEncryptionCount;
 use crate::core_crypto::prelude::LweSize;
 use crate::core_crypto::prelude::MonomialDegree;
 use crate::core_crypto::prelude::PBSOrder;
 use crate::core_crypto::prelude::PlaintextCount;
+use crate::core_crypto::prelude::PolynomialSize;
+use crate::odd::error::OddError;
 use crate::odd::prelude::*;
 use std::cell::RefCell;
 use std::time::Instant;
-use std::time::SystemTime;
-use std::time::UNIX_EPOCH;
 pub mod bootstrapping;
 use crate::core_crypto::commons::generators::{
     DeterministicSeeder, EncryptionRandomGenerator, SecretRandomGenerator,
@@ -31,6 +32,117 @@ use crate::core_crypto::seeders::new_seeder;
 
 use super::ciphertext::Encoding;
 
+const LOG2_Q_64: usize = 64;
+
+/// Map an element of Zp onto the torus, the same way messages are encoded before encryption
+pub(crate) fn zp_to_torus(value: u64, modulus: u64) -> u64 {
+    ((1u128 << 64) / modulus as u128 * value as u128) as u64
+}
+
+/// Round an element of the torus to the closest element of Zp
+pub(crate) fn torus_to_zp(value: u64, modulus: u64) -> u64 {
+    let rounded = (value as u128 * modulus as u128 + (1u128 << 63)) >> 64;
+    (rounded % modulus as u128) as u64
+}
+
+/// Squared norm of the vi of a mvb computing `lut_fi`, by which the noise of the common factor is
+/// multiplied
+fn vi_squared_norm(
+    input_encoding: &Encoding,
+    output_encoding: &Encoding,
+    representatives: &[u64],
+    lut_fi: &[u64],
+) -> Result<f64, OddError> {
+    let enc_inter = input_encoding.try_apply_lut_to_encoding(&|x| lut_fi[x as usize])?;
+    Ok(
+        Bootstrapper::create_vi_differences(&enc_inter, output_encoding, representatives)?
+            .iter()
+            .map(|&d| (d as f64).powi(2))
+            .sum(),
+    )
+}
+
+/// Build the trivial ciphertext of an element of Zo under a canonical encoding
+fn trivial_from_message(message: u64, encoding: &Encoding) -> Result<Ciphertext, OddError> {
+    let value = encoding.try_get_part_single_value_if_canonical(message)?;
+    Ok(Ciphertext::Trivial(value, encoding.clone()))
+}
+
+/// Build the trivial ciphertext of an element of Zo, written as its chosen representative
+fn trivial_from_representative(
+    message: u64,
+    encoding: &Encoding,
+    representatives: &[u64],
+) -> Result<Ciphertext, OddError> {
+    representatives
+        .get(message as usize)
+        .map(|value| Ciphertext::Trivial(*value, encoding.clone()))
+        .ok_or_else(|| OddError::MessageOutOfRange {
+            message,
+            origin_modulus: encoding.get_origin_modulus(),
+        })
+}
+
+/// Check that the table `lut_fi` of a bootstrapping from `input_encoding` can be written in
+/// `output_encoding` with the given representatives
+pub(crate) fn check_lut(
+    input_encoding: &Encoding,
+    output_encoding: &Encoding,
+    representatives: &[u64],
+    lut_fi: &[u64],
+) -> Result<(), OddError> {
+    output_encoding.try_check_representatives(representatives)?;
+    if lut_fi.len() as u64 != input_encoding.get_origin_modulus() {
+        return Err(OddError::LengthMismatch {
+            expected: input_encoding.get_origin_modulus() as usize,
+            got: lut_fi.len(),
+        });
+    }
+    match lut_fi
+        .iter()
+        .find(|&&x| x >= output_encoding.get_origin_modulus())
+    {
+        Some(&message) => Err(OddError::MessageOutOfRange {
+            message,
+            origin_modulus: output_encoding.get_origin_modulus(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Check the tables `lut_fis` of a mvb from `input_encoding`, the i-th one being written in
+/// `output_encodings[i]` with `representatives[i]`
+fn check_mvb(
+    input_encoding: &Encoding,
+    output_encodings: &[Encoding],
+    representatives: &[Vec<u64>],
+    lut_fis: &[Vec<u64>],
+) -> Result<(), OddError> {
+    for len in [output_encodings.len(), representatives.len()] {
+        if len != lut_fis.len() {
+            return Err(OddError::LengthMismatch {
+                expected: lut_fis.len(),
+                got: len,
+            });
+        }
+    }
+    if !input_encoding.is_valid() {
+        return Err(OddError::InvalidEncoding);
+    }
+    for ((encoding_out, reps), lut_fi) in output_encodings.iter().zip(representatives).zip(lut_fis) {
+        check_lut(input_encoding, encoding_out, reps, lut_fi)?;
+    }
+    Ok(())
+}
+
+/// Check that a lookup table built for a polynomial of `polynomial_size` coefficients can be used
+/// with the server key
+fn check_lookup_table_size(polynomial_size: PolynomialSize, server_key: &ServerKey) -> Result<(), OddError> {
+    if polynomial_size != server_key.bootstrapping_key.polynomial_size() {
+        return Err(OddError::IncompatibleLookupTable);
+    }
+    Ok(())
+}
 
 /// Trait to be able to acces thread_local
 /// engines in a generic way
@@ -42,8 +154,12 @@ pub(crate) trait WithThreadLocalEngine {
 
 // All our thread local engines
 // that our exposed types will use internally to implement their methods
+//
+// An operator takes an engine out of its thread for its whole duration and puts it back when it
+// is done: a nested operator on the same thread (e.g. a job stolen by rayon while the thread waits
+// for a parallel section) takes another engine, instead of borrowing the same one twice
 thread_local! {
-    static ODD_ENGINE: RefCell<OddEngine> = RefCell::new(OddEngine::new());
+    static ODD_ENGINES: RefCell<Vec<OddEngine>> = const { RefCell::new(Vec::new()) };
 }
 
 pub struct OddEngine {
@@ -64,7 +180,12 @@ impl WithThreadLocalEngine for OddEngine {
     where
         F: FnOnce(&mut Self) -> R,
     {
-        ODD_ENGINE.with(|engine_cell| func(&mut engine_cell.borrow_mut()))
+        let mut engine = ODD_ENGINES
+            .with(|engines| engines.borrow_mut().pop())
+            .unwrap_or_default();
+        let result = func(&mut engine);
+        ODD_ENGINES.with(|engines| engines.borrow_mut().push(engine));
+        result
     }
 }
 
@@ -96,8 +217,58 @@ impl OddEngine {
         server_key
     }
 
-    pub fn trivial_encrypt(&mut self, message: u64) -> Ciphertext {
-        Ciphertext::Trivial(message)
+    pub fn create_compressed_server_key(&mut self, cks: &ClientKey) -> CompressedServerKey {
+        self.bootstrapper.new_compressed_server_key(cks)
+    }
+
+    pub fn trivial_encrypt(
+        &mut self,
+        message: u64,
+        encoding: &Encoding,
+    ) -> Result<Ciphertext, OddError> {
+        trivial_from_message(message, encoding)
+    }
+
+    pub fn create_public_key(&mut self, client_key: &ClientKey) -> PublicKey {
+        let (lwe_sk, encryption_noise) = client_key.encryption_key_and_noise();
+
+        // Formula is (n + 1) * log2(q) + 128
+        let zero_encryption_count = LwePublicKeyZeroEncryptionCount(
+            lwe_sk.lwe_dimension().to_lwe_size().0 * LOG2_Q_64 + 128,
+        );
+
+        let lwe_public_key: LwePublicKeyOwned<u64> = par_allocate_and_generate_new_lwe_public_key(
+            &lwe_sk,
+            zero_encryption_count,
+            encryption_noise,
+            CiphertextModulus::new_native(),
+            &mut self.encryption_generator,
+        );
+
+        PublicKey {
+            lwe_public_key,
+            parameters: client_key.parameters,
+        }
+    }
+
+    pub fn create_compact_public_key(&mut self, client_key: &ClientKey) -> Result<CompactPublicKey, OddError> {
+        let (lwe_sk, encryption_noise) = client_key.encryption_key_and_noise();
+
+        if !lwe_sk.lwe_dimension().0.is_power_of_two() {
+            return Err(OddError::UnsupportedKeyDimension { dimension: lwe_sk.lwe_dimension().0 });
+        }
+
+        let lwe_compact_public_key = allocate_and_generate_new_lwe_compact_public_key(
+            &lwe_sk,
+            encryption_noise,
+            CiphertextModulus::new_native(),
+            &mut self.encryption_generator,
+        );
+
+        Ok(CompactPublicKey {
+            lwe_compact_public_key,
+            parameters: client_key.parameters,
+        })
     }
 
     fn encryption_from_plaintext(
@@ -105,16 +276,7 @@ impl OddEngine {
         cks: &ClientKey,
         plaintext: Plaintext<u64>,
     ) -> LweCiphertext<Vec<u64>> {
-        let (lwe_sk, encryption_noise) = match cks.parameters.encryption_key_choice {
-            EncryptionKeyChoice::Big => (
-                cks.glwe_secret_key.as_lwe_secret_key(),
-                cks.parameters.glwe_noise_distribution,
-            ),
-            EncryptionKeyChoice::Small => {
-                let view = LweSecretKey::from_container(cks.lwe_secret_key.as_ref());
-                (view, cks.parameters.lwe_noise_distribution)
-            }
-        };
+        let (lwe_sk, encryption_noise) = cks.encryption_key_and_noise();
 
         allocate_and_encrypt_new_lwe_ciphertext(
             &lwe_sk,
@@ -129,10 +291,9 @@ impl OddEngine {
         &mut self,
         message: u64,
         encoding: &Encoding,
-    ) -> Plaintext<u64> {
-        let zpelem = encoding.get_part_single_value_if_canonical(message);
-        let buffer: u128 = (1 << 64) / encoding.get_modulus() as u128 * zpelem as u128;
-        Plaintext(buffer as u64)
+    ) -> Result<Plaintext<u64>, OddError> {
+        let zpelem = encoding.try_get_part_single_value_if_canonical(message)?;
+        Ok(Plaintext(zp_to_torus(zpelem, encoding.get_modulus())))
     }
 
     pub fn encrypt_arithmetic(
@@ -140,25 +301,118 @@ impl OddEngine {
         message: u64,
         encoding: &Encoding,
         cks: &ClientKey,
-    ) -> Ciphertext {
-        assert!(message < encoding.get_origin_modulus());
-
+    ) -> Result<Ciphertext, OddError> {
         //  Encode the arithmetic message over Zp
-        let plaintext = self.encode_message_into_plaintext(message, encoding);
+        let plaintext = self.encode_message_into_plaintext(message, encoding)?;
 
         let ct = self.encryption_from_plaintext(cks, plaintext);
-        Ciphertext::EncodingEncrypted(ct, encoding.clone())
+        Ok(Ciphertext::EncodingEncrypted(
+            ct,
+            encoding.clone(),
+            cks.parameters.encryption_variance(),
+        ))
+    }
+
+    pub fn encrypt_arithmetic_compressed(
+        &mut self,
+        message: u64,
+        encoding: &Encoding,
+        cks: &ClientKey,
+    ) -> Result<CompressedCiphertext, OddError> {
+        let plaintext = self.encode_message_into_plaintext(message, encoding)?;
+
+        let (lwe_sk, encryption_noise) = cks.encryption_key_and_noise();
+
+        let ct = allocate_and_encrypt_new_seeded_lwe_ciphertext(
+            &lwe_sk,
+            plaintext,
+            encryption_noise,
+            CiphertextModulus::new_native(),
+            &mut self.bootstrapper.seeder,
+        );
+
+        Ok(CompressedCiphertext {
+            ciphertext: ct,
+            encoding: encoding.clone(),
+            noise_variance: cks.parameters.encryption_variance(),
+        })
+    }
+
+    pub fn encrypt_arithmetic_with_public_key(
+        &mut self,
+        message: u64,
+        encoding: &Encoding,
+        pks: &PublicKey,
+    ) -> Result<Ciphertext, OddError> {
+        let plaintext = self.encode_message_into_plaintext(message, encoding)?;
+
+        let mut output = LweCiphertext::new(
+            0u64,
+            pks.lwe_public_key.lwe_size(),
+            CiphertextModulus::new_native(),
+        );
+
+        encrypt_lwe_ciphertext_with_public_key(
+            &pks.lwe_public_key,
+            &mut output,
+            plaintext,
+            &mut self.secret_generator,
+        );
+
+        let variance = pks
+            .parameters
+            .public_key_encryption_variance(pks.lwe_public_key.zero_encryption_count().0);
+        Ok(Ciphertext::EncodingEncrypted(output, encoding.clone(), variance))
     }
 
-    pub fn decrypt(&mut self, ct: &Ciphertext, cks: &ClientKey) -> u64 {
+    pub fn encrypt_arithmetic_with_compact_public_key(
+        &mut self,
+        message: u64,
+        encoding: &Encoding,
+        compact_pks: &CompactPublicKey,
+    ) -> Result<Ciphertext, OddError> {
+        let plaintext = self.encode_message_into_plaintext(message, encoding)?;
+
+        let encryption_noise = match compact_pks.parameters.encryption_key_choice {
+            EncryptionKeyChoice::Big => compact_pks.parameters.glwe_noise_distribution,
+            EncryptionKeyChoice::Small => compact_pks.parameters.lwe_noise_distribution,
+        };
+
+        let mut output = LweCiphertext::new(
+            0u64,
+            compact_pks
+                .lwe_compact_public_key
+                .lwe_dimension()
+                .to_lwe_size(),
+            CiphertextModulus::new_native(),
+        );
+
+        encrypt_lwe_ciphertext_with_compact_public_key(
+            &compact_pks.lwe_compact_public_key,
+            &mut output,
+            plaintext,
+            encryption_noise,
+            encryption_noise,
+            &mut self.secret_generator,
+            &mut self.encryption_generator,
+        );
+
+        Ok(Ciphertext::EncodingEncrypted(
+            output,
+            encoding.clone(),
+            compact_pks.parameters.compact_public_key_encryption_variance(),
+        ))
+    }
+
+    pub fn decrypt(&mut self, ct: &Ciphertext, cks: &ClientKey) -> Result<u64, OddError> {
         let lwe_sk = match cks.parameters.encryption_key_choice {
             EncryptionKeyChoice::Big => cks.glwe_secret_key.as_lwe_secret_key(),
             EncryptionKeyChoice::Small => LweSecretKey::from_container(cks.lwe_secret_key.as_ref()),
         };
 
         match ct {
-            Ciphertext::Trivial(b) => *b,
-            Ciphertext::EncodingEncrypted(ciphertext, encoding) => {
+            Ciphertext::Trivial(..) => ct.decrypt_trivial(),
+            Ciphertext::EncodingEncrypted(ciphertext, encoding, _) => {
                 Self::decrypt_arithmetic(&lwe_sk, ciphertext, encoding)
             }
         }
@@ -168,7 +422,7 @@ impl OddEngine {
         lwe_sk: &LweSecretKey<&[u64]>,
         ciphertext: &LweCiphertext<Vec<u64>>,
         encoding: &Encoding,
-    ) -> u64 {
+    ) -> Result<u64, OddError> {
         // decryption
         let decrypted = decrypt_lwe_ciphertext(&lwe_sk, ciphertext);
 
@@ -185,18 +439,18 @@ impl OddEngine {
 
         let closest_integer = floating_result.round() as u64 % encoding.get_modulus();
 
-        for i in 0..encoding.get_origin_modulus() {
-            if encoding.is_partition_containing(i, closest_integer) {
-                return i;
-            }
-        }
-        panic!("No value in Zo has been found for : {}.", floating_result);
+        encoding
+            .inverse_encoding(closest_integer)
+            .ok_or_else(|| OddError::UndecodableValue {
+                value: closest_integer,
+                modulus: encoding.get_modulus(),
+            })
     }
 
-    pub fn measure_noise(&mut self, ct: &Ciphertext, cks: &ClientKey) -> i64 {
+    pub fn measure_noise(&mut self, ct: &Ciphertext, cks: &ClientKey) -> Result<i64, OddError> {
         match ct {
-            Ciphertext::Trivial(_) => panic!("No error level with trivial ciphertext"),
-            Ciphertext::EncodingEncrypted(ciphertext, encoding) => {
+            Ciphertext::Trivial(..) => Ok(0),
+            Ciphertext::EncodingEncrypted(ciphertext, encoding, _) => {
                 let lwe_sk = match cks.parameters.encryption_key_choice {
                     EncryptionKeyChoice::Big => cks.glwe_secret_key.as_lwe_secret_key(),
                     EncryptionKeyChoice::Small => {
@@ -228,7 +482,7 @@ impl OddEngine {
 
                 //remettre le bruit dans Zq
                 let noise_int = (noise * (1u128 << 64) as f64).round() as i64;
-                noise_int
+                Ok(noise_int)
             }
         }
     }
@@ -262,14 +516,38 @@ impl OddEngine {
 ///
 
 impl OddEngine {
+    /// Sum the inputs (all encoded with the plaintext modulus of `enc_inter`) and bootstrap the
+    /// sum, the element of Zp in the part of i in `enc_inter` being mapped to i in `enc_out`.
     pub fn exec_gadget_with_extraction(
         &mut self,
-        enc_in: &Vec<Encoding>,
+        enc_in: &[Encoding],
         enc_inter: &Encoding,
         enc_out: &Encoding,
-        input: &Vec<Ciphertext>,
+        input: &[Ciphertext],
         server_key: &ServerKey,
-    ) -> Ciphertext {
+    ) -> Result<Ciphertext, OddError> {
+        let representatives = enc_out.try_default_representatives()?;
+        let modulus = enc_inter.get_modulus();
+        if input.is_empty() {
+            return Err(OddError::EmptyInput);
+        }
+        if input.iter().any(|x| x.get_encoding().get_modulus() != modulus) {
+            return Err(OddError::InvalidEncoding);
+        }
+
+        // the sum of trivial inputs is decoded in the clear
+        if input.iter().all(Ciphertext::is_trivial) {
+            let sum = input.iter().fold(0u64, |acc, x| match x {
+                Ciphertext::Trivial(value, _) => acc.wrapping_add(zp_to_torus(*value, modulus)),
+                Ciphertext::EncodingEncrypted(..) => acc,
+            });
+            let value = torus_to_zp(sum, modulus);
+            let message = enc_inter
+                .inverse_encoding(value)
+                .ok_or(OddError::UndecodableValue { value, modulus })?;
+            return trivial_from_representative(message, enc_out, &representatives);
+        }
+
         let size = match server_key.pbs_order {
             PBSOrder::KeyswitchBootstrap => server_key
                 .key_switching_key
@@ -287,89 +565,296 @@ impl OddEngine {
         let bootstrapper = &mut self.bootstrapper;
 
         // compute the sum
-        input.iter().for_each(|x| match x {
-            Ciphertext::EncodingEncrypted(x_ct, _) => {
-                lwe_ciphertext_add_assign(&mut buffer_lwe_before_pbs, &x_ct);
+        for x in input {
+            match x {
+                Ciphertext::EncodingEncrypted(x_ct, _, _) => {
+                    lwe_ciphertext_add_assign(&mut buffer_lwe_before_pbs, x_ct);
+                }
+                Ciphertext::Trivial(value, encoding) => {
+                    lwe_ciphertext_plaintext_add_assign(
+                        &mut buffer_lwe_before_pbs,
+                        Plaintext(zp_to_torus(*value, encoding.get_modulus())),
+                    );
+                }
             }
-            Ciphertext::Trivial(_) => panic!("Not yet implemented with trivial ciphertexts"),
-        });
+        }
 
         // compute the bootstrap and the key switch
-        bootstrapper.apply_bootstrapping_pattern(
+        Ok(bootstrapper.apply_bootstrapping_pattern(
             buffer_lwe_before_pbs,
             enc_inter,
             enc_out,
+            &representatives,
             server_key,
-        )
+        ))
     }
 
     pub fn apply_lut(
         &mut self,
         input: &Ciphertext,
         output_encoding: &Encoding,
+        representatives: &[u64],
         f: &dyn Fn(u64) -> u64,
         server_key: &ServerKey,
-    ) -> Ciphertext {
-        match input {
-            Ciphertext::EncodingEncrypted(c, enc_in) => {
-                let bootstrapper = &mut self.bootstrapper;
-                let enc_inter = enc_in.apply_lut_to_encoding(f);
-                bootstrapper.apply_bootstrapping_pattern(
-                    c.clone(),
+    ) -> Result<Ciphertext, OddError> {
+        let mut output = input.clone();
+        self.apply_lut_assign(&mut output, output_encoding, representatives, f, server_key)?;
+        Ok(output)
+    }
+
+    /// Same as [`Self::apply_lut`], the result replacing `ciphertext` (which is left untouched on
+    /// error).
+    pub fn apply_lut_assign(
+        &mut self,
+        ciphertext: &mut Ciphertext,
+        output_encoding: &Encoding,
+        representatives: &[u64],
+        f: &dyn Fn(u64) -> u64,
+        server_key: &ServerKey,
+    ) -> Result<(), OddError> {
+        let input_encoding = ciphertext.get_encoding();
+        let lut: Vec<u64> = (0..input_encoding.get_origin_modulus()).map(f).collect();
+        check_lut(input_encoding, output_encoding, representatives, &lut)?;
+        match ciphertext {
+            Ciphertext::EncodingEncrypted(c, encoding, variance) => {
+                let enc_inter = encoding.try_apply_lut_to_encoding(f)?;
+                let accumulator = self.bootstrapper.cached_accumulator(
                     &enc_inter,
                     output_encoding,
+                    representatives,
+                    server_key,
+                );
+                self.bootstrapper.apply_bootstrapping_pattern_with_accumulator_assign(c, &accumulator, server_key);
+                *encoding = output_encoding.clone();
+                *variance = server_key.parameters.bootstrap_output_variance(1.0);
+            }
+            Ciphertext::Trivial(..) => {
+                *ciphertext = trivial_from_representative(
+                    f(ciphertext.decrypt_trivial()?),
+                    output_encoding,
+                    representatives,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Build the accumulator of [`Self::apply_lut`] once, to apply it to many ciphertexts under
+    /// `input_encoding` with [`Self::apply_lookup_table`].
+    pub fn create_lookup_table(
+        &mut self,
+        input_encoding: &Encoding,
+        output_encoding: &Encoding,
+        representatives: &[u64],
+        f: &dyn Fn(u64) -> u64,
+        server_key: &ServerKey,
+    ) -> Result<OddLookupTable, OddError> {
+        if !input_encoding.is_valid() {
+            return Err(OddError::InvalidEncoding);
+        }
+        let lut: Vec<u64> = (0..input_encoding.get_origin_modulus()).map(f).collect();
+        check_lut(input_encoding, output_encoding, representatives, &lut)?;
+        let enc_inter = input_encoding.try_apply_lut_to_encoding(f)?;
+        let accumulator = self.bootstrapper.cached_accumulator(
+            &enc_inter,
+            output_encoding,
+            representatives,
+            server_key,
+        );
+        Ok(OddLookupTable {
+            input_encoding: input_encoding.clone(),
+            output_encoding: output_encoding.clone(),
+            representatives: representatives.to_vec(),
+            lut,
+            accumulator,
+        })
+    }
+
+    pub fn apply_lookup_table(
+        &mut self,
+        input: &Ciphertext,
+        table: &OddLookupTable,
+        server_key: &ServerKey,
+    ) -> Result<Ciphertext, OddError> {
+        let mut output = input.clone();
+        self.apply_lookup_table_assign(&mut output, table, server_key)?;
+        Ok(output)
+    }
+
+    pub fn apply_lookup_table_assign(
+        &mut self,
+        ciphertext: &mut Ciphertext,
+        table: &OddLookupTable,
+        server_key: &ServerKey,
+    ) -> Result<(), OddError> {
+        if *ciphertext.get_encoding() != table.input_encoding {
+            return Err(OddError::InvalidEncoding);
+        }
+        check_lookup_table_size(table.accumulator.polynomial_size(), server_key)?;
+        match ciphertext {
+            Ciphertext::EncodingEncrypted(c, encoding, variance) => {
+                self.bootstrapper.apply_bootstrapping_pattern_with_accumulator_assign(
+                    c,
+                    &table.accumulator,
                     server_key,
-                )
+                );
+                *encoding = table.output_encoding.clone();
+                *variance = server_key.parameters.bootstrap_output_variance(1.0);
+            }
+            Ciphertext::Trivial(..) => {
+                *ciphertext = trivial_from_representative(
+                    table.lut[ciphertext.decrypt_trivial()? as usize],
+                    &table.output_encoding,
+                    &table.representatives,
+                )?;
             }
-            _ => panic!(),
         }
+        Ok(())
     }
 
     pub fn mvb(
         &mut self,
         input: &Ciphertext,
-        output_encodings: &Vec<Encoding>,
-        lut_fis: &Vec<Vec<u64>>,
+        output_encodings: &[Encoding],
+        representatives: &[Vec<u64>],
+        lut_fis: &[Vec<u64>],
         server_key: &ServerKey,
-    ) -> Vec<Ciphertext> {
+    ) -> Result<Vec<Ciphertext>, OddError> {
+        check_mvb(input.get_encoding(), output_encodings, representatives, lut_fis)?;
         match input {
-            Ciphertext::EncodingEncrypted(c, input_encoding) => {
+            Ciphertext::EncodingEncrypted(_, input_encoding, _) => {
+                let table = self.create_mvb_lookup_table(
+                    input_encoding,
+                    output_encodings,
+                    representatives,
+                    lut_fis,
+                    server_key,
+                )?;
+                self.apply_mvb_lookup_table(input, &table, server_key)
+            }
+            Ciphertext::Trivial(..) => {
+                let message = input.decrypt_trivial()? as usize;
+                lut_fis
+                    .iter()
+                    .zip(output_encodings)
+                    .zip(representatives)
+                    .map(|((lut_fi, enc_i), reps)| {
+                        trivial_from_representative(lut_fi[message], enc_i, reps)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Build the vi polynomials of [`Self::mvb`] once, to apply them to many ciphertexts under
+    /// `input_encoding` with [`Self::apply_mvb_lookup_table`].
+    pub fn create_mvb_lookup_table(
+        &mut self,
+        input_encoding: &Encoding,
+        output_encodings: &[Encoding],
+        representatives: &[Vec<u64>],
+        lut_fis: &[Vec<u64>],
+        server_key: &ServerKey,
+    ) -> Result<MvbLookupTable, OddError> {
+        check_mvb(input_encoding, output_encodings, representatives, lut_fis)?;
+        if input_encoding.get_modulus() % 2 == 0 {
+            return Err(OddError::UnsupportedModulus {
+                operation: "mvb",
+                modulus: input_encoding.get_modulus(),
+            });
+        }
+        let variances = output_encodings
+            .iter()
+            .zip(representatives)
+            .zip(lut_fis)
+            .map(|((encoding_out, reps), lut_fi)| {
+                let squared_norm = vi_squared_norm(input_encoding, encoding_out, reps, lut_fi)?;
+                Ok(server_key.parameters.bootstrap_output_variance(squared_norm))
+            })
+            .collect::<Result<Vec<_>, OddError>>()?;
+        let vis = self.bootstrapper.create_vis(
+            input_encoding,
+            output_encodings,
+            representatives,
+            lut_fis,
+            server_key,
+        )?;
+        Ok(MvbLookupTable {
+            input_encoding: input_encoding.clone(),
+            output_encodings: output_encodings.to_vec(),
+            representatives: representatives.to_vec(),
+            lut_fis: lut_fis.to_vec(),
+            vis,
+            variances,
+        })
+    }
+
+    pub fn apply_mvb_lookup_table(
+        &mut self,
+        input: &Ciphertext,
+        table: &MvbLookupTable,
+        server_key: &ServerKey,
+    ) -> Result<Vec<Ciphertext>, OddError> {
+        if *input.get_encoding() != table.input_encoding {
+            return Err(OddError::InvalidEncoding);
+        }
+        for vi in &table.vis {
+            check_lookup_table_size(vi.polynomial_size(), server_key)?;
+        }
+        let output_encodings = &table.output_encodings;
+        match input {
+            Ciphertext::EncodingEncrypted(c, _, _) => {
                 let bootstrapper = &mut self.bootstrapper;
                 match server_key.pbs_order {
                     PBSOrder::BootstrapKeyswitch => {
                         let cis = bootstrapper.mvb_bootstrap(
                             c.clone(),
-                            input_encoding,
                             output_encodings,
-                            lut_fis,
+                            &table.vis,
                             server_key,
                         );
                         //keyswitching
-                        cis.iter()
+                        Ok(cis
+                            .iter()
                             .map(|ci| server_key.keyswitch(ci))
                             .zip(output_encodings)
-                            .map(|(ci, enc_i)| Ciphertext::EncodingEncrypted(ci, enc_i.clone()))
-                            .collect()
+                            .zip(&table.variances)
+                            .map(|((ci, enc_i), variance)| {
+                                Ciphertext::EncodingEncrypted(ci, enc_i.clone(), *variance)
+                            })
+                            .collect())
                     }
                     PBSOrder::KeyswitchBootstrap => {
                         let c_after_ks = server_key.keyswitch(c);
                         let cis: Vec<LweCiphertext<Vec<u64>>> = bootstrapper.mvb_bootstrap(
                             c_after_ks,
-                            input_encoding,
                             output_encodings,
-                            lut_fis,
+                            &table.vis,
                             server_key,
                         );
-                        cis.iter()
+                        Ok(cis
+                            .into_iter()
                             .zip(output_encodings)
-                            .map(|(ci, enc_i)| {
-                                Ciphertext::EncodingEncrypted(ci.clone(), enc_i.clone())
+                            .zip(&table.variances)
+                            .map(|((ci, enc_i), variance)| {
+                                Ciphertext::EncodingEncrypted(ci, enc_i.clone(), *variance)
                             })
-                            .collect()
+                            .collect())
                     }
                 }
             }
-            _ => panic!(),
+            Ciphertext::Trivial(..) => {
+                let message = input.decrypt_trivial()? as usize;
+                table
+                    .lut_fis
+                    .iter()
+                    .zip(output_encodings)
+                    .zip(&table.representatives)
+                    .map(|((lut_fi, enc_i), reps)| {
+                        trivial_from_representative(lut_fi[message], enc_i, reps)
+                    })
+                    .collect()
+            }
         }
     }
 
@@ -401,114 +886,153 @@ impl OddEngine {
         ciphertext: &Ciphertext,
         enc_out: &Encoding,
         server_key: &ServerKey,
-    ) -> GlweCiphertextOwned<u64> {
+    ) -> Result<GlweCiphertextOwned<u64>, OddError> {
         let bootstrapper = &mut self.bootstrapper;
 
         match ciphertext {
-            Ciphertext::EncodingEncrypted(lwe_ciphertext, _) => {
-                let c_after_ks = server_key.keyswitch(lwe_ciphertext);
-
-                bootstrapper.bootstrap_common_factor(&c_after_ks, enc_out, &server_key)
+            Ciphertext::EncodingEncrypted(lwe_ciphertext, _, _) => {
+                let mut common_factor = GlweCiphertext::new(
+                    0u64,
+                    server_key.bootstrapping_key.glwe_size(),
+                    server_key.bootstrapping_key.polynomial_size(),
+                    CiphertextModulus::new_native(),
+                );
+                match server_key.pbs_order {
+                    PBSOrder::KeyswitchBootstrap => {
+                        let c_after_ks = server_key.keyswitch(lwe_ciphertext);
+                        bootstrapper.bootstrap_common_factor_into(&c_after_ks, &mut common_factor, enc_out, server_key)
+                    }
+                    PBSOrder::BootstrapKeyswitch => {
+                        bootstrapper.bootstrap_common_factor_into(lwe_ciphertext, &mut common_factor, enc_out, server_key)
+                    }
+                }
+                Ok(common_factor)
             }
-            Ciphertext::Trivial(_) => panic!(),
+            Ciphertext::Trivial(..) => Err(OddError::TrivialCiphertext {
+                operation: "tree bootstrapping",
+            }),
         }
     }
 
+    /// Tree bootstrapping of a single output digit, for any number of encrypted inputs.
+    ///
+    /// The table `lut_fi` is indexed by `sum_i x_i * prod_{j > i} o_j`, i.e. the last input is
+    /// the least significant digit. It is consumed one level at a time: a mvb on the last input
+    /// (whose blind rotation is the given `common_factor`) produces the `t / o_{d-1}` partial
+    /// tables, then for each of the other inputs (from the least to the most significant) every
+    /// group of `o_i` consecutive ciphertexts is packed into an accumulator which is blind
+    /// rotated by this input. For an even modulus, the selectors should leave the second half of Zp
+    /// empty (see [`Encoding::has_padding`]), and the last input should have an odd modulus.
     pub fn simple_tree_bootstrapping(
         &mut self,
         common_factor: &GlweCiphertextOwned<u64>,
-        inputs: &Vec<Ciphertext>,
+        inputs: &[Ciphertext],
         encoding_out: &Encoding,
         t: u64,
         lut_fi: Vec<u64>,
         server_key: &ServerKey,
-        client_key_debug: &ClientKey,
-        log: bool,
-    ) -> Ciphertext {
-        let c_0 = inputs[1].clone();
-        match c_0 {
-            Ciphertext::EncodingEncrypted(_, encoding_in_0) => {
-                let bootstrapper = &mut self.bootstrapper;
+    ) -> Result<Ciphertext, OddError> {
+        let (c_0, selectors) = inputs.split_last().ok_or(OddError::EmptyInput)?;
+        if inputs.iter().any(Ciphertext::is_trivial) {
+            return Err(OddError::TrivialCiphertext {
+                operation: "tree bootstrapping",
+            });
+        }
+        if !selectors.iter().all(|c| c.get_encoding().has_padding()) {
+            return Err(OddError::MissingPadding);
+        }
+        if c_0.get_encoding().get_modulus() % 2 == 0 {
+            return Err(OddError::UnsupportedModulus {
+                operation: "mvb",
+                modulus: c_0.get_encoding().get_modulus(),
+            });
+        }
+        let product = inputs
+            .iter()
+            .map(|c| c.get_encoding().get_origin_modulus())
+            .product::<u64>();
+        if product != t {
+            return Err(OddError::InvalidTreeModulus {
+                expected: t,
+                got: product,
+            });
+        }
+        if lut_fi.len() as u64 != t {
+            return Err(OddError::LengthMismatch {
+                expected: t as usize,
+                got: lut_fi.len(),
+            });
+        }
 
-                let o_0 = encoding_in_0.get_origin_modulus();
+        let representatives = encoding_out.try_default_representatives()?;
 
-                let first_functions: Vec<Vec<u64>> = (0..t / o_0)
-                    .map(|j: u64| (0..o_0).map(|x| lut_fi[(x + j * o_0) as usize]).collect())
-                    .collect(); // x \in [0, o_0[
+        let bootstrapper = &mut self.bootstrapper;
 
-                match server_key.pbs_order {
-                    PBSOrder::BootstrapKeyswitch => {
-                        panic!()
-                    }
-                    PBSOrder::KeyswitchBootstrap => {
-                        // No keyswitch here, because this part jas been taken part while bootstrapping the common factor
-
-                        let first_ciphertexts = bootstrapper
-                            .mvb_bootstrap_with_common_factor_given(
-                                &common_factor,
-                                &encoding_in_0,
-                                &vec![encoding_out.clone(); (t / o_0).try_into().unwrap()],
-                                &first_functions,
-                                &server_key,
-                                &client_key_debug,
-                            );
-                        if log {
-                            println!(
-                                "TIMING POST_MVB_TREE ? {:?}",
-                                SystemTime::now().duration_since(UNIX_EPOCH).unwrap()
-                            );
-                        }
-                        
-                        let next_accumulator = bootstrapper.pack_into_new_accumulator(
-                            first_ciphertexts,
-                            server_key,
-                            encoding_in_0.get_modulus(),
-                        );
-                        if log {
-                            println!(
-                                "TIMING POST_PACKING_TREE ? {:?}",
-                                SystemTime::now().duration_since(UNIX_EPOCH).unwrap()
-                            );
-                        }
-
-
-                        //for now, only depth-2 trees
-
-                        let c_1 = inputs[0].clone();
-                        match c_1 {
-                            Ciphertext::EncodingEncrypted(lwe_c_1, _) => {
-                                //we assume that they both hve the same input encoding
-                                let lwe_c_1_after_ks = server_key.keyswitch(&lwe_c_1);
-                                if log {
-                                    println!(
-                                        "TIMING POST_SECOND_KEYSWITCH_TREE ? {:?}",
-                                        SystemTime::now().duration_since(UNIX_EPOCH).unwrap()
-                                    );
-                                }
-
-                                let final_lwe = bootstrapper.bootstrap(
-                                    &lwe_c_1_after_ks,
-                                    &next_accumulator,
-                                    server_key,
-                                );
-                                if log {
-                                    println!(
-                                        "TIMING POST_SIMPLE_BOOTSTRAPPING_IN_TREE ? {:?}",
-                                        SystemTime::now().duration_since(UNIX_EPOCH).unwrap()
-                                    );
-                                }
-
-                                Ciphertext::EncodingEncrypted(final_lwe, encoding_out.clone())
-                            }
-                            _ => panic!(),
-                        }
-                    }
-                }
-            }
-            Ciphertext::Trivial(_) => {
-                panic!()
+        let encoding_in_0 = c_0.get_encoding();
+        let o_0 = encoding_in_0.get_origin_modulus();
+
+        let first_functions: Vec<Vec<u64>> = (0..t / o_0)
+            .map(|j: u64| (0..o_0).map(|x| lut_fi[(x + j * o_0) as usize]).collect())
+            .collect(); // x \in [0, o_0[
+
+        // the noise of the first level depends on the vi, and every other level adds a packing
+        // keyswitch and a blind rotation
+        let parameters = &server_key.parameters;
+        let mut squared_norm = 0f64;
+        for first_function in &first_functions {
+            squared_norm =
+                squared_norm.max(vi_squared_norm(encoding_in_0, encoding_out, &representatives, first_function)?);
+        }
+        let mut variance = NoiseVariance(parameters.blind_rotation_variance().0 * squared_norm);
+        for _ in selectors {
+            variance = variance
+                + parameters.packing_keyswitch_variance()
+                + parameters.blind_rotation_variance();
+        }
+
+        // No keyswitch here, because this part jas been taken part while bootstrapping the common factor
+        // The blind rotations output ciphertexts under the big key, whatever the PBS order
+        let vis = bootstrapper.create_vis(
+            encoding_in_0,
+            &vec![encoding_out.clone(); first_functions.len()],
+            &vec![representatives; first_functions.len()],
+            &first_functions,
+            server_key,
+        )?;
+        let mut ciphertexts =
+            bootstrapper.mvb_bootstrap_with_common_factor_given(common_factor, &vis);
+
+        for selector in selectors.iter().rev() {
+            let Ciphertext::EncodingEncrypted(lwe_selector, encoding_selector, _) = selector else {
+                unreachable!()
+            };
+            let o = encoding_selector.get_origin_modulus() as usize;
+
+            let lwe_selector_after_ks = match server_key.pbs_order {
+                PBSOrder::KeyswitchBootstrap => server_key.keyswitch(lwe_selector),
+                PBSOrder::BootstrapKeyswitch => lwe_selector.clone(),
+            };
+
+            // the result of each group replaces its first ciphertext, which is not needed once packed
+            for group in ciphertexts.chunks_mut(o) {
+                let next_accumulator = bootstrapper.pack_into_new_accumulator(
+                    group,
+                    server_key,
+                    encoding_selector,
+                );
+
+                bootstrapper.bootstrap(&lwe_selector_after_ks, &mut group[0], &next_accumulator, server_key);
             }
+            ciphertexts = ciphertexts.into_iter().step_by(o).collect();
+        }
+
+        let mut result = ciphertexts.pop().unwrap();
+        if server_key.pbs_order == PBSOrder::BootstrapKeyswitch {
+            // back to the small key of the inputs
+            result = server_key.keyswitch(&result);
+            variance = variance + parameters.keyswitch_variance();
         }
+        Ok(Ciphertext::EncodingEncrypted(result, encoding_out.clone(), variance))
     }
 
     pub fn encoding_switching_mul_constant(
@@ -516,7 +1040,7 @@ impl OddEngine {
         input: &Ciphertext,
         coefficient: u64,
         server_key: &ServerKey,
-    ) -> Ciphertext {
+    ) -> Result<Ciphertext, OddError> {
         let size = match server_key.pbs_order {
             PBSOrder::KeyswitchBootstrap => server_key
                 .key_switching_key
@@ -531,19 +1055,31 @@ impl OddEngine {
         // compute the product with the coefficient
         let c = Cleartext(coefficient);
         match input {
-            Ciphertext::EncodingEncrypted(x_ct, encoding) => {
+            Ciphertext::EncodingEncrypted(x_ct, encoding, variance) => {
                 lwe_ciphertext_cleartext_mul(&mut result, &x_ct, c);
-                let new_encoding = encoding.multiply_encoding_by_constant(coefficient);
-                Ciphertext::EncodingEncrypted(result, new_encoding)
+                let new_encoding = encoding.try_multiply_encoding_by_constant(coefficient)?;
+                Ok(Ciphertext::EncodingEncrypted(
+                    result,
+                    new_encoding,
+                    variance.scale(coefficient),
+                ))
             }
-            Ciphertext::Trivial(_) => {
-                panic!("Error : casting a trivial ciphertext ! ");
+            Ciphertext::Trivial(value, encoding) => {
+                let new_encoding = encoding.try_multiply_encoding_by_constant(coefficient)?;
+                Ok(Ciphertext::Trivial(
+                    value * (coefficient % encoding.get_modulus()) % encoding.get_modulus(),
+                    new_encoding,
+                ))
             }
         }
     }
 
     // Warning : To use only  with similar encodings!
-    pub fn simple_sum(&mut self, input: &Vec<Ciphertext>, server_key: &ServerKey) -> Ciphertext {
+    pub fn simple_sum(
+        &mut self,
+        input: &[Ciphertext],
+        server_key: &ServerKey,
+    ) -> Result<Ciphertext, OddError> {
         let size = match server_key.pbs_order {
             PBSOrder::KeyswitchBootstrap => server_key
                 .key_switching_key
@@ -555,20 +1091,41 @@ impl OddEngine {
                 .to_lwe_size(),
         };
 
+        let same_encoding = match input.first() {
+            Some(x) => x.get_encoding(),
+            None => return Err(OddError::EmptyInput),
+        };
+
+        // trivial inputs are summed on the torus, and only added to the result if any input is
+        // encrypted
         let mut result = LweCiphertext::new(0u64, size, CiphertextModulus::new_native());
-        input.iter().for_each(|x| match x {
-            Ciphertext::EncodingEncrypted(x_ct, _) => {
-                lwe_ciphertext_add_assign(&mut result, x_ct);
-            }
-            Ciphertext::Trivial(_) => {
-                panic!("simple_sum not yet implemented with plaintexts")
+        let mut trivial_sum = 0u64;
+        let mut all_trivial = true;
+        let mut variance = NoiseVariance::ZERO;
+        for x in input {
+            match x {
+                Ciphertext::EncodingEncrypted(x_ct, _, x_variance) => {
+                    lwe_ciphertext_add_assign(&mut result, x_ct);
+                    variance = variance + *x_variance;
+                    all_trivial = false;
+                }
+                Ciphertext::Trivial(value, encoding) => {
+                    trivial_sum =
+                        trivial_sum.wrapping_add(zp_to_torus(*value, encoding.get_modulus()));
+                }
             }
-        });
-        let same_encoding = match &input[0] {
-            Ciphertext::EncodingEncrypted(_, enc) => enc,
-            _ => panic!(),
-        };
-        Ciphertext::EncodingEncrypted(result, same_encoding.to_owned())
+        }
+
+        if all_trivial {
+            let value = torus_to_zp(trivial_sum, same_encoding.get_modulus());
+            return Ok(Ciphertext::Trivial(value, same_encoding.to_owned()));
+        }
+        lwe_ciphertext_plaintext_add_assign(&mut result, Plaintext(trivial_sum));
+        Ok(Ciphertext::EncodingEncrypted(
+            result,
+            same_encoding.to_owned(),
+            variance,
+        ))
     }
 
     pub fn simple_plaintext_sum(
@@ -577,7 +1134,7 @@ impl OddEngine {
         constant: u64,
         modulus: u64,
         server_key: &ServerKey,
-    ) -> Ciphertext {
+    ) -> Result<Ciphertext, OddError> {
         let size = match server_key.pbs_order {
             PBSOrder::KeyswitchBootstrap => server_key
                 .key_switching_key
@@ -593,13 +1150,17 @@ impl OddEngine {
         let buffer_value: u128 = (1 << 64) / modulus as u128 * constant as u128;
         let value = Plaintext(buffer_value as u64);
         match input {
-            Ciphertext::EncodingEncrypted(x_ct, encoding) => {
+            Ciphertext::EncodingEncrypted(x_ct, encoding, variance) => {
                 lwe_ciphertext_plaintext_add_assign(&mut result, value);
                 lwe_ciphertext_add_assign(&mut result, x_ct);
-                Ciphertext::EncodingEncrypted(result, encoding.clone())
+                Ok(Ciphertext::EncodingEncrypted(result, encoding.clone(), *variance))
             }
-            Ciphertext::Trivial(_) => {
-                panic!("don't use trivial encryption in this context")
+            Ciphertext::Trivial(x_value, encoding) => {
+                let sum = zp_to_torus(*x_value, encoding.get_modulus()).wrapping_add(value.0);
+                Ok(Ciphertext::Trivial(
+                    torus_to_zp(sum, encoding.get_modulus()),
+                    encoding.clone(),
+                ))
             }
         }
     }
@@ -610,7 +1171,7 @@ impl OddEngine {
         constant: u64,
         modulus: u64,
         server_key: &ServerKey,
-    ) -> Ciphertext {
+    ) -> Result<Ciphertext, OddError> {
         let size = match server_key.pbs_order {
             PBSOrder::KeyswitchBootstrap => server_key
                 .key_switching_key
@@ -625,13 +1186,34 @@ impl OddEngine {
         let mut result = LweCiphertext::new(0u64, size, CiphertextModulus::new_native());
         let coeff = Cleartext(constant % modulus);
         match input {
-            Ciphertext::EncodingEncrypted(x_ct, encoding) => {
+            Ciphertext::EncodingEncrypted(x_ct, encoding, variance) => {
                 lwe_ciphertext_cleartext_mul(&mut result, x_ct, coeff);
-                Ciphertext::EncodingEncrypted(result, encoding.clone())
+                Ok(Ciphertext::EncodingEncrypted(
+                    result,
+                    encoding.clone(),
+                    variance.scale(coeff.0),
+                ))
             }
-            Ciphertext::Trivial(_) => {
-                panic!("don't use trivial encryption in this context")
+            Ciphertext::Trivial(value, encoding) => Ok(Ciphertext::Trivial(
+                value * (coeff.0 % encoding.get_modulus()) % encoding.get_modulus(),
+                encoding.clone(),
+            )),
+        }
+    }
+
+    /// Opposite of the input in Zp, computed by negating the LWE ciphertext: unlike a
+    /// multiplication by p - 1, it keeps the noise variance unchanged
+    pub fn simple_negation(&mut self, input: &Ciphertext) -> Result<Ciphertext, OddError> {
+        match input {
+            Ciphertext::EncodingEncrypted(x_ct, encoding, variance) => {
+                let mut result = x_ct.clone();
+                lwe_ciphertext_opposite_assign(&mut result);
+                Ok(Ciphertext::EncodingEncrypted(result, encoding.clone(), *variance))
             }
+            Ciphertext::Trivial(value, encoding) => Ok(Ciphertext::Trivial(
+                (encoding.get_modulus() - value) % encoding.get_modulus(),
+                encoding.clone(),
+            )),
         }
     }
 
@@ -641,7 +1223,7 @@ impl OddEngine {
         constant: u64,
         modulus: u64,
         server_key: &ServerKey,
-    ) -> Ciphertext {
+    ) -> Result<Ciphertext, OddError> {
         let size = match server_key.pbs_order {
             PBSOrder::KeyswitchBootstrap => server_key
                 .key_switching_key
@@ -657,13 +1239,21 @@ impl OddEngine {
         let buffer_value: u128 = (1 << 64) / modulus as u128 * constant as u128;
         let value = Plaintext(buffer_value as u64);
         match input {
-            Ciphertext::EncodingEncrypted(x_ct, encoding) => {
+            Ciphertext::EncodingEncrypted(x_ct, encoding, variance) => {
                 lwe_ciphertext_plaintext_add_assign(&mut result, value);
                 lwe_ciphertext_add_assign(&mut result, x_ct);
-                Ciphertext::EncodingEncrypted(result, encoding.add_constant(constant))
+                Ok(Ciphertext::EncodingEncrypted(
+                    result,
+                    encoding.try_add_constant(constant)?,
+                    *variance,
+                ))
             }
-            Ciphertext::Trivial(_) => {
-                panic!("don't use trivial encryption in this context")
+            Ciphertext::Trivial(x_value, encoding) => {
+                let sum = zp_to_torus(*x_value, encoding.get_modulus()).wrapping_add(value.0);
+                Ok(Ciphertext::Trivial(
+                    torus_to_zp(sum, encoding.get_modulus()),
+                    encoding.try_add_constant(constant)?,
+                ))
             }
         }
     }
@@ -0,0 +1,124 @@
+//! Errors returned by the fallible (`try_`) versions of the odd APIs.
+
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OddError {
+    /// The operation is not available on trivial ciphertexts
+    TrivialCiphertext { operation: &'static str },
+    /// The ciphertext is not trivial and can only be decrypted with the client key
+    NotTrivialCiphertext,
+    /// The parts of the encoding are not disjoint subsets of Zp (or break negacyclicity for an
+    /// even p)
+    InvalidEncoding,
+    /// The operation is not available for this plaintext modulus
+    UnsupportedModulus { operation: &'static str, modulus: u64 },
+    /// The operation requires an encoding with exactly one element of Zp per element of Zo
+    NonCanonicalEncoding,
+    /// The element of Zp chosen to represent the message does not belong to its part (or the
+    /// part is empty)
+    InvalidRepresentative { message: u64 },
+    /// The message is not an element of Zo
+    MessageOutOfRange { message: u64, origin_modulus: u64 },
+    /// The decrypted element of Zp does not belong to any part of the encoding
+    UndecodableValue { value: u64, modulus: u64 },
+    /// Two lists that should have the same length (or a list with a fixed length) do not match
+    LengthMismatch { expected: usize, got: usize },
+    /// An encoding of even p used as a selector should leave the second half of Zp empty
+    MissingPadding,
+    /// The product of the input origin moduli of a tree bootstrapping is not the table size
+    InvalidTreeModulus { expected: u64, got: u64 },
+    /// The operation requires at least one input
+    EmptyInput,
+    /// The estimated noise of the result may prevent it from being bootstrapped correctly
+    NoiseTooLarge { modulus: u64, log2_failure_probability: i64 },
+    /// No gadget of this many Boolean inputs computes the function with the plaintext modulus
+    NoGadget { arity: usize, modulus: u64 },
+    /// A gate of a circuit uses a wire which is not defined before it
+    InvalidWire { wire: usize },
+    /// A line of a circuit description cannot be parsed (line 0 for the whole description)
+    CircuitParse { line: usize, message: String },
+    /// The lookup table was built for a server key with another polynomial size
+    IncompatibleLookupTable,
+    /// A compact public key requires an encryption key of power of two dimension
+    UnsupportedKeyDimension { dimension: usize },
+    /// The thread pool of the batch operators cannot be built
+    ThreadPool { message: String },
+}
+
+impl Display for OddError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TrivialCiphertext { operation } => {
+                write!(f, "{operation} is not supported on trivial ciphertexts")
+            }
+            Self::NotTrivialCiphertext => write!(f, "The ciphertext is not trivial"),
+            Self::InvalidEncoding => write!(f, "This Arithmetic Encoding is not correct"),
+            Self::UnsupportedModulus { operation, modulus } => {
+                write!(f, "{operation} is not supported for the plaintext modulus {modulus}")
+            }
+            Self::NonCanonicalEncoding => write!(f, "The encoding should be canonical"),
+            Self::InvalidRepresentative { message } => write!(
+                f,
+                "No valid representative of {message} in the output encoding"
+            ),
+            Self::MessageOutOfRange {
+                message,
+                origin_modulus,
+            } => write!(
+                f,
+                "The message (={message}) should be smaller than the origin modulus {origin_modulus}"
+            ),
+            Self::UndecodableValue { value, modulus } => write!(
+                f,
+                "No value in Zo has been found for {value} (modulus {modulus})"
+            ),
+            Self::LengthMismatch { expected, got } => {
+                write!(f, "Expected {expected} elements, got {got}")
+            }
+            Self::MissingPadding => write!(
+                f,
+                "The encoding uses the second half of an even Zp, which is reserved for negacyclicity"
+            ),
+            Self::InvalidTreeModulus { expected, got } => write!(
+                f,
+                "The product of the input origin moduli (={got}) should be equal to {expected}"
+            ),
+            Self::EmptyInput => write!(f, "At least one input is required"),
+            Self::NoiseTooLarge {
+                modulus,
+                log2_failure_probability,
+            } => write!(
+                f,
+                "The noise is too large for the plaintext modulus {modulus}: the next bootstrapping \
+                 fails with probability 2^{log2_failure_probability}"
+            ),
+            Self::NoGadget { arity, modulus } => write!(
+                f,
+                "No gadget of {arity} inputs computes the function for the plaintext modulus {modulus}"
+            ),
+            Self::InvalidWire { wire } => write!(f, "The wire {wire} is used before being defined"),
+            Self::CircuitParse { line, message } => {
+                write!(f, "Invalid circuit description at line {line}: {message}")
+            }
+            Self::IncompatibleLookupTable => {
+                write!(f, "The lookup table was built for another polynomial size")
+            }
+            Self::UnsupportedKeyDimension { dimension } => write!(
+                f,
+                "A compact public key requires an encryption key of power of two dimension, got {dimension}"
+            ),
+            Self::ThreadPool { message } => {
+                write!(f, "Cannot build the thread pool of the batch operators: {message}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for OddError {}
+
+impl From<OddError> for crate::Error {
+    fn from(value: OddError) -> Self {
+        Self::new(value.to_string())
+    }
+}
@@ -0,0 +1,107 @@
+//! Lookup tables built once and applied to many ciphertexts.
+//!
+//! A bootstrapping computing a LUT blind rotates an accumulator derived from the encodings and the
+//! function, and a mvb multiplies the common factor by one vi polynomial per output. Each engine
+//! caches the accumulators and the vi it has already built, and the tables of this module hold
+//! them explicitly: once built with [`ServerKey::create_lookup_table`] or
+//! [`ServerKey::create_mvb_lookup_table`], applying them only performs the blind rotation.
+
+use std::sync::Arc;
+
+use crate::core_crypto::entities::GlweCiphertextOwned;
+use crate::odd::engine::bootstrapping::SparsePolynomial;
+use crate::odd::prelude::*;
+
+/// Accumulator of a LUT from `input_encoding` to `output_encoding`, applied with
+/// [`ServerKey::apply_lookup_table`]
+#[derive(Clone, Debug)]
+pub struct OddLookupTable {
+    pub(crate) input_encoding: Encoding,
+    pub(crate) output_encoding: Encoding,
+    pub(crate) representatives: Vec<u64>,
+    /// Image of each element of Zo, used on trivial ciphertexts
+    pub(crate) lut: Vec<u64>,
+    pub(crate) accumulator: Arc<GlweCiphertextOwned<u64>>,
+}
+
+impl OddLookupTable {
+    pub fn input_encoding(&self) -> &Encoding {
+        &self.input_encoding
+    }
+
+    pub fn output_encoding(&self) -> &Encoding {
+        &self.output_encoding
+    }
+
+    pub fn lut(&self) -> &[u64] {
+        &self.lut
+    }
+}
+
+/// vi polynomials of a mvb from `input_encoding` to `output_encodings`, applied with
+/// [`ServerKey::apply_mvb_lookup_table`]
+#[derive(Clone, Debug)]
+pub struct MvbLookupTable {
+    pub(crate) input_encoding: Encoding,
+    pub(crate) output_encodings: Vec<Encoding>,
+    pub(crate) representatives: Vec<Vec<u64>>,
+    pub(crate) lut_fis: Vec<Vec<u64>>,
+    pub(crate) vis: Vec<Arc<SparsePolynomial>>,
+    /// Estimated noise of each output
+    pub(crate) variances: Vec<NoiseVariance>,
+}
+
+impl MvbLookupTable {
+    pub fn input_encoding(&self) -> &Encoding {
+        &self.input_encoding
+    }
+
+    pub fn output_encodings(&self) -> &[Encoding] {
+        &self.output_encodings
+    }
+
+    pub fn lut_fis(&self) -> &[Vec<u64>] {
+        &self.lut_fis
+    }
+}
+
+
+#[test]
+fn test_lookup_tables() {
+    use crate::odd::TEST_PARAMETERS;
+
+    let (cks, sks) = gen_keys(&TEST_PARAMETERS);
+    let nibble = Encoding::new_canonical(16, (0..16).collect(), 17);
+    let f = |x: u64| (x * 5 + 3) % 16;
+    let table = sks.create_lookup_table(&nibble, &nibble, &f);
+    assert_eq!(table.lut()[1], 8);
+    for x in [0, 7, 15] {
+        let ct = cks.encrypt_arithmetic(x, &nibble);
+        let res = sks.apply_lookup_table(&ct, &table);
+        assert_eq!(cks.decrypt(&res), f(x));
+        assert_eq!(cks.decrypt(&sks.apply_lut(&ct, &nibble, &f)), f(x));
+    }
+    assert_eq!(sks.apply_lookup_table(&sks.trivial_encrypt(2, &nibble), &table).decrypt_trivial(), Ok(13));
+
+    // the outputs of a mvb table can have different moduli
+    let encodings_out = vec![nibble.clone(), Encoding::parity_encoding()];
+    let fis: Vec<Box<dyn Fn(u64) -> u64>> = vec![Box::new(f), Box::new(|x| x % 2)];
+    let table = sks.create_mvb_lookup_table(&nibble, &encodings_out, &fis);
+    for x in [4, 9] {
+        let res = sks.apply_mvb_lookup_table(&cks.encrypt_arithmetic(x, &nibble), &table);
+        assert_eq!(res.iter().map(|c| cks.decrypt(c)).collect::<Vec<_>>(), vec![f(x), x % 2]);
+        assert!(res.iter().zip(&encodings_out).all(|(c, e)| c.get_encoding() == e));
+    }
+
+    // the inputs must match the encoding the tables were built for
+    let other = Encoding::new_canonical(16, (1..17).collect(), 17);
+    let ct = cks.encrypt_arithmetic(3, &other);
+    let table = sks.create_lookup_table(&nibble, &nibble, &f);
+    assert_eq!(sks.try_apply_lookup_table(&ct, &table).unwrap_err(), OddError::InvalidEncoding);
+    let bit = cks.encrypt_arithmetic(1, &Encoding::parity_encoding());
+    assert_eq!(
+        sks.try_create_mvb_lookup_table(bit.get_encoding(), &[nibble], &[Box::new(|x| x) as Box<dyn Fn(u64) -> u64>])
+            .unwrap_err(),
+        OddError::UnsupportedModulus { operation: "mvb", modulus: 2 }
+    );
+}
@@ -12,16 +12,28 @@ use crate::odd::client_key::ClientKey;
 use crate::odd::server_key::ServerKey;
 #[cfg(test)]
 use rand::Rng;
+#[cfg(test)]
+use crate::core_crypto::prelude::DynamicDistribution;
 
-use self::prelude::CustomOddParameters;
+use self::prelude::*;
 
 
+pub mod backend;
+pub mod backward_compatibility;
+pub mod bristol;
 pub mod ciphertext;
+pub mod circuit;
 pub mod client_key;
+pub mod encoding_search;
 pub mod engine;
+pub mod error;
+pub mod lookup_table;
+pub mod noise;
 pub mod parameters;
 pub mod prelude;
+pub mod public_key;
 pub mod server_key;
+pub mod simulation;
 
 
 /// tool to generate random integers
@@ -34,6 +46,21 @@ pub(crate) fn random_integer() -> u64 {
     rng.gen::<u64>()
 }
 
+/// small parameter set used in the tests (same as the 40-bits security set of Hippogriph)
+#[cfg(test)]
+pub(crate) const TEST_PARAMETERS: CustomOddParameters = CustomOddParameters {
+    lwe_dimension: LweDimension(754),
+    glwe_dimension: GlweDimension(1),
+    polynomial_size: PolynomialSize(1024),
+    lwe_noise_distribution: DynamicDistribution::new_gaussian_from_std_dev(StandardDev(5.0e-8)),
+    glwe_noise_distribution: DynamicDistribution::new_gaussian_from_std_dev(StandardDev(5.871712650082723e-15)),
+    pbs_base_log: DecompositionBaseLog(23),
+    pbs_level: DecompositionLevelCount(2),
+    ks_base_log: DecompositionBaseLog(4),
+    ks_level: DecompositionLevelCount(3),
+    encryption_key_choice: EncryptionKeyChoice::Big,
+};
+
 /// Generate a couple of client and server keys with provided parameters:
 /// 
 /// The client is the one generating both keys.
@@ -0,0 +1,335 @@
+//! Estimation of the noise carried by the odd ciphertexts.
+//!
+//! Every encrypted [`Ciphertext`] carries an estimate of the variance of its noise, on the torus
+//! normalized to [0, 1). It is set by the encryption, grows with the linear operators and is reset
+//! by the bootstrappings, using the formulas of [`CustomOddParameters`] below (the usual TFHE
+//! noise analysis, for binary secret keys).
+//!
+//! A ciphertext is correctly bootstrapped as long as its noise stays within half a window of the
+//! accumulator, i.e. `1 / 4p` for an odd p (the windows of the odd elements of Zp are interleaved
+//! with the ones of the even elements) and `1 / 2p` for an even p. The [`NoiseCheck`] of a
+//! [`ServerKey`] compares the resulting failure probability to a threshold after each operator.
+
+use std::ops::Add;
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
+use tfhe_versionable::{Unversionize, UnversionizeError, Versionize, VersionizeOwned};
+
+use crate::core_crypto::prelude::DynamicDistribution;
+use crate::odd::backward_compatibility::noise::NoiseVarianceVersions;
+use crate::odd::prelude::*;
+
+/// Variance of the noise of a ciphertext, on the torus normalized to [0, 1).
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd, Serialize, Deserialize, Versionize)]
+#[versionize(NoiseVarianceVersions)]
+pub struct NoiseVariance(pub f64);
+
+impl NoiseVariance {
+    pub const ZERO: Self = Self(0.0);
+
+    /// Variance of the noise multiplied by an integer coefficient
+    pub fn scale(self, coefficient: u64) -> Self {
+        Self(self.0 * (coefficient as f64).powi(2))
+    }
+
+    /// Base 2 logarithm of (an upper bound on) the probability that a Gaussian noise of this
+    /// variance falls outside of the window of an element of Zp, p being `modulus`.
+    pub fn log2_failure_probability(self, modulus: u64) -> f64 {
+        let margin = if modulus % 2 == 1 {
+            1.0 / (4 * modulus) as f64
+        } else {
+            1.0 / (2 * modulus) as f64
+        };
+        if self.0 == 0.0 {
+            return f64::NEG_INFINITY;
+        }
+        // P(|e| > z sigma) <= sqrt(2 / pi) exp(-z^2 / 2) / z
+        let z = margin / self.0.sqrt();
+        let log2_bound = (2.0 / std::f64::consts::PI).sqrt().log2()
+            - z.log2()
+            - z * z / (2.0 * std::f64::consts::LN_2);
+        log2_bound.min(0.0)
+    }
+}
+
+impl Add for NoiseVariance {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self(self.0 + rhs.0)
+    }
+}
+
+/// Variance of a noise distribution, on the torus normalized to [0, 1)
+fn distribution_variance(distribution: DynamicDistribution<u64>) -> f64 {
+    match distribution {
+        DynamicDistribution::Gaussian(gaussian) => gaussian.standard_dev().0.powi(2),
+        DynamicDistribution::TUniform(t_uniform) => {
+            let bound = 2f64.powi(t_uniform.bound_log2() as i32);
+            (2.0 * bound * bound + 1.0) / 6.0 / 2f64.powi(128)
+        }
+    }
+}
+
+/// Variance added by a keyswitch from an LWE key of dimension `input_dimension`, with keys
+/// encrypted under `distribution`: the noise of the keys, plus the decomposition error multiplied
+/// by the input key
+fn keyswitch_variance(
+    input_dimension: usize,
+    base_log: DecompositionBaseLog,
+    level: DecompositionLevelCount,
+    distribution: DynamicDistribution<u64>,
+) -> NoiseVariance {
+    let base = 2f64.powi(base_log.0 as i32);
+    let n = input_dimension as f64;
+    let l = level.0 as f64;
+    NoiseVariance(
+        n * l * (base * base + 2.0) / 12.0 * distribution_variance(distribution)
+            + n / (24.0 * base.powf(2.0 * l)),
+    )
+}
+
+impl CustomOddParameters {
+    /// Variance of a fresh encryption
+    pub fn encryption_variance(&self) -> NoiseVariance {
+        match self.encryption_key_choice {
+            EncryptionKeyChoice::Big => NoiseVariance(distribution_variance(self.glwe_noise_distribution)),
+            EncryptionKeyChoice::Small => NoiseVariance(distribution_variance(self.lwe_noise_distribution)),
+        }
+    }
+
+    /// Variance of an encryption with a [`PublicKey`]: the sum of about half of its
+    /// `zero_encryption_count` encryptions of zero
+    pub fn public_key_encryption_variance(&self, zero_encryption_count: usize) -> NoiseVariance {
+        NoiseVariance(self.encryption_variance().0 * zero_encryption_count as f64 / 2.0)
+    }
+
+    /// Variance of an encryption with a [`CompactPublicKey`]: the noise of the key multiplied by
+    /// the binary mask of the encryption, plus the noise of the encryption
+    pub fn compact_public_key_encryption_variance(&self) -> NoiseVariance {
+        let dimension = self.ciphertext_lwe_dimension().0 as f64;
+        NoiseVariance(self.encryption_variance().0 * (dimension / 2.0 + 1.0))
+    }
+
+    /// Variance added by the keyswitch from the big LWE key to the small one
+    pub fn keyswitch_variance(&self) -> NoiseVariance {
+        let big_dimension = self.glwe_dimension.to_equivalent_lwe_dimension(self.polynomial_size).0;
+        keyswitch_variance(big_dimension, self.ks_base_log, self.ks_level, self.lwe_noise_distribution)
+    }
+
+    /// Variance added by the packing keyswitch of the tree bootstrapping
+    pub fn packing_keyswitch_variance(&self) -> NoiseVariance {
+        let big_dimension = self.glwe_dimension.to_equivalent_lwe_dimension(self.polynomial_size).0;
+        keyswitch_variance(big_dimension, self.ks_base_log, self.ks_level, self.glwe_noise_distribution)
+    }
+
+    /// Variance added by the modulus switching of the input of a blind rotation to Z_2N
+    pub fn modulus_switch_variance(&self) -> NoiseVariance {
+        let n = self.lwe_dimension.0 as f64;
+        let big_n = self.polynomial_size.0 as f64;
+        NoiseVariance((n / 2.0 + 1.0) / (48.0 * big_n * big_n))
+    }
+
+    /// Variance of the output of a blind rotation (of an accumulator without noise): one external
+    /// product by a GGSW of the bootstrapping key per coefficient of the input
+    pub fn blind_rotation_variance(&self) -> NoiseVariance {
+        let base = 2f64.powi(self.pbs_base_log.0 as i32);
+        let l = self.pbs_level.0 as f64;
+        let k = self.glwe_dimension.0 as f64;
+        let big_n = self.polynomial_size.0 as f64;
+        let external_product = (k + 1.0) * l * big_n * (base * base + 2.0) / 12.0
+            * distribution_variance(self.glwe_noise_distribution)
+            + (1.0 + k * big_n) / (24.0 * base.powf(2.0 * l));
+        NoiseVariance(self.lwe_dimension.0 as f64 * external_product)
+    }
+
+    /// Variance of the output of a bootstrapping whose accumulator is multiplied by a polynomial
+    /// of squared norm `squared_norm` (1 for a simple bootstrapping), back under the encryption key
+    pub fn bootstrap_output_variance(&self, squared_norm: f64) -> NoiseVariance {
+        let rotated = NoiseVariance(self.blind_rotation_variance().0 * squared_norm);
+        match self.encryption_key_choice {
+            EncryptionKeyChoice::Big => rotated,
+            EncryptionKeyChoice::Small => rotated + self.keyswitch_variance(),
+        }
+    }
+
+    /// Variance seen by the blind rotation when bootstrapping a ciphertext of variance `variance`
+    pub fn bootstrap_input_variance(&self, variance: NoiseVariance) -> NoiseVariance {
+        let before_modulus_switch = match self.encryption_key_choice {
+            EncryptionKeyChoice::Big => variance + self.keyswitch_variance(),
+            EncryptionKeyChoice::Small => variance,
+        };
+        before_modulus_switch + self.modulus_switch_variance()
+    }
+
+    /// Smallest variance of an encrypted ciphertext coming out of an encryption or a bootstrapping
+    pub fn min_encrypted_variance(&self) -> NoiseVariance {
+        let fresh = self.encryption_variance();
+        let bootstrapped = self.bootstrap_output_variance(1.0);
+        NoiseVariance(fresh.0.min(bootstrapped.0))
+    }
+
+    /// Base 2 logarithm of the probability that the next bootstrapping of `ciphertext` fails
+    pub fn log2_failure_probability(&self, ciphertext: &Ciphertext) -> f64 {
+        match ciphertext {
+            Ciphertext::Trivial(..) => f64::NEG_INFINITY,
+            Ciphertext::EncodingEncrypted(_, encoding, variance) => self
+                .bootstrap_input_variance(*variance)
+                .log2_failure_probability(encoding.get_modulus()),
+        }
+    }
+}
+
+type NoiseWarningHandler = Box<dyn Fn(&OddError) + Send + Sync>;
+
+static NOISE_WARNING_HANDLER: RwLock<Option<NoiseWarningHandler>> = RwLock::new(None);
+
+/// Call `handler` with the [`OddError::NoiseTooLarge`] of each operator whose result fails a
+/// [`NoiseCheck::Warn`], or drop these warnings for `None` (the default).
+pub fn set_noise_warning_handler(handler: Option<NoiseWarningHandler>) {
+    *NOISE_WARNING_HANDLER.write().unwrap() = handler;
+}
+
+/// What a [`ServerKey`] does when the result of an operator may fail to be bootstrapped with a
+/// probability above `2^log2_threshold`.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum NoiseCheck {
+    #[default]
+    Disabled,
+    /// Pass a [`OddError::NoiseTooLarge`] to the handler set with [`set_noise_warning_handler`]
+    /// and return the result anyway
+    Warn { log2_threshold: f64 },
+    /// Return [`OddError::NoiseTooLarge`] (the panicking APIs panic)
+    Deny { log2_threshold: f64 },
+}
+
+impl NoiseCheck {
+    pub(crate) fn check(&self, ciphertext: &Ciphertext, parameters: &CustomOddParameters) -> Result<(), OddError> {
+        let log2_threshold = match self {
+            Self::Disabled => return Ok(()),
+            Self::Warn { log2_threshold } | Self::Deny { log2_threshold } => *log2_threshold,
+        };
+        let log2_p_fail = parameters.log2_failure_probability(ciphertext);
+        if log2_p_fail <= log2_threshold {
+            return Ok(());
+        }
+        let error = OddError::NoiseTooLarge {
+            modulus: ciphertext.get_encoding().get_modulus(),
+            log2_failure_probability: log2_p_fail.ceil() as i64,
+        };
+        match self {
+            Self::Warn { .. } => {
+                if let Some(handler) = NOISE_WARNING_HANDLER.read().unwrap().as_ref() {
+                    handler(&error);
+                }
+                Ok(())
+            }
+            _ => Err(error),
+        }
+    }
+}
+
+// The noise check is a setting of the server, not a part of its key: it is left out of the
+// versioned layout of the key, and reads back as `NoiseCheck::Disabled`.
+impl Versionize for NoiseCheck {
+    type Versioned<'vers>
+        = ()
+    where
+        Self: 'vers;
+
+    fn versionize(&self) -> Self::Versioned<'_> {}
+}
+
+impl VersionizeOwned for NoiseCheck {
+    type VersionedOwned = ();
+
+    fn versionize_owned(self) -> Self::VersionedOwned {}
+}
+
+impl Unversionize for NoiseCheck {
+    fn unversionize(_versioned: Self::VersionedOwned) -> Result<Self, UnversionizeError> {
+        Ok(Self::default())
+    }
+}
+
+
+#[test]
+fn test_noise_estimates() {
+    use crate::odd::engine::{OddEngine, WithThreadLocalEngine};
+    use crate::odd::TEST_PARAMETERS;
+
+    let (cks, sks) = gen_keys(&TEST_PARAMETERS);
+    let nibble = Encoding::new_canonical(16, (0..16).collect(), 17);
+    let squared_noise = |c: &Ciphertext| {
+        let noise = OddEngine::with_thread_local_mut(|engine| engine.measure_noise(c, &cks)).unwrap();
+        (noise as f64 / 2f64.powi(64) / 17.0).powi(2)
+    };
+
+    // the empirical variances over a few samples stay close to the estimates
+    let samples = 20;
+    let mut measured = [0.0; 3];
+    let mut estimated = [0.0; 3];
+    for i in 0..samples {
+        let fresh = cks.encrypt_arithmetic(i % 16, &nibble);
+        let bootstrapped = sks.apply_lut(&fresh, &nibble, &|x| (x + 1) % 16);
+        let other = sks.apply_lut(&fresh, &nibble, &|x| (x + 2) % 16);
+        let combination = sks.linear_combination(&[bootstrapped.clone(), other], &[3, 1], 17);
+        for (k, c) in [fresh, bootstrapped, combination].iter().enumerate() {
+            measured[k] += squared_noise(c) / samples as f64;
+            estimated[k] = c.noise_variance().0;
+        }
+    }
+    for (m, e) in measured.iter().zip(estimated) {
+        assert!(m / e > 0.25 && m / e < 4.0, "measured {m:e}, estimated {e:e}");
+    }
+    assert_eq!(sks.trivial_encrypt(1, &nibble).noise_variance(), NoiseVariance::ZERO);
+}
+
+
+#[test]
+fn test_noise_check() {
+    use crate::odd::TEST_PARAMETERS;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let (cks, mut sks) = gen_keys(&TEST_PARAMETERS);
+    let nibble = Encoding::new_canonical(16, (0..16).collect(), 17);
+    let ct = sks.apply_lut(&cks.encrypt_arithmetic(1, &nibble), &nibble, &|x| x);
+    let many = vec![ct.clone(); 64];
+    let single = sks.log2_failure_probability(&ct);
+    let sum = sks.log2_failure_probability(&sks.simple_sum(&many));
+    assert!(sum > single);
+
+    // a threshold between the two rejects the long sum only
+    let log2_threshold = (single + sum) / 2.0;
+    sks.set_noise_check(NoiseCheck::Deny { log2_threshold });
+    assert!(sks.try_simple_sum(&[ct.clone(), ct]).is_ok());
+    assert_eq!(
+        sks.try_simple_sum(&many).unwrap_err(),
+        OddError::NoiseTooLarge { modulus: 17, log2_failure_probability: sum.ceil() as i64 }
+    );
+    let coefficients = vec![1; 64];
+    assert!(matches!(
+        sks.try_linear_combination(&many, &coefficients, 17),
+        Err(OddError::NoiseTooLarge { .. })
+    ));
+
+    // the warning mode only calls the handler, and the bootstrapping resets the noise
+    let warnings = Arc::new(AtomicUsize::new(0));
+    let counter = warnings.clone();
+    set_noise_warning_handler(Some(Box::new(move |error| {
+        assert!(matches!(error, OddError::NoiseTooLarge { modulus: 17, .. }));
+        counter.fetch_add(1, Ordering::Relaxed);
+    })));
+    sks.set_noise_check(NoiseCheck::Warn { log2_threshold });
+    let sum = sks.simple_sum(&many);
+    set_noise_warning_handler(None);
+    assert_eq!(warnings.load(Ordering::Relaxed), 1);
+    sks.set_noise_check(NoiseCheck::Deny { log2_threshold });
+    assert!(sks.try_apply_lut(&sum, &nibble, &|x| x).is_ok());
+
+    // the noise check is a setting of the server, which is not serialized with its key
+    let sks: ServerKey = bincode::deserialize(&bincode::serialize(&sks).unwrap()).unwrap();
+    assert_eq!(sks.noise_check(), NoiseCheck::Disabled);
+}
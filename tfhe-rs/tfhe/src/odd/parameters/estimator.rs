@@ -0,0 +1,187 @@
+//! Estimation of the noise and of the cost of a parameter set.
+//!
+//! A circuit on (o,p)-encodings alternates linear combinations and bootstrappings: each
+//! bootstrapping is fed a linear combination of bootstrapped (or freshly encrypted) ciphertexts,
+//! whose coefficients have a 2-norm bounded by some `max_norm`. Given a [`CustomOddParameters`],
+//! a modulus p and this bound, [`CustomOddParameters::estimate`] reports the noise of each step
+//! and the probability that a bootstrapping fails, using the formulas of
+//! [`crate::odd::noise`].
+//!
+//! [`CustomOddParameters::cheapest_decomposition`] looks for the cheapest decomposition
+//! parameters (of the bootstrapping and of the keyswitch) meeting a target failure probability.
+//! The dimensions and the noise distributions, which set the security of the parameters, are
+//! left untouched.
+
+use crate::odd::noise::NoiseVariance;
+use crate::odd::parameters::{CustomOddParameters, DecompositionBaseLog, DecompositionLevelCount};
+
+/// Bits of the ciphertext modulus, bounding the precision of a decomposition
+const MODULUS_BITS: usize = 64;
+
+/// Noise estimates of a parameter set for a modulus p and a bound on the 2-norm of the linear
+/// combinations fed to the bootstrappings.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NoiseEstimate {
+    pub modulus: u64,
+    pub max_norm: f64,
+    /// Variance of the output of a bootstrapping (keyswitch included for the small key)
+    pub bootstrap_variance: NoiseVariance,
+    /// Variance added by the keyswitch from the big LWE key to the small one
+    pub keyswitch_variance: NoiseVariance,
+    /// Variance added by the packing keyswitch of the tree bootstrapping
+    pub packing_keyswitch_variance: NoiseVariance,
+    /// Variance seen by the blind rotation for a linear combination of norm `max_norm`
+    pub input_variance: NoiseVariance,
+    /// Base 2 logarithm of the probability that a bootstrapping fails
+    pub log2_failure_probability: f64,
+}
+
+impl CustomOddParameters {
+    /// Variance of the inputs of the linear combinations: the largest of a fresh encryption and of
+    /// a bootstrapping output
+    fn linear_input_variance(&self) -> NoiseVariance {
+        let fresh = self.encryption_variance();
+        let bootstrapped = self.bootstrap_output_variance(1.0);
+        if fresh > bootstrapped {
+            fresh
+        } else {
+            bootstrapped
+        }
+    }
+
+    /// Estimates the noise of the bootstrappings of linear combinations of norm at most
+    /// `max_norm`, for an encoding modulus p = `modulus` (at least 2).
+    pub fn estimate(&self, modulus: u64, max_norm: f64) -> NoiseEstimate {
+        let combination = NoiseVariance(self.linear_input_variance().0 * max_norm * max_norm);
+        let input_variance = self.bootstrap_input_variance(combination);
+        NoiseEstimate {
+            modulus,
+            max_norm,
+            bootstrap_variance: self.bootstrap_output_variance(1.0),
+            keyswitch_variance: self.keyswitch_variance(),
+            packing_keyswitch_variance: self.packing_keyswitch_variance(),
+            input_variance,
+            log2_failure_probability: input_variance.log2_failure_probability(modulus),
+        }
+    }
+
+    /// Largest 2-norm of a linear combination that is bootstrapped with a failure probability
+    /// below `2^log2_target`, for an encoding modulus p = `modulus`. Returns 0 if even a single
+    /// ciphertext does not meet the target, and infinity if every norm meets it (which is the case
+    /// of any non-negative target).
+    pub fn max_supported_norm(&self, modulus: u64, log2_target: f64) -> f64 {
+        let meets = |norm: f64| self.estimate(modulus, norm).log2_failure_probability <= log2_target;
+        if !meets(1.0) {
+            return 0.0;
+        }
+        let mut low = 1.0;
+        let mut high = 2.0;
+        while meets(high) {
+            if !high.is_finite() {
+                return f64::INFINITY;
+            }
+            low = high;
+            high *= 2.0;
+        }
+        for _ in 0..64 {
+            let middle = (low + high) / 2.0;
+            if meets(middle) {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        low
+    }
+
+    /// Rough cost of a bootstrapping, in multiplications: the FFT-based external products of the
+    /// blind rotation plus the keyswitch. It is only meant to compare parameter sets.
+    pub fn estimated_cost(&self) -> f64 {
+        let n = self.lwe_dimension.0 as f64;
+        let k = self.glwe_dimension.0 as f64;
+        let big_n = self.polynomial_size.0 as f64;
+        let blind_rotation =
+            n * (k + 1.0) * (k + 1.0) * self.pbs_level.0 as f64 * big_n * big_n.log2();
+        let keyswitch = k * big_n * self.ks_level.0 as f64 * (n + 1.0);
+        blind_rotation + keyswitch
+    }
+
+    /// Cheapest (according to [`Self::estimated_cost`]) parameter set only differing from `self`
+    /// by its decomposition parameters, whose bootstrappings of linear combinations of norm
+    /// `max_norm` fail with a probability below `2^log2_target`. Returns `None` if no
+    /// decomposition meets the target.
+    pub fn cheapest_decomposition(
+        &self,
+        modulus: u64,
+        max_norm: f64,
+        log2_target: f64,
+    ) -> Option<Self> {
+        let decompositions = || {
+            (1..=MODULUS_BITS).flat_map(|level| {
+                (1..=MODULUS_BITS / level)
+                    .map(move |base_log| (DecompositionBaseLog(base_log), DecompositionLevelCount(level)))
+            })
+        };
+        let mut best: Option<(f64, Self)> = None;
+        for (pbs_base_log, pbs_level) in decompositions() {
+            for (ks_base_log, ks_level) in decompositions() {
+                let candidate = Self {
+                    pbs_base_log,
+                    pbs_level,
+                    ks_base_log,
+                    ks_level,
+                    ..*self
+                };
+                let cost = candidate.estimated_cost();
+                if best.as_ref().is_some_and(|(best_cost, _)| *best_cost <= cost) {
+                    continue;
+                }
+                if candidate.estimate(modulus, max_norm).log2_failure_probability <= log2_target {
+                    best = Some((cost, candidate));
+                }
+            }
+        }
+        best.map(|(_, parameters)| parameters)
+    }
+}
+
+
+#[test]
+fn test_estimate() {
+    use crate::odd::TEST_PARAMETERS;
+
+    let estimate = TEST_PARAMETERS.estimate(17, 4.0);
+    assert_eq!(estimate.bootstrap_variance, TEST_PARAMETERS.bootstrap_output_variance(1.0));
+    assert!(estimate.input_variance > estimate.bootstrap_variance);
+    assert!(estimate.log2_failure_probability < -15.0);
+
+    // larger norms and moduli fail more often
+    assert!(TEST_PARAMETERS.estimate(17, 8.0).log2_failure_probability > estimate.log2_failure_probability);
+    assert!(TEST_PARAMETERS.estimate(33, 4.0).log2_failure_probability > estimate.log2_failure_probability);
+
+    // the maximum supported norm is where the target is reached
+    let norm = TEST_PARAMETERS.max_supported_norm(17, -17.0);
+    assert!(norm > 1.0);
+    assert!(TEST_PARAMETERS.estimate(17, norm).log2_failure_probability <= -17.0);
+    assert!(TEST_PARAMETERS.estimate(17, norm * 1.01).log2_failure_probability > -17.0);
+    assert_eq!(TEST_PARAMETERS.max_supported_norm(1 << 20, -40.0), 0.0);
+
+    // the failure probability never exceeds 1
+    assert_eq!(TEST_PARAMETERS.max_supported_norm(17, 0.0), f64::INFINITY);
+    assert_eq!(TEST_PARAMETERS.max_supported_norm(17, 3.0), f64::INFINITY);
+}
+
+
+#[test]
+fn test_cheapest_decomposition() {
+    use crate::odd::TEST_PARAMETERS;
+
+    let target = TEST_PARAMETERS.estimate(17, 4.0).log2_failure_probability;
+    let cheapest = TEST_PARAMETERS.cheapest_decomposition(17, 4.0, target).unwrap();
+    assert!(cheapest.estimated_cost() <= TEST_PARAMETERS.estimated_cost());
+    assert!(cheapest.estimate(17, 4.0).log2_failure_probability <= target);
+    assert_eq!(cheapest.lwe_dimension, TEST_PARAMETERS.lwe_dimension);
+    assert_eq!(cheapest.polynomial_size, TEST_PARAMETERS.polynomial_size);
+
+    assert!(TEST_PARAMETERS.cheapest_decomposition(1 << 20, 4.0, -40.0).is_none());
+}
@@ -18,15 +18,25 @@
 //! This is an unsafe operation as failing to properly fix the parameters will potentially result
 //! with an incorrect and/or insecure computation.
 
+pub mod estimator;
+
 pub use crate::core_crypto::commons::dispersion::StandardDev;
 pub use crate::core_crypto::commons::parameters::{
     DecompositionBaseLog, DecompositionLevelCount, GlweDimension, LweDimension, PolynomialSize, EncryptionKeyChoice
 };
-use crate::core_crypto::prelude::DynamicDistribution;
+use crate::core_crypto::fft_impl::fft64::crypto::bootstrap::BootstrapKeyConformanceParams;
+use crate::core_crypto::prelude::{
+    CiphertextModulus, DynamicDistribution, KeyswitchKeyConformanceParams, LweCiphertextParameters,
+    MsDecompressionType, PackingKeyswitchConformanceParams,
+};
+use crate::odd::backward_compatibility::parameters::CustomOddParametersVersions;
+pub use estimator::NoiseEstimate;
 use serde::{Deserialize, Serialize};
+use tfhe_versionable::Versionize;
 
 /// A set of cryptographic parameters for homomorphic Boolean circuit evaluation.
-#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize, Versionize)]
+#[versionize(CustomOddParametersVersions)]
 pub struct CustomOddParameters {
     pub lwe_dimension: LweDimension,
     pub glwe_dimension: GlweDimension,
@@ -40,6 +50,10 @@ pub struct CustomOddParameters {
     pub encryption_key_choice: EncryptionKeyChoice
 }
 
+impl crate::named::Named for CustomOddParameters {
+    const NAME: &'static str = "odd::CustomOddParameters";
+}
+
 impl CustomOddParameters {
     /// Constructs a new set of parameters for boolean circuit evaluation.
     ///
@@ -75,4 +89,66 @@ impl CustomOddParameters {
             encryption_key_choice
         }
     }
-}
\ No newline at end of file
+
+    /// The dimension of the LWE ciphertexts handled by the user, i.e. the dimension of the key
+    /// selected by `encryption_key_choice`.
+    pub fn ciphertext_lwe_dimension(&self) -> LweDimension {
+        match self.encryption_key_choice {
+            EncryptionKeyChoice::Big => self
+                .glwe_dimension
+                .to_equivalent_lwe_dimension(self.polynomial_size),
+            EncryptionKeyChoice::Small => self.lwe_dimension,
+        }
+    }
+
+    /// The parameters an odd ciphertext has to match to be used with this parameter set.
+    pub fn to_lwe_ciphertext_parameters(&self) -> LweCiphertextParameters<u64> {
+        LweCiphertextParameters {
+            lwe_dim: self.ciphertext_lwe_dimension(),
+            ct_modulus: CiphertextModulus::new_native(),
+            ms_decompression_method: MsDecompressionType::ClassicPbs,
+        }
+    }
+}
+
+impl From<&CustomOddParameters> for BootstrapKeyConformanceParams {
+    fn from(value: &CustomOddParameters) -> Self {
+        Self {
+            decomp_base_log: value.pbs_base_log,
+            decomp_level_count: value.pbs_level,
+            input_lwe_dimension: value.lwe_dimension,
+            output_glwe_size: value.glwe_dimension.to_glwe_size(),
+            polynomial_size: value.polynomial_size,
+            ciphertext_modulus: CiphertextModulus::new_native(),
+        }
+    }
+}
+
+impl From<&CustomOddParameters> for KeyswitchKeyConformanceParams {
+    fn from(value: &CustomOddParameters) -> Self {
+        Self {
+            decomp_base_log: value.ks_base_log,
+            decomp_level_count: value.ks_level,
+            output_lwe_size: value.lwe_dimension.to_lwe_size(),
+            input_lwe_dimension: value
+                .glwe_dimension
+                .to_equivalent_lwe_dimension(value.polynomial_size),
+            ciphertext_modulus: CiphertextModulus::new_native(),
+        }
+    }
+}
+
+impl From<&CustomOddParameters> for PackingKeyswitchConformanceParams {
+    fn from(value: &CustomOddParameters) -> Self {
+        Self {
+            decomp_base_log: value.ks_base_log,
+            decomp_level_count: value.ks_level,
+            input_lwe_dimension: value
+                .glwe_dimension
+                .to_equivalent_lwe_dimension(value.polynomial_size),
+            output_glwe_size: value.glwe_dimension.to_glwe_size(),
+            output_polynomial_size: value.polynomial_size,
+            ciphertext_modulus: CiphertextModulus::new_native(),
+        }
+    }
+}
@@ -3,8 +3,17 @@
 //! The TFHE-rs preludes include convenient imports.
 //! Having `tfhe::odd::prelude::*;` should be enough to start using the lib.
 
-pub use super::ciphertext::{Ciphertext, Encoding};
+pub use super::backend::OddBackend;
+pub use super::bristol::{BristolCircuit, BristolGate};
+pub use super::circuit::{BooleanCircuit, CompiledCircuit, CompilerOptions, Gate, Wire};
+pub use super::ciphertext::{Ciphertext, CompressedCiphertext, Encoding};
 pub use super::client_key::ClientKey;
+pub use super::encoding_search::{search_gadget, Gadget, GadgetConstraints, ModulusParity};
+pub use super::error::OddError;
 pub use super::gen_keys;
+pub use super::lookup_table::{MvbLookupTable, OddLookupTable};
+pub use super::noise::{set_noise_warning_handler, NoiseCheck, NoiseVariance};
 pub use super::parameters::*;
-pub use super::server_key::ServerKey;
+pub use super::public_key::{CompactPublicKey, PublicKey};
+pub use super::server_key::{set_batch_threads, CompressedServerKey, ServerKey};
+pub use super::simulation::SimulatedServerKey;
@@ -0,0 +1,153 @@
+//! Module with the definition of the CompactPublicKey.
+
+use crate::core_crypto::entities::*;
+use crate::odd::backward_compatibility::public_key::CompactPublicKeyVersions;
+use crate::odd::ciphertext::{Ciphertext, Encoding};
+use crate::odd::client_key::ClientKey;
+use crate::odd::engine::{OddEngine, WithThreadLocalEngine};
+use crate::odd::error::OddError;
+use crate::odd::parameters::CustomOddParameters;
+use serde::{Deserialize, Serialize};
+use tfhe_versionable::Versionize;
+
+/// A structure containing a compact public key.
+///
+/// The key is a single RLWE-like encryption of zero, so it only weighs two polynomials. It
+/// requires the dimension of the key selected by `encryption_key_choice` to be a power of two,
+/// which is always the case with [`EncryptionKeyChoice::Big`](`crate::odd::prelude::EncryptionKeyChoice::Big`).
+#[derive(Clone, Debug, Serialize, Deserialize, Versionize)]
+#[versionize(CompactPublicKeyVersions)]
+pub struct CompactPublicKey {
+    pub(crate) lwe_compact_public_key: LweCompactPublicKeyOwned<u64>,
+    pub(crate) parameters: CustomOddParameters,
+}
+
+impl crate::named::Named for CompactPublicKey {
+    const NAME: &'static str = "odd::CompactPublicKey";
+}
+
+impl CompactPublicKey {
+    /// Encrypt a message of Zo with the given canonical encoding using the compact public key.
+    pub fn encrypt_arithmetic(&self, message: u64, encoding: &Encoding) -> Ciphertext {
+        self.try_encrypt_arithmetic(message, encoding)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_encrypt_arithmetic(
+        &self,
+        message: u64,
+        encoding: &Encoding,
+    ) -> Result<Ciphertext, OddError> {
+        OddEngine::with_thread_local_mut(|engine| {
+            engine.encrypt_arithmetic_with_compact_public_key(message, encoding, self)
+        })
+    }
+
+    /// Allocate and generate a compact public key from a client key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the dimension of the encryption key is not a power of two.
+    pub fn new(client_key: &ClientKey) -> Self {
+        Self::try_new(client_key).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Same as [`Self::new`], failing with [`OddError::UnsupportedKeyDimension`] if the dimension
+    /// of the encryption key selected by `encryption_key_choice` is not a power of two (which is
+    /// usually the case with [`EncryptionKeyChoice::Small`](`crate::odd::prelude::EncryptionKeyChoice::Small`)).
+    pub fn try_new(client_key: &ClientKey) -> Result<Self, OddError> {
+        OddEngine::with_thread_local_mut(|engine| engine.create_compact_public_key(client_key))
+    }
+
+    pub fn parameters(&self) -> CustomOddParameters {
+        self.parameters
+    }
+
+    /// Deconstruct a [`CompactPublicKey`] into its constituents.
+    pub fn into_raw_parts(self) -> (LweCompactPublicKeyOwned<u64>, CustomOddParameters) {
+        let Self {
+            lwe_compact_public_key,
+            parameters,
+        } = self;
+
+        (lwe_compact_public_key, parameters)
+    }
+
+    /// Construct a [`CompactPublicKey`] from its constituents.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the constituents are not compatible with each others.
+    pub fn from_raw_parts(
+        lwe_compact_public_key: LweCompactPublicKeyOwned<u64>,
+        parameters: CustomOddParameters,
+    ) -> Self {
+        assert_eq!(
+            lwe_compact_public_key.lwe_dimension(),
+            parameters.ciphertext_lwe_dimension(),
+            "Mismatch between LweCompactPublicKeyOwned LweDimension ({:?}) \
+            and parameters LweDimension ({:?})",
+            lwe_compact_public_key.lwe_dimension(),
+            parameters.ciphertext_lwe_dimension(),
+        );
+
+        assert!(
+            lwe_compact_public_key
+                .ciphertext_modulus()
+                .is_native_modulus(),
+            "LweCompactPublicKeyOwned CiphertextModulus needs to be the native modulus got ({:?})",
+            lwe_compact_public_key.ciphertext_modulus()
+        );
+
+        Self {
+            lwe_compact_public_key,
+            parameters,
+        }
+    }
+}
+
+#[test]
+fn test_compact_public_key_encryption() {
+    use crate::odd::prelude::*;
+    use crate::odd::TEST_PARAMETERS;
+
+    let (cks, sks) = gen_keys(&TEST_PARAMETERS);
+    let compact_pks = CompactPublicKey::new(&cks);
+
+    let encoding = Encoding::new_canonical(5, vec![0, 1, 2, 3, 4], 11);
+    for msg in 0..5 {
+        let ct = compact_pks.encrypt_arithmetic(msg, &encoding);
+        assert_eq!(cks.decrypt(&ct), msg);
+
+        let res = sks.apply_lut(&ct, &encoding, &|x| (3 * x) % 5);
+        assert_eq!(cks.decrypt(&res), (3 * msg) % 5);
+    }
+}
+
+#[test]
+fn test_compact_public_key_small_encryption_key() {
+    use crate::odd::prelude::*;
+    use crate::odd::TEST_PARAMETERS;
+
+    // the small key of the test parameters has dimension 754
+    let small_parameters = CustomOddParameters {
+        encryption_key_choice: EncryptionKeyChoice::Small,
+        ..TEST_PARAMETERS
+    };
+    let cks = ClientKey::new(&small_parameters);
+    assert_eq!(
+        CompactPublicKey::try_new(&cks).unwrap_err(),
+        OddError::UnsupportedKeyDimension { dimension: 754 }
+    );
+
+    // a small key of power of two dimension is supported
+    let parameters = CustomOddParameters {
+        lwe_dimension: LweDimension(1024),
+        ..small_parameters
+    };
+    let cks = ClientKey::new(&parameters);
+    let compact_pks = CompactPublicKey::try_new(&cks).unwrap();
+    let encoding = Encoding::new_canonical(5, vec![0, 1, 2, 3, 4], 11);
+    let ct = compact_pks.encrypt_arithmetic(3, &encoding);
+    assert_eq!(cks.decrypt(&ct), 3);
+}
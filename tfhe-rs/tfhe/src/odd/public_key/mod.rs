@@ -0,0 +1,10 @@
+//! The public keys of the client.
+//!
+//! This module implements the generation of public keys, which allow anyone to encrypt inputs
+//! for the server without knowing the secret key.
+
+mod compact;
+mod standard;
+
+pub use compact::CompactPublicKey;
+pub use standard::PublicKey;
@@ -0,0 +1,139 @@
+//! Module with the definition of the encryption PublicKey.
+
+use crate::core_crypto::entities::*;
+use crate::odd::backward_compatibility::public_key::PublicKeyVersions;
+use crate::odd::ciphertext::{Ciphertext, Encoding};
+use crate::odd::client_key::ClientKey;
+use crate::odd::engine::{OddEngine, WithThreadLocalEngine};
+use crate::odd::error::OddError;
+use crate::odd::parameters::CustomOddParameters;
+use serde::{Deserialize, Serialize};
+use tfhe_versionable::Versionize;
+
+/// A structure containing a public key.
+///
+/// It is made of encryptions of zero under the key selected by the `encryption_key_choice` of
+/// the parameters, so the ciphertexts it produces can be used exactly like the ones produced by
+/// [`ClientKey::encrypt_arithmetic`].
+///
+/// Note that this key is large (about `(n + 1)^2 * 64` words for a ciphertext dimension `n`); the
+/// [`CompactPublicKey`](`super::CompactPublicKey`) is a lot smaller.
+#[derive(Clone, Debug, Serialize, Deserialize, Versionize)]
+#[versionize(PublicKeyVersions)]
+pub struct PublicKey {
+    pub(crate) lwe_public_key: LwePublicKeyOwned<u64>,
+    pub(crate) parameters: CustomOddParameters,
+}
+
+impl crate::named::Named for PublicKey {
+    const NAME: &'static str = "odd::PublicKey";
+}
+
+impl PublicKey {
+    /// Encrypt a message of Zo with the given canonical encoding using the public key.
+    pub fn encrypt_arithmetic(&self, message: u64, encoding: &Encoding) -> Ciphertext {
+        self.try_encrypt_arithmetic(message, encoding)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_encrypt_arithmetic(
+        &self,
+        message: u64,
+        encoding: &Encoding,
+    ) -> Result<Ciphertext, OddError> {
+        OddEngine::with_thread_local_mut(|engine| {
+            engine.encrypt_arithmetic_with_public_key(message, encoding, self)
+        })
+    }
+
+    /// Allocate and generate a public key from a client key.
+    pub fn new(client_key: &ClientKey) -> Self {
+        OddEngine::with_thread_local_mut(|engine| engine.create_public_key(client_key))
+    }
+
+    pub fn parameters(&self) -> CustomOddParameters {
+        self.parameters
+    }
+
+    /// Deconstruct a [`PublicKey`] into its constituents.
+    pub fn into_raw_parts(self) -> (LwePublicKeyOwned<u64>, CustomOddParameters) {
+        let Self {
+            lwe_public_key,
+            parameters,
+        } = self;
+
+        (lwe_public_key, parameters)
+    }
+
+    /// Construct a [`PublicKey`] from its constituents.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the constituents are not compatible with each others.
+    pub fn from_raw_parts(
+        lwe_public_key: LwePublicKeyOwned<u64>,
+        parameters: CustomOddParameters,
+    ) -> Self {
+        assert_eq!(
+            lwe_public_key.lwe_size().to_lwe_dimension(),
+            parameters.ciphertext_lwe_dimension(),
+            "Mismatch between LwePublicKeyOwned LweDimension ({:?}) \
+            and parameters LweDimension ({:?})",
+            lwe_public_key.lwe_size().to_lwe_dimension(),
+            parameters.ciphertext_lwe_dimension(),
+        );
+
+        assert!(
+            lwe_public_key.ciphertext_modulus().is_native_modulus(),
+            "LwePublicKeyOwned CiphertextModulus needs to be the native modulus got ({:?})",
+            lwe_public_key.ciphertext_modulus()
+        );
+
+        Self {
+            lwe_public_key,
+            parameters,
+        }
+    }
+}
+
+#[test]
+fn test_public_key_encryption() {
+    use crate::odd::prelude::*;
+    use crate::odd::TEST_PARAMETERS;
+
+    let (cks, sks) = gen_keys(&TEST_PARAMETERS);
+    let pks = PublicKey::new(&cks);
+
+    let encoding = Encoding::new_canonical(5, vec![0, 1, 2, 3, 4], 11);
+    for msg in 0..5 {
+        let ct = pks.encrypt_arithmetic(msg, &encoding);
+        assert_eq!(cks.decrypt(&ct), msg);
+
+        let res = sks.apply_lut(&ct, &encoding, &|x| (x + 2) % 5);
+        assert_eq!(cks.decrypt(&res), (msg + 2) % 5);
+    }
+}
+
+#[test]
+fn test_public_key_small_encryption_key() {
+    use crate::odd::prelude::*;
+    use crate::odd::TEST_PARAMETERS;
+
+    // the ciphertexts are under the small key, and go through a bootstrap then a keyswitch
+    let parameters = CustomOddParameters {
+        encryption_key_choice: EncryptionKeyChoice::Small,
+        ..TEST_PARAMETERS
+    };
+    let (cks, sks) = gen_keys(&parameters);
+    let pks = PublicKey::new(&cks);
+    assert_eq!(pks.parameters().encryption_key_choice, EncryptionKeyChoice::Small);
+
+    let encoding = Encoding::new_canonical(5, vec![0, 1, 2, 3, 4], 11);
+    for msg in 0..5 {
+        let ct = pks.encrypt_arithmetic(msg, &encoding);
+        assert_eq!(cks.decrypt(&ct), msg);
+
+        let res = sks.apply_lut(&ct, &encoding, &|x| (x + 2) % 5);
+        assert_eq!(cks.decrypt(&res), (msg + 2) % 5);
+    }
+}
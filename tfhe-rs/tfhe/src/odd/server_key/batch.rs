@@ -0,0 +1,287 @@
+//! Batch versions of the bootstrapping operators.
+//!
+//! The inputs of a batch are processed in parallel, over the pool set with [`set_batch_threads`]
+//! (the global rayon pool by default), and the results are returned in the order of the inputs.
+//! Each worker thread keeps its own engine, so that the caches of accumulators and the scratch
+//! buffers of the bootstrappings are reused from one input to the next.
+//!
+//! The functions to evaluate are tabulated once on the calling thread, so they do not have to be
+//! `Sync`.
+
+use std::sync::{Arc, RwLock};
+
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use crate::odd::engine::{OddEngine, WithThreadLocalEngine};
+use crate::odd::prelude::*;
+
+static BATCH_THREAD_POOL: RwLock<Option<Arc<ThreadPool>>> = RwLock::new(None);
+
+/// Run the batch operators on a dedicated pool of `threads` threads, or on the global rayon pool
+/// for `None`.
+pub fn set_batch_threads(threads: Option<usize>) -> Result<(), OddError> {
+    let pool = match threads {
+        Some(threads) => Some(Arc::new(
+            ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .map_err(|err| OddError::ThreadPool {
+                    message: err.to_string(),
+                })?,
+        )),
+        None => None,
+    };
+    *BATCH_THREAD_POOL.write().unwrap() = pool;
+    Ok(())
+}
+
+//apply op to each input in parallel, the results being in the order of the inputs
+fn run_batch<T, R, F>(inputs: &[T], op: F) -> Result<Vec<R>, OddError>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> Result<R, OddError> + Sync,
+{
+    let run = || inputs.par_iter().map(&op).collect();
+    let pool = BATCH_THREAD_POOL.read().unwrap().clone();
+    pool.map_or_else(run, |pool| pool.install(run))
+}
+
+//values of f on 0..size, so that the workers can evaluate it without f being Sync
+fn tabulate(f: &dyn Fn(u64) -> u64, size: u64) -> Vec<u64> {
+    (0..size).map(f).collect()
+}
+
+impl ServerKey {
+    /// [`Self::apply_lut`] on each input.
+    pub fn apply_lut_batch(
+        &self,
+        inputs: &[Ciphertext],
+        encoding_out: &Encoding,
+        f: &dyn Fn(u64) -> u64,
+    ) -> Vec<Ciphertext> {
+        self.try_apply_lut_batch(inputs, encoding_out, f)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Fails if any of the inputs fails, the error being the one of one of them.
+    pub fn try_apply_lut_batch(
+        &self,
+        inputs: &[Ciphertext],
+        encoding_out: &Encoding,
+        f: &dyn Fn(u64) -> u64,
+    ) -> Result<Vec<Ciphertext>, OddError> {
+        let representatives = encoding_out.try_default_representatives()?;
+        let size = inputs
+            .iter()
+            .map(|c| c.get_encoding().get_origin_modulus())
+            .max()
+            .unwrap_or(0);
+        let lut = tabulate(f, size);
+        run_batch(inputs, |input| {
+            self.try_apply_lut_with_representatives(input, encoding_out, &representatives, &|x| {
+                lut[x as usize]
+            })
+        })
+    }
+
+    /// [`Self::mvb`] on each input.
+    pub fn mvb_batch(
+        &self,
+        inputs: &[Ciphertext],
+        encodings_out: &[Encoding],
+        fis: &[Box<dyn Fn(u64) -> u64>],
+    ) -> Vec<Vec<Ciphertext>> {
+        self.try_mvb_batch(inputs, encodings_out, fis)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Fails if `encodings_out` and `fis` have different lengths, or if any of the inputs fails,
+    /// the error being the one of one of them.
+    pub fn try_mvb_batch(
+        &self,
+        inputs: &[Ciphertext],
+        encodings_out: &[Encoding],
+        fis: &[Box<dyn Fn(u64) -> u64>],
+    ) -> Result<Vec<Vec<Ciphertext>>, OddError> {
+        if encodings_out.len() != fis.len() {
+            return Err(OddError::LengthMismatch {
+                expected: fis.len(),
+                got: encodings_out.len(),
+            });
+        }
+        let representatives: Vec<Vec<u64>> = encodings_out
+            .iter()
+            .map(Encoding::try_default_representatives)
+            .collect::<Result<_, _>>()?;
+        let inputs_with_luts: Vec<(&Ciphertext, Vec<Vec<u64>>)> = inputs
+            .iter()
+            .map(|input| {
+                (
+                    input,
+                    fis.iter()
+                        .map(|fi| tabulate(fi, input.get_encoding().get_origin_modulus()))
+                        .collect(),
+                )
+            })
+            .collect();
+        run_batch(&inputs_with_luts, |(input, lut_fis)| {
+            self.all_checked(OddEngine::with_thread_local_mut(|engine| {
+                engine.mvb(input, encodings_out, &representatives, lut_fis, self)
+            }))
+        })
+    }
+
+    /// [`Self::full_tree_bootstrapping`] on each list of inputs, `inputs[i]` holding the digits of the
+    /// i-th number.
+    pub fn tree_bootstrapping_batch(
+        &self,
+        inputs: &[Vec<Ciphertext>],
+        encodings_out: &[Encoding],
+        t: u64,
+        f: &dyn Fn(u64) -> u64,
+    ) -> Vec<Vec<Ciphertext>> {
+        self.try_tree_bootstrapping_batch(inputs, encodings_out, t, f)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_tree_bootstrapping_batch(
+        &self,
+        inputs: &[Vec<Ciphertext>],
+        encodings_out: &[Encoding],
+        t: u64,
+        f: &dyn Fn(u64) -> u64,
+    ) -> Result<Vec<Vec<Ciphertext>>, OddError> {
+        let table = tabulate(f, t);
+        run_batch(inputs, |digits| {
+            self.try_full_tree_bootstrapping(digits, encodings_out, t, &|x| table[x as usize])
+        })
+    }
+}
+
+#[test]
+fn test_batch_operators() {
+    use crate::odd::TEST_PARAMETERS;
+
+    let (cks, sks) = gen_keys(&TEST_PARAMETERS);
+    let nibble = Encoding::new_canonical(16, (0..16).collect(), 17);
+    let f = |x: u64| (x * 7 + 3) % 256;
+    let messages = [0x00, 0x5c, 0xff];
+
+    for threads in [Some(2), None] {
+        set_batch_threads(threads).unwrap();
+
+        let inputs: Vec<Ciphertext> = messages
+            .iter()
+            .map(|m| cks.encrypt_arithmetic(m % 16, &nibble))
+            .collect();
+        let res = sks.apply_lut_batch(&inputs, &nibble, &|x| f(x) % 16);
+        assert_eq!(
+            res.iter().map(|c| cks.decrypt(c)).collect::<Vec<_>>(),
+            messages.map(|m| f(m % 16) % 16)
+        );
+
+        let fis: Vec<Box<dyn Fn(u64) -> u64>> = vec![Box::new(|x| x % 2), Box::new(|x| x / 2)];
+        let res = sks.mvb_batch(&inputs, &[nibble.clone(), nibble.clone()], &fis);
+        assert_eq!(
+            res.iter()
+                .map(|r| r.iter().map(|c| cks.decrypt(c)).collect::<Vec<_>>())
+                .collect::<Vec<_>>(),
+            messages.map(|m| vec![m % 16 % 2, m % 16 / 2])
+        );
+
+        // a public number goes through with the others
+        let inputs: Vec<Vec<Ciphertext>> = messages
+            .iter()
+            .map(|m| {
+                vec![
+                    cks.encrypt_arithmetic(m / 16, &nibble),
+                    cks.encrypt_arithmetic(m % 16, &nibble),
+                ]
+            })
+            .chain([vec![
+                sks.trivial_encrypt(1, &nibble),
+                sks.trivial_encrypt(2, &nibble),
+            ]])
+            .collect();
+        let res = sks.tree_bootstrapping_batch(&inputs, &[nibble.clone(), nibble.clone()], 256, &f);
+        let expected = messages
+            .iter()
+            .chain(&[0x12])
+            .map(|&m| vec![f(m) / 16, f(m) % 16])
+            .collect::<Vec<_>>();
+        assert_eq!(
+            res.iter()
+                .map(|r| r.iter().map(|c| cks.decrypt(c)).collect::<Vec<_>>())
+                .collect::<Vec<_>>(),
+            expected
+        );
+    }
+
+    assert!(sks.try_mvb_batch(&[], &[nibble], &[]).is_err());
+}
+
+#[test]
+fn test_batch_nested_parallelism() {
+    use crate::odd::TEST_PARAMETERS;
+
+    let (cks, sks) = gen_keys(&TEST_PARAMETERS);
+    let nibble = Encoding::new_canonical(16, (0..16).collect(), 17);
+    let f = |x: u64| (x * 5 + 1) % 256;
+
+    // an operator called while the engine of the thread is taken runs on another engine
+    let ct = cks.encrypt_arithmetic(3, &nibble);
+    let res = OddEngine::with_thread_local_mut(|_| sks.apply_lut(&ct, &nibble, &|x| (x + 1) % 16));
+    assert_eq!(cks.decrypt(&res), 4);
+
+    // the workers of a batch wait for the parallel sections of their trees and mvbs, and steal the
+    // other inputs of the batch in the meantime
+    let pool = ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+    let messages = [0x00, 0x3a, 0x7f, 0xc4, 0xff];
+    let inputs: Vec<Vec<Ciphertext>> = messages
+        .iter()
+        .map(|m| {
+            vec![
+                cks.encrypt_arithmetic(m / 16, &nibble),
+                cks.encrypt_arithmetic(m % 16, &nibble),
+            ]
+        })
+        .collect();
+    let res = pool.install(|| {
+        sks.tree_bootstrapping_batch(&inputs, &[nibble.clone(), nibble.clone()], 256, &f)
+    });
+    let expected = messages
+        .iter()
+        .map(|&m| vec![f(m) / 16, f(m) % 16])
+        .collect::<Vec<_>>();
+    assert_eq!(
+        res.iter()
+            .map(|r| r.iter().map(|c| cks.decrypt(c)).collect::<Vec<_>>())
+            .collect::<Vec<_>>(),
+        expected
+    );
+
+    let inputs: Vec<Ciphertext> = messages
+        .iter()
+        .map(|m| cks.encrypt_arithmetic(m % 16, &nibble))
+        .collect();
+    let res = pool.install(|| {
+        let fis: Vec<Box<dyn Fn(u64) -> u64>> = vec![
+            Box::new(|x| x % 2),
+            Box::new(|x| x / 2),
+            Box::new(|x| (x + 3) % 16),
+        ];
+        sks.mvb_batch(
+            &inputs,
+            &[nibble.clone(), nibble.clone(), nibble.clone()],
+            &fis,
+        )
+    });
+    assert_eq!(
+        res.iter()
+            .map(|r| r.iter().map(|c| cks.decrypt(c)).collect::<Vec<_>>())
+            .collect::<Vec<_>>(),
+        messages.map(|m| vec![m % 16 % 2, m % 16 / 2, (m % 16 + 3) % 16])
+    );
+}
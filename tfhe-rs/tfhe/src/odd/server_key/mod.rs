@@ -7,119 +7,397 @@
 
 
 use itertools::Itertools;
+use rayon::prelude::*;
 
+use crate::conformance::ParameterSetConformant;
+use crate::core_crypto::fft_impl::fft64::crypto::bootstrap::BootstrapKeyConformanceParams;
+use crate::core_crypto::prelude::{
+    KeyswitchKeyConformanceParams, PBSOrder, PackingKeyswitchConformanceParams,
+};
 use crate::odd::prelude::*;
 use crate::odd::client_key::ClientKey;
-pub use crate::odd::engine::bootstrapping::ServerKey;
+pub use crate::odd::engine::bootstrapping::{CompressedServerKey, ServerKey};
 use crate::odd::engine::{
     OddEngine, WithThreadLocalEngine,
 };
 
+mod batch;
+pub use batch::set_batch_threads;
+
 
 impl ServerKey {
 
     ///Arithmetic only : application of LUT from Zo to Zo
+    ///
+    /// The output encoding does not have to be canonical: each value is then written as the smallest
+    /// element of its part (see [`Encoding::try_default_representatives`]).
     pub fn apply_lut(&self, input : &Ciphertext, encoding_out : &Encoding, f : &dyn Fn(u64) -> u64) -> Ciphertext{
-        OddEngine::with_thread_local_mut(|engine| engine.apply_lut(input, encoding_out, f, self))
+        self.try_apply_lut(input, encoding_out, f).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_apply_lut(&self, input : &Ciphertext, encoding_out : &Encoding, f : &dyn Fn(u64) -> u64) -> Result<Ciphertext, OddError>{
+        let representatives = encoding_out.try_default_representatives()?;
+        self.try_apply_lut_with_representatives(input, encoding_out, &representatives, f)
+    }
+
+    /// Same as [`Self::apply_lut`], the value i of Zo being written as `representatives[i]`, which
+    /// must belong to the part of i in `encoding_out`.
+    pub fn apply_lut_with_representatives(&self, input : &Ciphertext, encoding_out : &Encoding, representatives : &[u64], f : &dyn Fn(u64) -> u64) -> Ciphertext{
+        self.try_apply_lut_with_representatives(input, encoding_out, representatives, f).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_apply_lut_with_representatives(&self, input : &Ciphertext, encoding_out : &Encoding, representatives : &[u64], f : &dyn Fn(u64) -> u64) -> Result<Ciphertext, OddError>{
+        self.checked(OddEngine::with_thread_local_mut(|engine| engine.apply_lut(input, encoding_out, representatives, f, self)))
+    }
+
+    /// Same as [`Self::apply_lut`], the result replacing `ciphertext` without allocating a new one.
+    pub fn apply_lut_assign(&self, ciphertext : &mut Ciphertext, encoding_out : &Encoding, f : &dyn Fn(u64) -> u64){
+        self.try_apply_lut_assign(ciphertext, encoding_out, f).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_apply_lut_assign(&self, ciphertext : &mut Ciphertext, encoding_out : &Encoding, f : &dyn Fn(u64) -> u64) -> Result<(), OddError>{
+        let representatives = encoding_out.try_default_representatives()?;
+        self.try_apply_lut_with_representatives_assign(ciphertext, encoding_out, &representatives, f)
+    }
+
+    pub fn apply_lut_with_representatives_assign(&self, ciphertext : &mut Ciphertext, encoding_out : &Encoding, representatives : &[u64], f : &dyn Fn(u64) -> u64){
+        self.try_apply_lut_with_representatives_assign(ciphertext, encoding_out, representatives, f).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_apply_lut_with_representatives_assign(&self, ciphertext : &mut Ciphertext, encoding_out : &Encoding, representatives : &[u64], f : &dyn Fn(u64) -> u64) -> Result<(), OddError>{
+        let result = OddEngine::with_thread_local_mut(|engine| engine.apply_lut_assign(ciphertext, encoding_out, representatives, f, self));
+        self.checked_assign(ciphertext, result)
     }
     ///////////////////////////////////////////////////
     
 
-    pub fn mvb(&self, input : &Ciphertext, encodings_out : &Vec<Encoding>, fis : &Vec<Box<dyn Fn(u64) -> u64>>) -> Vec<Ciphertext>{
-        assert_eq!(encodings_out.len(), fis.len());
+    /// Evaluate each `fis[i]` on the input, the result being encrypted in `encodings_out[i]`.
+    ///
+    /// The output encodings can have different plaintext moduli: the blind rotation of the common
+    /// factor is performed once per distinct modulus. As for [`Self::apply_lut`], they do not have to
+    /// be canonical.
+    ///
+    /// The input must have an odd plaintext modulus, the outputs can have any one (including 2).
+    pub fn mvb(&self, input : &Ciphertext, encodings_out : &[Encoding], fis : &[Box<dyn Fn(u64) -> u64>]) -> Vec<Ciphertext>{
+        self.try_mvb(input, encodings_out, fis).unwrap_or_else(|err| panic!("{err}"))
+    }
 
-        match input{
-            Ciphertext::EncodingEncrypted(_, encoding) => {
-                let lut_fis : Vec<Vec<u64>> = fis.iter()
-                                                                        .map(|fi| (0..encoding.get_origin_modulus()).map(fi).collect())
-                                                                        .collect();
+    pub fn try_mvb(&self, input : &Ciphertext, encodings_out : &[Encoding], fis : &[Box<dyn Fn(u64) -> u64>]) -> Result<Vec<Ciphertext>, OddError>{
+        let representatives = encodings_out.iter().map(Encoding::try_default_representatives).collect::<Result<Vec<_>, _>>()?;
+        self.try_mvb_with_representatives(input, encodings_out, &representatives, fis)
+    }
 
-                OddEngine::with_thread_local_mut(|engine| engine.mvb(input, encodings_out, &lut_fis, self))
-            }
-            _ => panic!("No mvb with trivial ciphertexts")
+    /// Same as [`Self::mvb`], the value j of Zo being written as `representatives[i][j]` in the i-th
+    /// output.
+    pub fn mvb_with_representatives(&self, input : &Ciphertext, encodings_out : &[Encoding], representatives : &[Vec<u64>], fis : &[Box<dyn Fn(u64) -> u64>]) -> Vec<Ciphertext>{
+        self.try_mvb_with_representatives(input, encodings_out, representatives, fis).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_mvb_with_representatives(&self, input : &Ciphertext, encodings_out : &[Encoding], representatives : &[Vec<u64>], fis : &[Box<dyn Fn(u64) -> u64>]) -> Result<Vec<Ciphertext>, OddError>{
+        if encodings_out.len() != fis.len() {
+            return Err(OddError::LengthMismatch { expected: fis.len(), got: encodings_out.len() });
         }
+
+        let encoding = input.get_encoding();
+        let lut_fis : Vec<Vec<u64>> = fis.iter()
+                                                                .map(|fi| (0..encoding.get_origin_modulus()).map(fi).collect())
+                                                                .collect();
+
+        self.all_checked(OddEngine::with_thread_local_mut(|engine| engine.mvb(input, encodings_out, representatives, &lut_fis, self)))
+    }
+
+    /// Build the accumulator of `f` from `encoding_in` to `encoding_out` once, to apply it with
+    /// [`Self::apply_lookup_table`] without rebuilding it.
+    pub fn create_lookup_table(&self, encoding_in : &Encoding, encoding_out : &Encoding, f : &dyn Fn(u64) -> u64) -> OddLookupTable{
+        self.try_create_lookup_table(encoding_in, encoding_out, f).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_create_lookup_table(&self, encoding_in : &Encoding, encoding_out : &Encoding, f : &dyn Fn(u64) -> u64) -> Result<OddLookupTable, OddError>{
+        let representatives = encoding_out.try_default_representatives()?;
+        OddEngine::with_thread_local_mut(|engine| engine.create_lookup_table(encoding_in, encoding_out, &representatives, f, self))
+    }
+
+    /// Same as [`Self::apply_lut`] with a prebuilt table, the input being encoded with the input
+    /// encoding of the table.
+    pub fn apply_lookup_table(&self, input : &Ciphertext, table : &OddLookupTable) -> Ciphertext{
+        self.try_apply_lookup_table(input, table).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_apply_lookup_table(&self, input : &Ciphertext, table : &OddLookupTable) -> Result<Ciphertext, OddError>{
+        self.checked(OddEngine::with_thread_local_mut(|engine| engine.apply_lookup_table(input, table, self)))
+    }
+
+    pub fn apply_lookup_table_assign(&self, ciphertext : &mut Ciphertext, table : &OddLookupTable){
+        self.try_apply_lookup_table_assign(ciphertext, table).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_apply_lookup_table_assign(&self, ciphertext : &mut Ciphertext, table : &OddLookupTable) -> Result<(), OddError>{
+        let result = OddEngine::with_thread_local_mut(|engine| engine.apply_lookup_table_assign(ciphertext, table, self));
+        self.checked_assign(ciphertext, result)
+    }
+
+    /// Build the vi polynomials of a mvb from `encoding_in` once, to apply them with
+    /// [`Self::apply_mvb_lookup_table`] without rebuilding them.
+    pub fn create_mvb_lookup_table(&self, encoding_in : &Encoding, encodings_out : &[Encoding], fis : &[Box<dyn Fn(u64) -> u64>]) -> MvbLookupTable{
+        self.try_create_mvb_lookup_table(encoding_in, encodings_out, fis).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_create_mvb_lookup_table(&self, encoding_in : &Encoding, encodings_out : &[Encoding], fis : &[Box<dyn Fn(u64) -> u64>]) -> Result<MvbLookupTable, OddError>{
+        let representatives : Vec<Vec<u64>> = encodings_out.iter().map(Encoding::try_default_representatives).collect::<Result<_, _>>()?;
+        let lut_fis : Vec<Vec<u64>> = fis.iter()
+            .map(|fi| (0..encoding_in.get_origin_modulus()).map(fi).collect())
+            .collect();
+        OddEngine::with_thread_local_mut(|engine| engine.create_mvb_lookup_table(encoding_in, encodings_out, &representatives, &lut_fis, self))
+    }
+
+    /// Same as [`Self::mvb`] with a prebuilt table, the input being encoded with the input encoding
+    /// of the table.
+    pub fn apply_mvb_lookup_table(&self, input : &Ciphertext, table : &MvbLookupTable) -> Vec<Ciphertext>{
+        self.try_apply_mvb_lookup_table(input, table).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_apply_mvb_lookup_table(&self, input : &Ciphertext, table : &MvbLookupTable) -> Result<Vec<Ciphertext>, OddError>{
+        self.all_checked(OddEngine::with_thread_local_mut(|engine| engine.apply_mvb_lookup_table(input, table, self)))
+    }
+
+    /// Evaluate a gadget: the inputs, which must share the plaintext modulus of `enc_inter`, are
+    /// summed and the sum is bootstrapped, the part of i in `enc_inter` being mapped to i in
+    /// `enc_out`. The encodings of a gadget can be found with
+    /// [`search_gadget`](crate::odd::encoding_search::search_gadget).
+    pub fn exec_gadget(&self, inputs : &[Ciphertext], enc_inter : &Encoding, enc_out : &Encoding) -> Ciphertext{
+        self.try_exec_gadget(inputs, enc_inter, enc_out).unwrap_or_else(|err| panic!("{err}"))
     }
 
+    pub fn try_exec_gadget(&self, inputs : &[Ciphertext], enc_inter : &Encoding, enc_out : &Encoding) -> Result<Ciphertext, OddError>{
+        let enc_in = inputs.iter().map(|c| c.get_encoding().clone()).collect::<Vec<_>>();
+        self.checked(OddEngine::with_thread_local_mut(|engine| engine.exec_gadget_with_extraction(&enc_in, enc_inter, enc_out, inputs, self)))
+    }
+
+    /// Evaluate `f` on the number whose digits are encrypted by `inputs`, returning its digits in
+    /// the radix given by `encodings_out`.
+    ///
+    /// Both lists are ordered from the most to the least significant digit, the radix of each digit
+    /// being the origin modulus of its encoding: the inputs encode `x = sum_i x_i * prod_{j > i} o_j`
+    /// (so `t` must be the product of the input origin moduli), and the i-th output encrypts the
+    /// i-th digit of `f(x)` (reduced modulo the product of the output origin moduli) in
+    /// `encodings_out[i]`.
+    ///
+    /// The outputs can have any plaintext modulus, including the parity encoding, and do not have to
+    /// be canonical. The inputs other than the last one can have an even modulus only if they leave
+    /// the second half of Zp empty. The last input goes through a mvb, so, as for [`Self::mvb`], its
+    /// modulus must be odd ([`OddError::UnsupportedModulus`] otherwise).
     pub fn full_tree_bootstrapping(
         &self,
-        inputs: &Vec<Ciphertext>,
-        encodings_out: &Vec<Encoding>,
+        inputs: &[Ciphertext],
+        encodings_out: &[Encoding],
         t: u64,
         f: &dyn Fn(u64) -> u64,
-        client_key_debug: &ClientKey,
-        log : bool
     ) -> Vec<Ciphertext> {
+        self.try_full_tree_bootstrapping(inputs, encodings_out, t, f)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_full_tree_bootstrapping(
+        &self,
+        inputs: &[Ciphertext],
+        encodings_out: &[Encoding],
+        t: u64,
+        f: &dyn Fn(u64) -> u64,
+    ) -> Result<Vec<Ciphertext>, OddError> {
+        if inputs.is_empty() || encodings_out.is_empty() {
+            return Err(OddError::EmptyInput);
+        }
+
         let origin_submodulis: Vec<u64> = inputs
             .iter()
-            .map(|c| match c {
-                Ciphertext::EncodingEncrypted(_, encoding) => encoding.get_origin_modulus(),
-                Ciphertext::Trivial(_) => panic!("No tree bootstrapping with trivial ciphertexts (yet)"),
-            })
+            .map(|c| c.get_encoding().get_origin_modulus())
             .collect();
     
-        assert_eq!(origin_submodulis.iter().product::<u64>(), t);
-    
-        let o = origin_submodulis[0];
-        let lut_f0 = (0..t).map(|x| f(x) % o).collect_vec();
-        let lut_f1 = (0..t).map(|x: u64| (f(x) - f(x) % o) / o).collect_vec();
+        let product = origin_submodulis.iter().product::<u64>();
+        if product != t {
+            return Err(OddError::InvalidTreeModulus { expected: t, got: product });
+        }
 
+        // trivial inputs are folded into the table, which is then indexed by the encrypted inputs only
+        let (encrypted_inputs, indices) = fold_trivial_inputs(inputs)?;
 
-        let common_factor = OddEngine::with_thread_local_mut(|engine|{
-            engine.compute_common_factor(&inputs[1], &encodings_out[0], &self)
-        });
+        let lut_fis = digit_tables(encodings_out, f, &indices);
 
+        let representatives = encodings_out.iter().map(Encoding::try_default_representatives).collect::<Result<Vec<_>, _>>()?;
+        if encrypted_inputs.is_empty() {
+            return Ok(lut_fis
+                .iter()
+                .zip(encodings_out)
+                .zip(&representatives)
+                .map(|((lut_fi, encoding_out), reps)| Ciphertext::Trivial(reps[lut_fi[0] as usize], encoding_out.clone()))
+                .collect());
+        }
+        let t = indices.len() as u64;
 
-        let r0 = OddEngine::with_thread_local_mut(|engine| {
-            engine.simple_tree_bootstrapping(&common_factor.clone(), inputs, &encodings_out[0], t, lut_f0, &self, client_key_debug, log)
-        });
-    
-        let r1 = OddEngine::with_thread_local_mut(|engine| {
-            engine.simple_tree_bootstrapping(&common_factor.clone(), inputs, &encodings_out[1], t, lut_f1, &self, client_key_debug, false)
-        });
-    
-        vec![r1, r0]
+        if encrypted_inputs.len() == 1 {
+            return self.all_checked(OddEngine::with_thread_local_mut(|engine| {
+                engine.mvb(&encrypted_inputs[0], encodings_out, &representatives, &lut_fis, self)
+            }));
+        }
+
+        // the last input goes through a mvb, whose vi are only defined for an odd modulus
+        let modulus_0 = encrypted_inputs.last().unwrap().get_encoding().get_modulus();
+        if modulus_0 % 2 == 0 {
+            return Err(OddError::UnsupportedModulus { operation: "mvb", modulus: modulus_0 });
+        }
+
+        // the common factor depends on the plaintext modulus of the outputs: it is computed once
+        // for all the digits sharing the same one
+        let output_moduli = encodings_out.iter().map(|e| e.get_modulus()).unique().collect_vec();
+
+        let mut results: Vec<Option<Ciphertext>> = vec![None; encodings_out.len()];
+        for output_modulus in output_moduli {
+            let digits = (0..encodings_out.len())
+                .filter(|&i| encodings_out[i].get_modulus() == output_modulus)
+                .collect_vec();
+
+            let common_factor = OddEngine::with_thread_local_mut(|engine|{
+                engine.compute_common_factor(encrypted_inputs.last().unwrap(), &encodings_out[digits[0]], self)
+            })?;
+
+            // the trees of the digits are independent: each one runs on the engine of its own worker
+            let digit_results = digits
+                .par_iter()
+                .map(|&i| OddEngine::with_thread_local_mut(|engine| {
+                    engine.simple_tree_bootstrapping(&common_factor, &encrypted_inputs, &encodings_out[i], t, lut_fis[i].clone(), self)
+                }))
+                .collect::<Result<Vec<_>, _>>()?;
+            for (&i, ri) in digits.iter().zip(digit_results) {
+                results[i] = Some(ri);
+            }
+        }
+
+        self.all_checked(Ok(results.into_iter().map(Option::unwrap).collect()))
     }
     
      
     
     ///Encoding Switching : universal
     pub fn encoding_switching_lut(&self, input : &Ciphertext, encoding_out : &Encoding) -> Ciphertext{
-        OddEngine::with_thread_local_mut(|engine| engine.apply_lut(input, encoding_out, &|x|{x}, self))
+        self.try_encoding_switching_lut(input, encoding_out).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_encoding_switching_lut(&self, input : &Ciphertext, encoding_out : &Encoding) -> Result<Ciphertext, OddError>{
+        self.try_apply_lut(input, encoding_out, &|x|{x})
+    }
+
+    pub fn encoding_switching_lut_assign(&self, ciphertext : &mut Ciphertext, encoding_out : &Encoding){
+        self.try_encoding_switching_lut_assign(ciphertext, encoding_out).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_encoding_switching_lut_assign(&self, ciphertext : &mut Ciphertext, encoding_out : &Encoding) -> Result<(), OddError>{
+        self.try_apply_lut_assign(ciphertext, encoding_out, &|x|{x})
     }
 
     //transforme un encodage en un autre avec un external product par un coefficient donné
     pub fn encoding_switching_mul_constant(&self, input : &Ciphertext, coefficient : u64) -> Ciphertext{
-        OddEngine::with_thread_local_mut(|engine| engine.encoding_switching_mul_constant(input, coefficient, &self))
+        self.try_encoding_switching_mul_constant(input, coefficient).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_encoding_switching_mul_constant(&self, input : &Ciphertext, coefficient : u64) -> Result<Ciphertext, OddError>{
+        self.checked(OddEngine::with_thread_local_mut(|engine| engine.encoding_switching_mul_constant(input, coefficient, self)))
     }
 
     pub fn encoding_switching_sum_constant(&self, input : &Ciphertext, constant : u64, modulus : u64) -> Ciphertext{
-        OddEngine::with_thread_local_mut(|engine| engine.encoding_switching_sum_constant(input, constant, modulus,&self))
+        self.try_encoding_switching_sum_constant(input, constant, modulus).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_encoding_switching_sum_constant(&self, input : &Ciphertext, constant : u64, modulus : u64) -> Result<Ciphertext, OddError>{
+        self.checked(OddEngine::with_thread_local_mut(|engine| engine.encoding_switching_sum_constant(input, constant, modulus, self)))
     }
     ////////////////////////
 
 
     //simple sum : no check is performed so use it wisely
-    pub fn simple_sum(&self, input : &Vec<Ciphertext>) -> Ciphertext{
-        OddEngine::with_thread_local_mut(|engine| engine.simple_sum(input, &self))
+    pub fn simple_sum(&self, input : &[Ciphertext]) -> Ciphertext{
+        self.try_simple_sum(input).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_simple_sum(&self, input : &[Ciphertext]) -> Result<Ciphertext, OddError>{
+        self.checked(OddEngine::with_thread_local_mut(|engine| engine.simple_sum(input, self)))
     }
 
     pub fn simple_plaintext_sum(&self, input : &Ciphertext, constant : u64, modulus : u64) -> Ciphertext{
-        OddEngine::with_thread_local_mut(|engine| engine.simple_plaintext_sum(input, constant, modulus,&self))
+        self.try_simple_plaintext_sum(input, constant, modulus).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_simple_plaintext_sum(&self, input : &Ciphertext, constant : u64, modulus : u64) -> Result<Ciphertext, OddError>{
+        self.checked(OddEngine::with_thread_local_mut(|engine| engine.simple_plaintext_sum(input, constant, modulus, self)))
     }
 
     pub fn simple_mul_constant(&self, input : &Ciphertext, coeff : u64, modulus:u64) -> Ciphertext{
-        OddEngine::with_thread_local_mut(|engine| engine.simple_mul_constant(input, coeff, modulus,  &self))
+        self.try_simple_mul_constant(input, coeff, modulus).unwrap_or_else(|err| panic!("{err}"))
     }
 
+    pub fn try_simple_mul_constant(&self, input : &Ciphertext, coeff : u64, modulus:u64) -> Result<Ciphertext, OddError>{
+        self.checked(OddEngine::with_thread_local_mut(|engine| engine.simple_mul_constant(input, coeff, modulus, self)))
+    }
+
+
+    pub fn simple_negation(&self, input : &Ciphertext) -> Ciphertext{
+        self.try_simple_negation(input).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_simple_negation(&self, input : &Ciphertext) -> Result<Ciphertext, OddError>{
+        self.checked(OddEngine::with_thread_local_mut(|engine| engine.simple_negation(input)))
+    }
 
     //Same: all inputs should have the same encoding
-    pub fn linear_combination(&self, input : &Vec<Ciphertext>, coefficients : &Vec<u64>, modulus : u64) -> Ciphertext{
-        let buffer : Vec<Ciphertext>= input.iter().zip(coefficients).map(|(ct, coeff)| self.simple_mul_constant(ct, *coeff, modulus)).collect();
+    pub fn linear_combination(&self, input : &[Ciphertext], coefficients : &[u64], modulus : u64) -> Ciphertext{
+        self.try_linear_combination(input, coefficients, modulus).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_linear_combination(&self, input : &[Ciphertext], coefficients : &[u64], modulus : u64) -> Result<Ciphertext, OddError>{
+        if input.len() != coefficients.len() {
+            return Err(OddError::LengthMismatch { expected: input.len(), got: coefficients.len() });
+        }
 
-        OddEngine::with_thread_local_mut(|engine| engine.simple_sum(
+        let buffer : Vec<Ciphertext>= input.iter().zip(coefficients).map(|(ct, coeff)| self.try_simple_mul_constant(ct, *coeff, modulus)).collect::<Result<_, _>>()?;
+
+        self.checked(OddEngine::with_thread_local_mut(|engine| engine.simple_sum(
             &buffer,
             self)
-        )
+        ))
+    }
+
+
+    ///Noise tracking
+    ///
+    /// Choose what the operators do when the estimated noise of their result gets too large to be
+    /// bootstrapped safely (nothing by default).
+    pub fn set_noise_check(&mut self, noise_check : NoiseCheck){
+        self.noise_check = noise_check;
+    }
+
+    pub fn noise_check(&self) -> NoiseCheck{
+        self.noise_check
+    }
+
+    /// Base 2 logarithm of the estimated probability that the next bootstrapping of `ciphertext`
+    /// fails.
+    pub fn log2_failure_probability(&self, ciphertext : &Ciphertext) -> f64{
+        self.parameters.log2_failure_probability(ciphertext)
+    }
+
+    fn checked(&self, result : Result<Ciphertext, OddError>) -> Result<Ciphertext, OddError>{
+        let ciphertext = result?;
+        self.noise_check.check(&ciphertext, &self.parameters)?;
+        Ok(ciphertext)
+    }
+
+    //the ciphertext keeps the result of the operation even when its noise check fails
+    fn checked_assign(&self, ciphertext : &Ciphertext, result : Result<(), OddError>) -> Result<(), OddError>{
+        result?;
+        self.noise_check.check(ciphertext, &self.parameters)
+    }
+
+    fn all_checked(&self, result : Result<Vec<Ciphertext>, OddError>) -> Result<Vec<Ciphertext>, OddError>{
+        result?.into_iter().map(|ciphertext| self.checked(Ok(ciphertext))).collect()
     }
 }
 
@@ -129,13 +407,543 @@ impl ServerKey {
 
 
 
+/// Tables of the output digits of a tree bootstrapping: `result[i][j]` is the i-th digit of
+/// `f(indices[j])` in the radix given by the origin moduli of `encodings_out` (the last digit
+/// being the least significant one).
+pub(crate) fn digit_tables(encodings_out: &[Encoding], f: &dyn Fn(u64) -> u64, indices: &[u64]) -> Vec<Vec<u64>> {
+    let radices: Vec<u64> = encodings_out.iter().map(|e| e.get_origin_modulus()).collect();
+    let weights: Vec<u64> = (0..radices.len()).map(|i| radices[i + 1..].iter().product()).collect();
+    radices
+        .iter()
+        .zip(&weights)
+        .map(|(radix, weight)| indices.iter().map(|&x| f(x) / weight % radix).collect())
+        .collect()
+}
+
+/// Fold the trivial inputs of a tree bootstrapping into its table.
+///
+/// The table is indexed by `sum_i x_i * prod_{j > i} o_j` (the last input being the least
+/// significant digit). Return the encrypted inputs and, for each index of the table of these
+/// encrypted inputs only, the corresponding index in the original table.
+fn fold_trivial_inputs(inputs: &[Ciphertext]) -> Result<(Vec<Ciphertext>, Vec<u64>), OddError> {
+    let mut encrypted_inputs = vec![];
+    let mut indices = vec![0u64];
+    let mut weight = 1;
+    for input in inputs.iter().rev() {
+        let o = input.get_encoding().get_origin_modulus();
+        match input {
+            Ciphertext::Trivial(..) => {
+                let x = input.decrypt_trivial()?;
+                for index in &mut indices {
+                    *index += x * weight;
+                }
+            }
+            Ciphertext::EncodingEncrypted(..) => {
+                // this input is the most significant digit among the encrypted inputs seen so far
+                indices = (0..o)
+                    .flat_map(|x| indices.iter().map(move |index| index + x * weight))
+                    .collect();
+                encrypted_inputs.push(input.clone());
+            }
+        }
+        weight *= o;
+    }
+    encrypted_inputs.reverse();
+    Ok((encrypted_inputs, indices))
+}
+
+impl ParameterSetConformant for ServerKey {
+    type ParameterSet = CustomOddParameters;
+
+    fn is_conformant(&self, parameter_set: &CustomOddParameters) -> bool {
+        let Self {
+            bootstrapping_key,
+            key_switching_key,
+            lwe_packing_keyswitch_key,
+            pbs_order,
+            parameters,
+            ..
+        } = self;
+
+        let bsk_param: BootstrapKeyConformanceParams = parameter_set.into();
+        let ksk_param: KeyswitchKeyConformanceParams = parameter_set.into();
+        let packing_ksk_param: PackingKeyswitchConformanceParams = parameter_set.into();
+
+        let pbs_order_ok = matches!(
+            (*pbs_order, parameter_set.encryption_key_choice),
+            (PBSOrder::KeyswitchBootstrap, EncryptionKeyChoice::Big)
+                | (PBSOrder::BootstrapKeyswitch, EncryptionKeyChoice::Small)
+        );
+
+        bootstrapping_key.is_conformant(&bsk_param)
+            && key_switching_key.is_conformant(&ksk_param)
+            && lwe_packing_keyswitch_key.is_conformant(&packing_ksk_param)
+            && pbs_order_ok
+            && parameters == parameter_set
+    }
+}
+
+impl ParameterSetConformant for CompressedServerKey {
+    type ParameterSet = CustomOddParameters;
+
+    fn is_conformant(&self, parameter_set: &CustomOddParameters) -> bool {
+        let Self {
+            bootstrapping_key,
+            key_switching_key,
+            lwe_packing_keyswitch_key,
+            pbs_order,
+            parameters,
+        } = self;
+
+        let bsk_param: BootstrapKeyConformanceParams = parameter_set.into();
+        let ksk_param: KeyswitchKeyConformanceParams = parameter_set.into();
+        let packing_ksk_param: PackingKeyswitchConformanceParams = parameter_set.into();
+
+        let pbs_order_ok = matches!(
+            (*pbs_order, parameter_set.encryption_key_choice),
+            (PBSOrder::KeyswitchBootstrap, EncryptionKeyChoice::Big)
+                | (PBSOrder::BootstrapKeyswitch, EncryptionKeyChoice::Small)
+        );
+
+        bootstrapping_key.is_conformant(&bsk_param)
+            && key_switching_key.is_conformant(&ksk_param)
+            && lwe_packing_keyswitch_key.is_conformant(&packing_ksk_param)
+            && pbs_order_ok
+            && parameters == parameter_set
+    }
+}
+
+impl CompressedServerKey {
+    pub fn new(cks: &ClientKey) -> Self {
+        OddEngine::with_thread_local_mut(|engine| engine.create_compressed_server_key(cks))
+    }
+}
+
 impl ServerKey {
     pub fn new(cks: &ClientKey) -> Self {
         OddEngine::with_thread_local_mut(|engine| engine.create_server_key(cks))
     }
 
-    pub fn trivial_encrypt(&self, message: u64) -> Ciphertext {
-        Ciphertext::Trivial(message)
+    /// Create a trivial (noiseless, not encrypted) ciphertext of an element of Zo under a
+    /// canonical encoding, usable as an operand of every homomorphic operation.
+    pub fn trivial_encrypt(&self, message: u64, encoding: &Encoding) -> Ciphertext {
+        self.try_trivial_encrypt(message, encoding)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    pub fn try_trivial_encrypt(&self, message: u64, encoding: &Encoding) -> Result<Ciphertext, OddError> {
+        OddEngine::with_thread_local_mut(|engine| engine.trivial_encrypt(message, encoding))
+    }
+}
+
+
+
+#[test]
+fn test_serialization_keys_and_ciphertexts() {
+    use crate::odd::TEST_PARAMETERS;
+
+    let (cks, sks) = gen_keys(&TEST_PARAMETERS);
+    let encoding = Encoding::new_canonical(4, vec![0, 1, 2, 3], 9);
+    let ct = cks.encrypt_arithmetic(3, &encoding);
+
+    let cks: ClientKey = bincode::deserialize(&bincode::serialize(&cks).unwrap()).unwrap();
+    let sks: ServerKey = bincode::deserialize(&bincode::serialize(&sks).unwrap()).unwrap();
+    let ct: Ciphertext = bincode::deserialize(&bincode::serialize(&ct).unwrap()).unwrap();
+
+    let encoding_out = Encoding::new_canonical(4, vec![0, 1, 2, 3], 9);
+    let result = sks.apply_lut(&ct, &encoding_out, &|x| (x + 1) % 4);
+    let result: Ciphertext = bincode::deserialize(&bincode::serialize(&result).unwrap()).unwrap();
+    assert_eq!(cks.decrypt(&result), 0);
+    match result {
+        Ciphertext::EncodingEncrypted(_, enc, _) => assert_eq!(enc, encoding_out),
+        Ciphertext::Trivial(..) => panic!(),
+    }
+}
+
+
+#[test]
+fn test_compressed_server_key() {
+    use crate::odd::TEST_PARAMETERS;
+
+    let cks = ClientKey::new(&TEST_PARAMETERS);
+    let compressed_sks = CompressedServerKey::new(&cks);
+    assert!(compressed_sks.is_conformant(&TEST_PARAMETERS));
+
+    let compressed_size = bincode::serialized_size(&compressed_sks).unwrap();
+    let sks = compressed_sks.decompress();
+    assert!(sks.is_conformant(&TEST_PARAMETERS));
+    assert!(compressed_size < bincode::serialized_size(&sks).unwrap());
+
+    let encoding = Encoding::new_canonical(4, vec![0, 1, 2, 3], 9);
+    for msg in 0..4 {
+        let ct = cks.encrypt_arithmetic(msg, &encoding);
+        let res = sks.apply_lut(&ct, &encoding, &|x| (x + 3) % 4);
+        assert_eq!(cks.decrypt(&res), (msg + 3) % 4);
+    }
+
+    // the packing key switching key is used by the tree bootstrapping
+    let encoding = Encoding::new_canonical(16, (0..16).collect(), 17);
+    let inputs = vec![cks.encrypt_arithmetic(5, &encoding), cks.encrypt_arithmetic(12, &encoding)];
+    let res = sks.full_tree_bootstrapping(&inputs, &vec![encoding; 2], 256, &|x| (x * 7 + 3) % 256);
+    let expected = (0x5c * 7 + 3) % 256;
+    assert_eq!(cks.decrypt(&res[0]), expected / 16);
+    assert_eq!(cks.decrypt(&res[1]), expected % 16);
+}
+
+
+#[test]
+fn test_try_apis_return_errors() {
+    use crate::odd::TEST_PARAMETERS;
+
+    let (cks, sks) = gen_keys(&TEST_PARAMETERS);
+    let encoding = Encoding::new_canonical(4, vec![0, 1, 2, 3], 9);
+
+    assert_eq!(
+        cks.try_encrypt_arithmetic(4, &encoding).unwrap_err(),
+        OddError::MessageOutOfRange { message: 4, origin_modulus: 4 }
+    );
+
+    let ct = cks.encrypt_arithmetic(1, &encoding);
+    let non_canonical = Encoding::new(4, [[0].into(), [1, 5].into(), [2].into(), [3].into()].into(), 9);
+    assert_eq!(
+        sks.try_apply_lut_with_representatives(&ct, &non_canonical, &[0, 2, 2, 3], &|x| x).unwrap_err(),
+        OddError::InvalidRepresentative { message: 1 }
+    );
+    assert_eq!(ct.decrypt_trivial(), Err(OddError::NotTrivialCiphertext));
+    assert_eq!(sks.try_simple_sum(&[]).unwrap_err(), OddError::EmptyInput);
+    assert_eq!(
+        sks.try_full_tree_bootstrapping(&[], &vec![encoding.clone(); 2], 1, &|x| x).unwrap_err(),
+        OddError::EmptyInput
+    );
+    assert_eq!(
+        sks.try_full_tree_bootstrapping(&[ct.clone(), ct.clone()], &[], 16, &|x| x)
+            .unwrap_err(),
+        OddError::EmptyInput
+    );
+    assert_eq!(
+        sks.try_full_tree_bootstrapping(&[ct.clone(), ct.clone()], &vec![encoding.clone(); 2], 15, &|x| x)
+            .unwrap_err(),
+        OddError::InvalidTreeModulus { expected: 15, got: 16 }
+    );
+
+    // well-formed inputs go through
+    let res = sks.try_apply_lut(&ct, &encoding, &|x| (x + 1) % 4).unwrap();
+    assert_eq!(cks.try_decrypt(&res), Ok(2));
+}
+
+
+#[test]
+fn test_trivial_ciphertexts() {
+    use crate::odd::TEST_PARAMETERS;
+
+    let (cks, sks) = gen_keys(&TEST_PARAMETERS);
+    let encoding = Encoding::new_canonical(4, vec![0, 1, 2, 3], 9);
+    let encoding_out = Encoding::new_canonical(4, vec![0, 2, 4, 6], 9);
+
+    let ct = cks.encrypt_arithmetic(1, &encoding);
+    let trivial = sks.trivial_encrypt(2, &encoding);
+    assert_eq!(cks.decrypt(&trivial), 2);
+
+    // mixed and fully trivial sums
+    let sum = sks.simple_sum(&[ct.clone(), trivial.clone()]);
+    assert!(!sum.is_trivial());
+    assert_eq!(cks.decrypt(&sum), 3);
+    let one = sks.trivial_encrypt(1, &encoding);
+    let sum = sks.simple_sum(&[one.clone(), trivial.clone()]);
+    assert_eq!(sum.decrypt_trivial(), Ok(3));
+    assert_eq!(sks.simple_plaintext_sum(&trivial, 1, 9).decrypt_trivial(), Ok(3));
+    assert_eq!(sks.simple_mul_constant(&one, 3, 9).decrypt_trivial(), Ok(3));
+    // the negation keeps the noise of the input: 3 - 1 = 2 and 3 - 2 = 1
+    let opposite = sks.simple_negation(&ct);
+    assert_eq!(opposite.noise_variance(), ct.noise_variance());
+    assert_eq!(cks.decrypt(&sks.simple_plaintext_sum(&opposite, 3, 9)), 2);
+    let opposite = sks.simple_negation(&trivial);
+    assert_eq!(sks.simple_plaintext_sum(&opposite, 3, 9).decrypt_trivial(), Ok(1));
+
+    // constant folding of the LUTs
+    let res = sks.apply_lut(&trivial, &encoding_out, &|x| (x + 1) % 4);
+    assert_eq!(res.decrypt_trivial(), Ok(3));
+    assert_eq!(res.get_encoding(), &encoding_out);
+    let fis: Vec<Box<dyn Fn(u64) -> u64>> = vec![Box::new(|x| x % 2), Box::new(|x| x / 2)];
+    let res = sks.mvb(&trivial, &vec![encoding_out; 2], &fis);
+    assert_eq!(res.iter().map(|c| c.decrypt_trivial().unwrap()).collect::<Vec<_>>(), vec![0, 1]);
+
+    // tree bootstrapping with one or two public inputs
+    let encoding = Encoding::new_canonical(16, (0..16).collect(), 17);
+    let f = |x: u64| (x * 7 + 3) % 256;
+    let expected = f(0x5c);
+    let encrypted = [cks.encrypt_arithmetic(5, &encoding), cks.encrypt_arithmetic(12, &encoding)];
+    let public = vec![sks.trivial_encrypt(5, &encoding), sks.trivial_encrypt(12, &encoding)];
+    for inputs in [
+        vec![public[0].clone(), encrypted[1].clone()],
+        vec![encrypted[0].clone(), public[1].clone()],
+        public.clone(),
+    ] {
+        let res = sks.full_tree_bootstrapping(&inputs, &vec![encoding.clone(); 2], 256, &f);
+        assert_eq!(cks.decrypt(&res[0]), expected / 16);
+        assert_eq!(cks.decrypt(&res[1]), expected % 16);
+    }
+}
+
+
+#[test]
+fn test_tree_bootstrapping_three_inputs() {
+    use crate::odd::TEST_PARAMETERS;
+
+    let (cks, sks) = gen_keys(&TEST_PARAMETERS);
+    let f = |x: u64| (x * 37 + 11) % 256;
+
+    // a 12-bit LUT evaluated on three nibbles
+    let nibble = Encoding::new_canonical(16, (0..16).collect(), 17);
+    for x in [0x3a7, 0xf05] {
+        let inputs = (0..3).rev().map(|i| cks.encrypt_arithmetic((x >> (4 * i)) % 16, &nibble)).collect::<Vec<_>>();
+        let res = sks.full_tree_bootstrapping(&inputs, &vec![nibble.clone(); 2], 4096, &f);
+        assert_eq!(cks.decrypt(&res[0]) * 16 + cks.decrypt(&res[1]), f(x));
+    }
+
+    // heterogeneous origin moduli (and plaintext moduli) of the inputs
+    let encodings_in = [
+        nibble.clone(),
+        Encoding::new_canonical(2, vec![0, 1], 5),
+        Encoding::new_canonical(3, vec![0, 2, 4], 7),
+    ];
+    for (x_0, x_1, x_2) in [(11, 1, 2), (4, 0, 1)] {
+        let inputs = vec![
+            cks.encrypt_arithmetic(x_0, &encodings_in[0]),
+            cks.encrypt_arithmetic(x_1, &encodings_in[1]),
+            cks.encrypt_arithmetic(x_2, &encodings_in[2]),
+        ];
+        let x = x_2 + 3 * (x_1 + 2 * x_0);
+        let res = sks.full_tree_bootstrapping(&inputs, &vec![nibble.clone(); 2], 96, &f);
+        assert_eq!(cks.decrypt(&res[0]) * 16 + cks.decrypt(&res[1]), f(x));
+
+        // with a public middle digit
+        let inputs = vec![inputs[0].clone(), sks.trivial_encrypt(x_1, &encodings_in[1]), inputs[2].clone()];
+        let res = sks.full_tree_bootstrapping(&inputs, &vec![nibble.clone(); 2], 96, &f);
+        assert_eq!(cks.decrypt(&res[0]) * 16 + cks.decrypt(&res[1]), f(x));
+    }
+}
+
+
+#[test]
+fn test_tree_bootstrapping_output_digits() {
+    use crate::odd::TEST_PARAMETERS;
+
+    let (cks, sks) = gen_keys(&TEST_PARAMETERS);
+    let nibble = Encoding::new_canonical(16, (0..16).collect(), 17);
+
+    // 8-bit to 12-bit map
+    let f = |x: u64| (x * x * 7 + 5) % 4096;
+    let x = 0xb6;
+    let inputs = vec![cks.encrypt_arithmetic(x / 16, &nibble), cks.encrypt_arithmetic(x % 16, &nibble)];
+    let res = sks.full_tree_bootstrapping(&inputs, &vec![nibble.clone(); 3], 256, &f);
+    let digits = res.iter().map(|c| cks.decrypt(c)).collect_vec();
+    assert_eq!(digits, vec![f(x) / 256, f(x) / 16 % 16, f(x) % 16]);
+
+    // mixed radix outputs, with different plaintext moduli
+    let encodings_out = vec![
+        nibble.clone(),
+        Encoding::new_canonical(5, (0..5).collect(), 17),
+        Encoding::new_canonical(3, vec![0, 2, 4], 7),
+    ];
+    let f = |x: u64| x * 3 + 1;
+    let res = sks.full_tree_bootstrapping(&inputs, &encodings_out, 256, &f);
+    let digits = res.iter().map(|c| cks.decrypt(c)).collect_vec();
+    let y = f(x) % 240;
+    assert_eq!(digits, vec![y / 15, y / 3 % 5, y % 3]);
+    assert!(res.iter().zip(&encodings_out).all(|(c, e)| c.get_encoding() == e));
+
+    // a single digit
+    let res = sks.full_tree_bootstrapping(&inputs, &[nibble], 256, &|x| x / 16);
+    assert_eq!(cks.decrypt(&res[0]), x / 16);
+}
+
+
+#[test]
+fn test_tree_bootstrapping_both_pbs_orders() {
+    use crate::odd::TEST_PARAMETERS;
+
+    let small_parameters = CustomOddParameters {
+        encryption_key_choice: EncryptionKeyChoice::Small,
+        ..TEST_PARAMETERS
+    };
+    let nibble = Encoding::new_canonical(16, (0..16).collect(), 17);
+    let f = |x: u64| (x * 13 + 7) % 256;
+    let x = 0x9d;
+
+    let results = [TEST_PARAMETERS, small_parameters].map(|parameters| {
+        let (cks, sks) = gen_keys(&parameters);
+        let inputs = vec![cks.encrypt_arithmetic(x / 16, &nibble), cks.encrypt_arithmetic(x % 16, &nibble)];
+        let res = sks.full_tree_bootstrapping(&inputs, &vec![nibble.clone(); 2], 256, &f);
+
+        // the outputs are under the encryption key and can be fed to another operator
+        let lut = sks.apply_lut(&res[1], &nibble, &|y| 15 - y);
+        let fis: Vec<Box<dyn Fn(u64) -> u64>> = vec![Box::new(|y| y / 2), Box::new(|y| y % 2 * 15)];
+        let mvb = sks.mvb(&res[0], &vec![nibble.clone(); 2], &fis);
+        [&res[0], &res[1], &lut, &mvb[0], &mvb[1]].map(|c| cks.decrypt(c))
+    });
+    let y = f(x);
+    assert_eq!(results[0], [y / 16, y % 16, 15 - y % 16, y / 32, y / 16 % 2 * 15]);
+    assert_eq!(results[0], results[1]);
+}
+
+
+
+#[test]
+fn test_even_output_moduli() {
+    use crate::odd::TEST_PARAMETERS;
+
+    let (cks, sks) = gen_keys(&TEST_PARAMETERS);
+    let nibble = Encoding::new_canonical(16, (0..16).collect(), 17);
+    let quarter = Encoding::new_canonical(4, (0..4).collect(), 8);
+    let f = |x: u64| (x * 13 + 5) % 256;
+    let x = 0x9d;
+    let inputs = vec![cks.encrypt_arithmetic(x / 16, &nibble), cks.encrypt_arithmetic(x % 16, &nibble)];
+
+    // tree bootstrapping straight to Boolean encodings
+    let bits = sks.full_tree_bootstrapping(&inputs, &vec![Encoding::parity_encoding(); 8], 256, &f);
+    let bits = bits.iter().map(|c| cks.decrypt(c)).collect_vec();
+    assert_eq!(bits, (0..8).rev().map(|i| (f(x) >> i) % 2).collect_vec());
+    let res = sks.full_tree_bootstrapping(&inputs, &vec![quarter.clone(); 4], 256, &f);
+    let digits = res.iter().map(|c| cks.decrypt(c)).collect_vec();
+    assert_eq!(digits, (0..4).rev().map(|i| (f(x) >> (2 * i)) % 4).collect_vec());
+
+    // mvb to Boolean encodings, and to an even modulus other than 2
+    let fis: Vec<Box<dyn Fn(u64) -> u64>> = vec![Box::new(|y| y % 2), Box::new(|y| y / 8)];
+    for encoding_out in [Encoding::parity_encoding(), Encoding::new_canonical_binary(1, 4), quarter.clone()] {
+        let res = sks.mvb(&inputs[0], &vec![encoding_out; 2], &fis);
+        assert_eq!(res.iter().map(|c| cks.decrypt(c)).collect_vec(), vec![x / 16 % 2, x / 128]);
+    }
+
+    // even selector, with a padding bit
+    let padded = Encoding::new_canonical(2, vec![0, 1], 4);
+    let inputs = vec![cks.encrypt_arithmetic(1, &padded), cks.encrypt_arithmetic(x % 16, &nibble)];
+    let res = sks.full_tree_bootstrapping(&inputs, &vec![nibble.clone(); 2], 32, &f);
+    let y = f(16 + x % 16);
+    assert_eq!(cks.decrypt(&res[0]) * 16 + cks.decrypt(&res[1]), y);
+
+    let inputs = vec![cks.encrypt_arithmetic(1, &Encoding::parity_encoding()), inputs[1].clone()];
+    assert_eq!(
+        sks.try_full_tree_bootstrapping(&inputs, &vec![nibble.clone(); 2], 32, &f).unwrap_err(),
+        OddError::MissingPadding
+    );
+
+    // the last input cannot have an even modulus
+    let inputs = vec![cks.encrypt_arithmetic(x / 16, &nibble), cks.encrypt_arithmetic(x % 4, &quarter)];
+    assert_eq!(
+        sks.try_full_tree_bootstrapping(&inputs, &vec![nibble; 2], 64, &f).unwrap_err(),
+        OddError::UnsupportedModulus { operation: "mvb", modulus: 8 }
+    );
+}
+
+
+#[test]
+fn test_mvb_heterogeneous_outputs() {
+    use crate::odd::TEST_PARAMETERS;
+
+    let (cks, sks) = gen_keys(&TEST_PARAMETERS);
+    let nibble = Encoding::new_canonical(16, (0..16).collect(), 17);
+
+    // a nibble and its four bits from the same input
+    let mut encodings_out = vec![nibble.clone()];
+    encodings_out.extend(vec![Encoding::parity_encoding(); 4]);
+    let mut fis: Vec<Box<dyn Fn(u64) -> u64>> = vec![Box::new(|x| (x + 5) % 16)];
+    fis.extend((0..4).rev().map(|i| Box::new(move |x: u64| (x >> i) % 2) as Box<dyn Fn(u64) -> u64>));
+    for x in [0b1011, 0b0110] {
+        let res = sks.mvb(&cks.encrypt_arithmetic(x, &nibble), &encodings_out, &fis);
+        let mut expected = vec![(x + 5) % 16];
+        expected.extend((0..4).rev().map(|i| (x >> i) % 2));
+        assert_eq!(res.iter().map(|c| cks.decrypt(c)).collect_vec(), expected);
+        assert!(res.iter().zip(&encodings_out).all(|(c, e)| c.get_encoding() == e));
+    }
+
+    // the inputs are validated before any bootstrapping
+    let input = cks.encrypt_arithmetic(3, &nibble);
+    let non_canonical = Encoding::new(2, vec![[0, 2].into(), [1].into()], 5);
+    let parity: Vec<Box<dyn Fn(u64) -> u64>> = vec![Box::new(|x| x % 2)];
+    assert_eq!(
+        sks.try_mvb_with_representatives(&input, &[non_canonical], &[vec![0, 2]], &parity).unwrap_err(),
+        OddError::InvalidRepresentative { message: 1 }
+    );
+    let too_large: Vec<Box<dyn Fn(u64) -> u64>> = vec![Box::new(|x| x)];
+    assert_eq!(
+        sks.try_mvb(&input, &[Encoding::parity_encoding()], &too_large).unwrap_err(),
+        OddError::MessageOutOfRange { message: 2, origin_modulus: 2 }
+    );
+    let bit = cks.encrypt_arithmetic(1, &Encoding::parity_encoding());
+    assert_eq!(
+        sks.try_mvb(&bit, &[nibble], &parity).unwrap_err(),
+        OddError::UnsupportedModulus { operation: "mvb", modulus: 2 }
+    );
+}
+
+
+#[test]
+fn test_non_canonical_outputs() {
+    use crate::odd::TEST_PARAMETERS;
+
+    let (cks, sks) = gen_keys(&TEST_PARAMETERS);
+    let encoding = Encoding::new_canonical(4, vec![0, 1, 2, 3], 9);
+    let non_canonical = Encoding::new(4, [[0].into(), [1, 5].into(), [2].into(), [3].into()].into(), 9);
+    let ct = cks.encrypt_arithmetic(1, &encoding);
+
+    // 1 is written as 1 by default, and as 5 when asked: only the latter is stable by doubling
+    let default = sks.apply_lut(&ct, &non_canonical, &|x| x);
+    assert_eq!(cks.decrypt(&default), 1);
+    assert_eq!(cks.decrypt(&sks.simple_sum(&[default.clone(), default])), 2);
+    let chosen = sks.apply_lut_with_representatives(&ct, &non_canonical, &[0, 5, 2, 3], &|x| x);
+    assert_eq!(cks.decrypt(&chosen), 1);
+    assert_eq!(cks.decrypt(&sks.simple_sum(&[chosen.clone(), chosen])), 1);
+
+    // per-output representatives in a mvb, and on trivial inputs
+    let fis: Vec<Box<dyn Fn(u64) -> u64>> = vec![Box::new(|x| x), Box::new(|x| x)];
+    let representatives = vec![vec![0, 5, 2, 3], vec![0, 1, 2, 3]];
+    for input in [ct, sks.trivial_encrypt(1, &encoding)] {
+        let res = sks.mvb_with_representatives(&input, &vec![non_canonical.clone(); 2], &representatives, &fis);
+        let doubled = res.iter().map(|c| cks.decrypt(&sks.simple_sum(&[c.clone(), c.clone()]))).collect_vec();
+        assert_eq!(doubled, vec![1, 2]);
     }
 }
 
+
+#[test]
+fn test_assign_operators() {
+    use crate::odd::TEST_PARAMETERS;
+
+    let small_parameters = CustomOddParameters {
+        encryption_key_choice: EncryptionKeyChoice::Small,
+        ..TEST_PARAMETERS
+    };
+    let nibble = Encoding::new_canonical(16, (0..16).collect(), 17);
+    let f = |x: u64| (x * 5 + 3) % 16;
+    let keys = [gen_keys(&TEST_PARAMETERS), gen_keys(&small_parameters)];
+
+    // the two keys are used alternately on the same thread, which shares its buffers between them
+    for round in 0..2 {
+        for (cks, sks) in &keys {
+            let x = 7 + round;
+            let mut ct = cks.encrypt_arithmetic(x, &nibble);
+            let expected = sks.apply_lut(&ct, &nibble, &f);
+            sks.apply_lut_assign(&mut ct, &nibble, &f);
+            assert_eq!(cks.decrypt(&ct), cks.decrypt(&expected));
+            assert_eq!(ct.get_encoding(), expected.get_encoding());
+            assert_eq!(ct.noise_variance(), expected.noise_variance());
+
+            let table = sks.create_lookup_table(&nibble, &nibble, &f);
+            sks.apply_lookup_table_assign(&mut ct, &table);
+            assert_eq!(cks.decrypt(&ct), f(f(x)));
+
+            sks.encoding_switching_lut_assign(&mut ct, &Encoding::new_canonical(16, (0..16).map(|i| 2 * i).collect(), 33));
+            assert_eq!(cks.decrypt(&ct), f(f(x)));
+        }
+    }
+
+    // public inputs stay public, and errors leave the ciphertext untouched
+    let (_, sks) = &keys[0];
+    let mut trivial = sks.trivial_encrypt(2, &nibble);
+    sks.apply_lut_assign(&mut trivial, &nibble, &f);
+    assert_eq!(trivial.decrypt_trivial(), Ok(13));
+    let table = sks.create_lookup_table(&Encoding::new_canonical(16, (1..17).collect(), 17), &nibble, &f);
+    assert_eq!(sks.try_apply_lookup_table_assign(&mut trivial, &table), Err(OddError::InvalidEncoding));
+    assert_eq!(trivial.decrypt_trivial(), Ok(13));
+}
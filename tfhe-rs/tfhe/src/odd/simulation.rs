@@ -0,0 +1,362 @@
+//! Simulation of the homomorphic operators on clear values.
+//!
+//! The [`SimulatedServerKey`] computes on trivial ciphertexts, which carry the element of Zp and
+//! the [`Encoding`] exactly, so that they can be read with [`Ciphertext::decrypt_trivial`] or
+//! [`ClientKey::decrypt`]. Unlike the constant folding of the [`ServerKey`], a simulated
+//! bootstrapping reads the accumulator as a blind rotation would: an element of Zp outside of
+//! every part selects the empty window (0), and the second half of the torus reads the opposite of
+//! the first one (negacyclicity).
+
+use crate::odd::backend::OddBackend;
+use crate::odd::engine::bootstrapping::Memory;
+use crate::odd::engine::{check_lut, torus_to_zp, zp_to_torus};
+use crate::odd::prelude::*;
+use crate::odd::server_key::digit_tables;
+
+/// A server key computing on clear values, used to validate circuits and encodings without
+/// running any bootstrapping.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SimulatedServerKey;
+
+/// Return the element of Zp and the encoding carried by a simulated ciphertext
+fn clear_value(ciphertext: &Ciphertext) -> Result<(u64, &Encoding), OddError> {
+    match ciphertext {
+        Ciphertext::Trivial(value, encoding) => Ok((*value, encoding)),
+        Ciphertext::EncodingEncrypted(..) => Err(OddError::NotTrivialCiphertext),
+    }
+}
+
+/// Value read by a blind rotation of `x` (an element of Zp) on an accumulator made of `windows`
+/// (elements of Z_{p_out}), the first window being centered on 0.
+fn blind_rotation(x: u64, p: u64, windows: &[u64], p_out: u64) -> u64 {
+    let l = windows.len() as u64;
+    // position of x on the 2l windows of the negacyclic rotation
+    let position = ((2 * l * x + p / 2) / p % (2 * l)) as usize;
+    position
+        .checked_sub(windows.len())
+        .map_or_else(|| windows[position], |negated| (p_out - windows[negated]) % p_out)
+}
+
+/// Windows of the accumulator of a bootstrapping from `enc_inter` (the input encoding on which the
+/// function has been applied) to `enc_out`, as filled by the real bootstrapping.
+fn accumulator_windows(enc_inter: &Encoding, enc_out: &Encoding, representatives: &[u64]) -> Result<Vec<u64>, OddError> {
+    let p = enc_inter.get_modulus();
+    if p % 2 == 1 {
+        Ok(Memory::create_accumulator(enc_inter, enc_out, representatives))
+    } else if p != 2 {
+        Ok(Memory::create_accumulator_wopbs(enc_inter, representatives))
+    } else {
+        // the two halves of the torus should hold opposite values
+        let (new_false, new_true) = (representatives[0], representatives[1]);
+        let p_out = enc_out.get_modulus();
+        if new_false != (p_out - new_true) % p_out {
+            return Err(OddError::InvalidEncoding);
+        }
+        let new_0 = if enc_inter.is_partition_containing(1, 0) { new_true } else { new_false };
+        Ok(vec![new_0])
+    }
+}
+
+/// Windows of the accumulator packed by a tree bootstrapping, `values[i]` being selected by the
+/// element i of Zo
+fn packed_windows(values: &[u64], encoding_selector: &Encoding, p_out: u64) -> Vec<u64> {
+    let p = encoding_selector.get_modulus();
+    let selected = |zp_elem: u64| {
+        encoding_selector
+            .inverse_encoding(zp_elem)
+            .and_then(|i| values.get(i as usize))
+            .copied()
+            .unwrap_or(0)
+    };
+    if p % 2 == 1 {
+        (0..p)
+            .map(|k| {
+                if k % 2 == 0 {
+                    selected(k / 2)
+                } else {
+                    (p_out - selected(p.div_ceil(2) + (k - 1) / 2)) % p_out
+                }
+            })
+            .collect()
+    } else {
+        (0..p / 2).map(selected).collect()
+    }
+}
+
+impl SimulatedServerKey {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Simulate the bootstrapping of `x` (encoded by `encoding_in`) through the table `lut_fi`
+    fn simulate_bootstrapping(
+        x: u64,
+        encoding_in: &Encoding,
+        encoding_out: &Encoding,
+        representatives: &[u64],
+        lut_fi: &[u64],
+    ) -> Result<u64, OddError> {
+        check_lut(encoding_in, encoding_out, representatives, lut_fi)?;
+        let enc_inter = encoding_in.try_apply_lut_to_encoding(&|i| lut_fi[i as usize])?;
+        let windows = accumulator_windows(&enc_inter, encoding_out, representatives)?;
+        Ok(blind_rotation(x, encoding_in.get_modulus(), &windows, encoding_out.get_modulus()))
+    }
+
+    /// Simulate a tree bootstrapping of a single output digit, the table being indexed by all the
+    /// inputs (the last one being the least significant)
+    fn simulate_tree_bootstrapping(
+        inputs: &[Ciphertext],
+        encoding_out: &Encoding,
+        representatives: &[u64],
+        lut_fi: &[u64],
+    ) -> Result<u64, OddError> {
+        let (c_0, selectors) = inputs.split_last().ok_or(OddError::EmptyInput)?;
+        if !selectors.iter().all(|c| c.get_encoding().has_padding()) {
+            return Err(OddError::MissingPadding);
+        }
+        let (x_0, encoding_in_0) = clear_value(c_0)?;
+        if encoding_in_0.get_modulus() % 2 == 0 {
+            return Err(OddError::UnsupportedModulus {
+                operation: "mvb",
+                modulus: encoding_in_0.get_modulus(),
+            });
+        }
+        let o_0 = encoding_in_0.get_origin_modulus() as usize;
+
+        let mut values = lut_fi
+            .chunks(o_0)
+            .map(|first_function| {
+                Self::simulate_bootstrapping(x_0, encoding_in_0, encoding_out, representatives, first_function)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for selector in selectors.iter().rev() {
+            let (x, encoding_selector) = clear_value(selector)?;
+            let o = encoding_selector.get_origin_modulus() as usize;
+            values = values
+                .chunks(o)
+                .map(|group| {
+                    let windows = packed_windows(group, encoding_selector, encoding_out.get_modulus());
+                    blind_rotation(x, encoding_selector.get_modulus(), &windows, encoding_out.get_modulus())
+                })
+                .collect();
+        }
+        Ok(values[0])
+    }
+}
+
+impl OddBackend for SimulatedServerKey {
+    fn try_trivial_encrypt(&self, message: u64, encoding: &Encoding) -> Result<Ciphertext, OddError> {
+        let value = encoding.try_get_part_single_value_if_canonical(message)?;
+        Ok(Ciphertext::Trivial(value, encoding.clone()))
+    }
+
+    fn try_apply_lut_with_representatives(
+        &self,
+        input: &Ciphertext,
+        encoding_out: &Encoding,
+        representatives: &[u64],
+        f: &dyn Fn(u64) -> u64,
+    ) -> Result<Ciphertext, OddError> {
+        let (x, encoding_in) = clear_value(input)?;
+        let lut: Vec<u64> = (0..encoding_in.get_origin_modulus()).map(f).collect();
+        let value = Self::simulate_bootstrapping(x, encoding_in, encoding_out, representatives, &lut)?;
+        Ok(Ciphertext::Trivial(value, encoding_out.clone()))
+    }
+
+    fn try_mvb_with_representatives(
+        &self,
+        input: &Ciphertext,
+        encodings_out: &[Encoding],
+        representatives: &[Vec<u64>],
+        fis: &[Box<dyn Fn(u64) -> u64>],
+    ) -> Result<Vec<Ciphertext>, OddError> {
+        for len in [encodings_out.len(), representatives.len()] {
+            if len != fis.len() {
+                return Err(OddError::LengthMismatch { expected: fis.len(), got: len });
+            }
+        }
+        let (x, encoding_in) = clear_value(input)?;
+        if !encoding_in.is_valid() {
+            return Err(OddError::InvalidEncoding);
+        }
+        if encoding_in.get_modulus() % 2 == 0 {
+            return Err(OddError::UnsupportedModulus {
+                operation: "mvb",
+                modulus: encoding_in.get_modulus(),
+            });
+        }
+        encodings_out
+            .iter()
+            .zip(representatives)
+            .zip(fis)
+            .map(|((encoding_out, reps), fi)| {
+                let lut: Vec<u64> = (0..encoding_in.get_origin_modulus()).map(fi).collect();
+                let value = Self::simulate_bootstrapping(x, encoding_in, encoding_out, reps, &lut)?;
+                Ok(Ciphertext::Trivial(value, encoding_out.clone()))
+            })
+            .collect()
+    }
+
+    fn try_full_tree_bootstrapping(
+        &self,
+        inputs: &[Ciphertext],
+        encodings_out: &[Encoding],
+        t: u64,
+        f: &dyn Fn(u64) -> u64,
+    ) -> Result<Vec<Ciphertext>, OddError> {
+        if inputs.is_empty() || encodings_out.is_empty() {
+            return Err(OddError::EmptyInput);
+        }
+        let product = inputs.iter().map(|c| c.get_encoding().get_origin_modulus()).product::<u64>();
+        if product != t {
+            return Err(OddError::InvalidTreeModulus { expected: t, got: product });
+        }
+
+        let indices: Vec<u64> = (0..t).collect();
+        let lut_fis = digit_tables(encodings_out, f, &indices);
+        encodings_out
+            .iter()
+            .zip(&lut_fis)
+            .map(|(encoding_out, lut_fi)| {
+                let representatives = encoding_out.try_default_representatives()?;
+                let value = Self::simulate_tree_bootstrapping(inputs, encoding_out, &representatives, lut_fi)?;
+                Ok(Ciphertext::Trivial(value, encoding_out.clone()))
+            })
+            .collect()
+    }
+
+    fn try_exec_gadget(&self, inputs: &[Ciphertext], enc_inter: &Encoding, enc_out: &Encoding) -> Result<Ciphertext, OddError> {
+        let p = enc_inter.get_modulus();
+        if inputs.is_empty() {
+            return Err(OddError::EmptyInput);
+        }
+        let mut sum = 0u64;
+        for input in inputs {
+            let (x, encoding) = clear_value(input)?;
+            if encoding.get_modulus() != p {
+                return Err(OddError::InvalidEncoding);
+            }
+            sum = sum.wrapping_add(zp_to_torus(x, p));
+        }
+        let representatives = enc_out.try_default_representatives()?;
+        let windows = accumulator_windows(enc_inter, enc_out, &representatives)?;
+        let value = blind_rotation(torus_to_zp(sum, p), p, &windows, enc_out.get_modulus());
+        Ok(Ciphertext::Trivial(value, enc_out.clone()))
+    }
+
+    fn try_encoding_switching_mul_constant(&self, input: &Ciphertext, coefficient: u64) -> Result<Ciphertext, OddError> {
+        let (x, encoding) = clear_value(input)?;
+        let p = encoding.get_modulus();
+        Ok(Ciphertext::Trivial(
+            x * (coefficient % p) % p,
+            encoding.try_multiply_encoding_by_constant(coefficient)?,
+        ))
+    }
+
+    fn try_encoding_switching_sum_constant(&self, input: &Ciphertext, constant: u64, modulus: u64) -> Result<Ciphertext, OddError> {
+        let (x, encoding) = clear_value(input)?;
+        let sum = zp_to_torus(x, encoding.get_modulus()).wrapping_add(zp_to_torus(constant, modulus));
+        Ok(Ciphertext::Trivial(
+            torus_to_zp(sum, encoding.get_modulus()),
+            encoding.try_add_constant(constant)?,
+        ))
+    }
+
+    fn try_simple_sum(&self, input: &[Ciphertext]) -> Result<Ciphertext, OddError> {
+        let same_encoding = input.first().ok_or(OddError::EmptyInput)?.get_encoding();
+        let mut sum = 0u64;
+        for ciphertext in input {
+            let (x, encoding) = clear_value(ciphertext)?;
+            sum = sum.wrapping_add(zp_to_torus(x, encoding.get_modulus()));
+        }
+        Ok(Ciphertext::Trivial(
+            torus_to_zp(sum, same_encoding.get_modulus()),
+            same_encoding.clone(),
+        ))
+    }
+
+    fn try_simple_plaintext_sum(&self, input: &Ciphertext, constant: u64, modulus: u64) -> Result<Ciphertext, OddError> {
+        let (x, encoding) = clear_value(input)?;
+        let sum = zp_to_torus(x, encoding.get_modulus()).wrapping_add(zp_to_torus(constant, modulus));
+        Ok(Ciphertext::Trivial(torus_to_zp(sum, encoding.get_modulus()), encoding.clone()))
+    }
+
+    fn try_simple_mul_constant(&self, input: &Ciphertext, coeff: u64, modulus: u64) -> Result<Ciphertext, OddError> {
+        let (x, encoding) = clear_value(input)?;
+        let p = encoding.get_modulus();
+        Ok(Ciphertext::Trivial(x * (coeff % modulus % p) % p, encoding.clone()))
+    }
+
+    fn try_simple_negation(&self, input: &Ciphertext) -> Result<Ciphertext, OddError> {
+        let (x, encoding) = clear_value(input)?;
+        let p = encoding.get_modulus();
+        Ok(Ciphertext::Trivial((p - x) % p, encoding.clone()))
+    }
+}
+
+
+/// A small circuit mixing all the operators, written once for both backends
+#[cfg(test)]
+fn test_circuit<B: OddBackend>(backend: &B, inputs: &[Ciphertext]) -> Vec<Ciphertext> {
+    let nibble = Encoding::new_canonical(16, (0..16).collect(), 17);
+    let quarter = Encoding::new_canonical(4, (0..4).collect(), 8);
+    let f = |x: u64| (x * 13 + 5) % 256;
+
+    let digits = backend.full_tree_bootstrapping(inputs, &[quarter, nibble.clone()], 256, &f);
+    let fis: Vec<Box<dyn Fn(u64) -> u64>> = vec![Box::new(|x| x % 2), Box::new(|x| (x + 9) % 16)];
+    let mvb = backend.mvb(&digits[1], &[Encoding::parity_encoding(), nibble.clone()], &fis);
+    let lut = backend.apply_lut(&digits[0], &nibble, &|x| 3 * x);
+    let sum = backend.linear_combination(&[mvb[1].clone(), lut.clone()], &[1, 1], 17);
+    let switched = backend.encoding_switching_mul_constant(&lut, 2);
+    let shifted = backend.encoding_switching_sum_constant(&switched, 1, 17);
+    let back = backend.encoding_switching_lut(&shifted, &nibble);
+    vec![digits[0].clone(), digits[1].clone(), mvb[0].clone(), mvb[1].clone(), lut, sum, back]
+}
+
+
+#[test]
+fn test_simulation_matches_server_key() {
+    use crate::odd::TEST_PARAMETERS;
+
+    let (cks, sks) = gen_keys(&TEST_PARAMETERS);
+    let simulator = SimulatedServerKey::new();
+    let nibble = Encoding::new_canonical(16, (0..16).collect(), 17);
+    for x in [0x9d, 0x42] {
+        let inputs = vec![cks.encrypt_arithmetic(x / 16, &nibble), cks.encrypt_arithmetic(x % 16, &nibble)];
+        let clear_inputs = vec![simulator.trivial_encrypt(x / 16, &nibble), simulator.trivial_encrypt(x % 16, &nibble)];
+        let expected: Vec<u64> = test_circuit(&sks, &inputs).iter().map(|c| cks.decrypt(c)).collect();
+        let simulated = test_circuit(&simulator, &clear_inputs);
+        assert!(simulated.iter().all(Ciphertext::is_trivial));
+        let simulated: Vec<u64> = simulated.iter().map(|c| c.decrypt_trivial().unwrap()).collect();
+        assert_eq!(simulated, expected);
+    }
+
+    // the simulator does not accept encrypted inputs
+    let ct = cks.encrypt_arithmetic(1, &nibble);
+    assert_eq!(simulator.try_apply_lut(&ct, &nibble, &|x| x).unwrap_err(), OddError::NotTrivialCiphertext);
+}
+
+
+#[test]
+fn test_simulation_wrap_around() {
+    use crate::odd::TEST_PARAMETERS;
+
+    let (cks, sks) = gen_keys(&TEST_PARAMETERS);
+    let simulator = SimulatedServerKey::new();
+    let nibble = Encoding::new_canonical(16, (0..16).collect(), 17);
+    let padded = Encoding::new_canonical(2, vec![0, 1], 4);
+
+    // 1 + 1 overflows the padding bit: the blind rotation reads the opposite of the window of 0
+    let ct = cks.encrypt_arithmetic(1, &padded);
+    let overflow = sks.simple_sum(&[ct.clone(), ct]);
+    let res = sks.apply_lut(&overflow, &nibble, &|x| x + 1);
+    let undecodable = OddError::UndecodableValue { value: 16, modulus: 17 };
+    assert_eq!(cks.try_decrypt(&res).unwrap_err(), undecodable);
+
+    let clear = simulator.trivial_encrypt(1, &padded);
+    let overflow = simulator.simple_sum(&[clear.clone(), clear]);
+    let res = simulator.apply_lut(&overflow, &nibble, &|x| x + 1);
+    assert!(matches!(res, Ciphertext::Trivial(16, _)));
+    assert_eq!(res.decrypt_trivial().unwrap_err(), undecodable);
+}
@@ -675,3 +675,104 @@ mod test_shortint {
         assert!(safe_deserialize::<Baz>(foo_ser.as_slice(), 0x1000).is_err());
     }
 }
+
+#[cfg(all(test, feature = "odd"))]
+mod test_odd {
+    use crate::odd::prelude::*;
+    use crate::odd::TEST_PARAMETERS;
+
+    use super::*;
+
+    #[test]
+    fn safe_serialization_odd_ct_versioned() {
+        let (ck, _sk) = gen_keys(&TEST_PARAMETERS);
+
+        let encoding = Encoding::new_canonical(4, vec![0, 1, 2, 3], 9);
+        let msg = 2_u64;
+
+        let ct = ck.encrypt_arithmetic(msg, &encoding);
+
+        let mut buffer = vec![];
+
+        let config = SerializationConfig::new(1 << 20);
+
+        let size = config.serialized_size(&ct).unwrap();
+        config.serialize_into(&ct, &mut buffer).unwrap();
+        assert_eq!(size as usize, buffer.len());
+
+        assert!(safe_deserialize::<Ciphertext>(buffer.as_slice(), size - 1).is_err());
+        assert!(safe_deserialize::<Encoding>(buffer.as_slice(), size).is_err());
+
+        let ct2: Ciphertext = safe_deserialize(buffer.as_slice(), size).unwrap();
+
+        let dec = ck.decrypt(&ct2);
+        assert_eq!(msg, dec);
+    }
+
+    #[test]
+    fn safe_serialization_odd_keys() {
+        let (ck, sk) = gen_keys(&TEST_PARAMETERS);
+
+        let mut ck_buffer = vec![];
+        safe_serialize(&ck, &mut ck_buffer, 1 << 30).unwrap();
+        let ck2: ClientKey = safe_deserialize(ck_buffer.as_slice(), 1 << 30).unwrap();
+        assert_eq!(ck, ck2);
+
+        let mut sk_buffer = vec![];
+        safe_serialize(&sk, &mut sk_buffer, 1 << 30).unwrap();
+        let sk2: ServerKey = safe_deserialize(sk_buffer.as_slice(), 1 << 30).unwrap();
+
+        let encoding = Encoding::new_canonical(4, vec![0, 1, 2, 3], 9);
+        let ct = ck2.encrypt_arithmetic(1, &encoding);
+        let res = sk2.apply_lut(&ct, &encoding, &|x| (3 * x) % 4);
+        assert_eq!(ck.decrypt(&res), 3);
+    }
+
+    #[test]
+    fn safe_deserialization_odd_conformant() {
+        let (ck, sk) = gen_keys(&TEST_PARAMETERS);
+
+        let mut other_parameters = TEST_PARAMETERS;
+        other_parameters.polynomial_size = PolynomialSize(2048);
+
+        let encoding = Encoding::new_canonical(4, vec![0, 1, 2, 3], 9);
+        let msg = 3_u64;
+        let ct = ck.encrypt_arithmetic(msg, &encoding);
+
+        let mut ct_buffer = vec![];
+        safe_serialize(&ct, &mut ct_buffer, 1 << 20).unwrap();
+
+        assert!(safe_deserialize_conformant::<Ciphertext>(
+            ct_buffer.as_slice(),
+            1 << 20,
+            &other_parameters
+        )
+        .is_err());
+
+        let ct2: Ciphertext =
+            safe_deserialize_conformant(ct_buffer.as_slice(), 1 << 20, &TEST_PARAMETERS).unwrap();
+        assert_eq!(ck.decrypt(&ct2), msg);
+
+        let mut sk_buffer = vec![];
+        safe_serialize(&sk, &mut sk_buffer, 1 << 30).unwrap();
+
+        assert!(safe_deserialize_conformant::<ServerKey>(
+            sk_buffer.as_slice(),
+            1 << 30,
+            &other_parameters
+        )
+        .is_err());
+
+        let mut small_key_parameters = TEST_PARAMETERS;
+        small_key_parameters.encryption_key_choice = EncryptionKeyChoice::Small;
+        assert!(safe_deserialize_conformant::<ServerKey>(
+            sk_buffer.as_slice(),
+            1 << 30,
+            &small_key_parameters
+        )
+        .is_err());
+
+        let _: ServerKey =
+            safe_deserialize_conformant(sk_buffer.as_slice(), 1 << 30, &TEST_PARAMETERS).unwrap();
+    }
+}
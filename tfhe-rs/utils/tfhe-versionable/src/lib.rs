@@ -979,6 +979,34 @@ impl<T: Unversionize + std::hash::Hash + Eq> Unversionize for HashSet<T> {
     }
 }
 
+impl<T: Versionize> VersionizeSlice for HashSet<T> {
+    type VersionedSlice<'vers>
+        = Vec<Vec<T::Versioned<'vers>>>
+    where
+        T: 'vers;
+
+    fn versionize_slice(slice: &[Self]) -> Self::VersionedSlice<'_> {
+        slice.iter().map(|set| set.versionize()).collect()
+    }
+}
+
+impl<T: VersionizeOwned> VersionizeVec for HashSet<T> {
+    type VersionedVec = Vec<Vec<T::VersionedOwned>>;
+
+    fn versionize_vec(vec: Vec<Self>) -> Self::VersionedVec {
+        vec.into_iter().map(|set| set.versionize_owned()).collect()
+    }
+}
+
+impl<T: Unversionize + std::hash::Hash + Eq> UnversionizeVec for HashSet<T> {
+    fn unversionize_vec(versioned: Self::VersionedVec) -> Result<Vec<Self>, UnversionizeError> {
+        versioned
+            .into_iter()
+            .map(HashSet::<T>::unversionize)
+            .collect()
+    }
+}
+
 // converts to `Vec<(K::Versioned, V::Versioned)>` for the versioned type, so we don't have to
 // derive Eq/Hash on it.
 impl<K: Versionize, V: Versionize> Versionize for HashMap<K, V> {